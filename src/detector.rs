@@ -1,4 +1,5 @@
 use crate::input::IqSample;
+use rayon::prelude::*;
 use rustfft::{FftPlanner, num_complex::Complex};
 use std::f32::consts::PI;
 
@@ -21,6 +22,19 @@ pub fn blackman_window(size: usize) -> Vec<f32> {
 pub struct Segment {
     pub start_sample: usize,
     pub end_sample: usize,
+    /// Strongest power profile frame within the segment (dB)
+    pub peak_power_db: f32,
+    /// Peak minus mean frame power (dB): near zero for steady carriers, high for bursty traffic
+    pub crest_db: Option<f32>,
+    /// Frequency extent, when detected in the time-frequency plane
+    pub band: Option<Band>,
+}
+
+/// Occupied frequency range of a segment, relative to the tuned frequency
+#[derive(Debug, Clone, Copy)]
+pub struct Band {
+    pub center_hz: f64,
+    pub bandwidth_hz: f64,
 }
 
 impl Segment {
@@ -31,17 +45,94 @@ impl Segment {
     pub fn duration_ms(&self, sample_rate: u32) -> f32 {
         self.duration_samples() as f32 / sample_rate as f32 * 1000.0
     }
+
+    /// Range of power profile frames whose windows fall within this segment
+    pub fn frame_range(&self, window_size: usize, num_frames: usize) -> std::ops::Range<usize> {
+        let hop_size = (window_size / 2).max(1); // Must match calculate_power_profile
+        let first = (self.start_sample / hop_size).min(num_frames);
+        let last = (self.end_sample.saturating_sub(window_size) / hop_size + 1).clamp(first, num_frames);
+        if last == first {
+            first..(first + 1).min(num_frames)
+        } else {
+            first..last
+        }
+    }
+}
+
+/// Standard deviation of the power profile (dB) over a segment's frames
+/// Unmodulated carriers and birdies hold nearly constant power, so this stays close to zero
+/// The outer 10% of frames on each side is skipped so on/off ramps don't count as variation
+pub fn segment_power_std_db(segment: &Segment, profile: &[f32], window_size: usize) -> f32 {
+    let frames = &profile[segment.frame_range(window_size, profile.len())];
+    let edge = frames.len() / 10;
+    let frames = &frames[edge..frames.len() - edge];
+    if frames.len() < 2 {
+        return 0.0;
+    }
+    let mean = frames.iter().sum::<f32>() / frames.len() as f32;
+    let variance = frames.iter().map(|p| (p - mean).powi(2)).sum::<f32>() / frames.len() as f32;
+    variance.sqrt()
+}
+
+/// Peak minus mean power (dB) of the profile frames covering a segment, the mean taken in linear power
+/// None when the segment covers no finite frames
+pub fn segment_crest_db(segment: &Segment, profile: &[f32], window_size: usize) -> Option<f32> {
+    let frames: Vec<f32> = profile[segment.frame_range(window_size, profile.len())]
+        .iter()
+        .copied()
+        .filter(|p| p.is_finite())
+        .collect();
+    let peak = frames.iter().copied().reduce(f32::max)?;
+    let mean = frames.iter().map(|p| 10f64.powf(*p as f64 / 10.0)).sum::<f64>() / frames.len() as f64;
+    Some(peak - 10.0 * mean.log10() as f32)
+}
+
+/// Half-width in unpadded bins of the band ignored around each notch: the Blackman window's main lobe,
+/// so a tone anywhere near the notch frequency leaks into no remaining bin above its sidelobes
+const NOTCH_HALF_WIDTH_BINS: f64 = 3.0;
+
+/// How a frame's spectrum is reduced to a single power value for detection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerMetric {
+    /// Strongest single bin; best for narrowband bursts
+    Peak,
+    /// Sum over all bins; catches wideband signals with no dominant bin
+    Total,
+}
+
+/// Calculate FFT power in dB for a window of samples, as the peak bin or the total over all bins
+/// The peak finds the strongest signal in any frequency bin, much better for narrowband bursts
+/// Applies window function to reduce spectral leakage; bins fewer than `dc_guard` from DC are skipped
+/// either way (1 skips the DC bin alone). Bins within the window's main lobe of any `notches`
+/// frequency (fractions of the sample rate) are ignored too.
+pub fn calculate_power_db(
+    samples: &[IqSample],
+    window: &[f32],
+    metric: PowerMetric,
+    notches: &[f64],
+    dc_guard: usize,
+    planner: &mut FftPlanner<f32>,
+) -> f32 {
+    calculate_power_db_padded(samples, window, samples.len(), metric, notches, dc_guard, planner)
 }
 
-/// Calculate peak FFT bin power in dB for a window of samples
-/// This finds the strongest signal in any frequency bin, much better for narrowband bursts
-/// Applies window function to reduce spectral leakage
-pub fn calculate_peak_power_db(samples: &[IqSample], window: &[f32], planner: &mut FftPlanner<f32>) -> f32 {
+/// Like `calculate_power_db`, zero-padding the windowed samples to `fft_size` points
+/// A power-of-two size avoids rustfft's slower mixed-radix and Bluestein paths. Levels stay comparable
+/// with the unpadded measure; padding only interpolates the spectrum between the original bins.
+pub fn calculate_power_db_padded(
+    samples: &[IqSample],
+    window: &[f32],
+    fft_size: usize,
+    metric: PowerMetric,
+    notches: &[f64],
+    dc_guard: usize,
+    planner: &mut FftPlanner<f32>,
+) -> f32 {
     if samples.is_empty() {
         return f32::NEG_INFINITY;
     }
 
-    let fft_size = samples.len();
+    let fft_size = fft_size.max(samples.len());
     let fft = planner.plan_fft_forward(fft_size);
 
     // Apply window and convert to complex
@@ -50,45 +141,251 @@ pub fn calculate_peak_power_db(samples: &[IqSample], window: &[f32], planner: &m
         .zip(window.iter())
         .map(|(s, w)| Complex::new(s.i * w, s.q * w))
         .collect();
+    buffer.resize(fft_size, Complex::new(0.0, 0.0));
 
     // Compute FFT
     fft.process(&mut buffer);
 
-    // Find peak magnitude (skip DC bin)
-    let peak_power = buffer
+    // Find peak magnitude or total energy (skip bins near DC and notched bins)
+    let notch_width = NOTCH_HALF_WIDTH_BINS / samples.len() as f64;
+    let bins = buffer
         .iter()
-        .skip(1)
-        .map(|c| c.norm_sqr())
-        .fold(0.0f32, f32::max);
+        .enumerate()
+        .filter(|(k, _)| (*k).min(fft_size - k) >= dc_guard)
+        .filter(|(k, _)| {
+            let freq = *k as f64 / fft_size as f64;
+            let freq = if freq >= 0.5 { freq - 1.0 } else { freq };
+            !notches.iter().any(|notch| (freq - notch).abs() < notch_width)
+        })
+        .map(|(_, c)| c.norm_sqr());
+    let power = match metric {
+        PowerMetric::Peak => bins.fold(0.0f32, f32::max),
+        PowerMetric::Total => bins.sum(),
+    };
 
-    // Normalize by FFT size and convert to dB
-    let normalized_power = peak_power / (fft_size * fft_size) as f32;
-    10.0 * normalized_power.log10()
+    // Normalize by FFT size and convert to dB; a bin's amplitude scales with the samples transformed,
+    // while the total over all bins also grows with the padded length
+    let len = samples.len() as f32;
+    let normalized_power = match metric {
+        PowerMetric::Peak => power / (len * len),
+        PowerMetric::Total => power / (fft_size as f32 * len),
+    };
+    let power_db = 10.0 * normalized_power.log10();
+    if power_db.is_finite() {
+        power_db
+    } else {
+        f32::NEG_INFINITY
+    }
 }
 
-/// Calculate peak power profile over time using FFT with 50% overlap
-/// Uses Blackman window for reduced spectral leakage
-fn calculate_peak_power_profile(samples: &[IqSample], window_size: usize) -> Vec<f32> {
+/// Calculate power profile over time using FFT with 50% overlap
+/// Uses Blackman window for reduced spectral leakage; frames are computed in parallel
+pub fn calculate_power_profile(
+    samples: &[IqSample],
+    window_size: usize,
+    metric: PowerMetric,
+    notches: &[f64],
+    dc_guard: usize,
+) -> Vec<f32> {
     if samples.len() < window_size {
         let window = blackman_window(samples.len());
         let mut planner = FftPlanner::new();
-        return vec![calculate_peak_power_db(samples, &window, &mut planner)];
+        return vec![calculate_power_db(samples, &window, metric, notches, dc_guard, &mut planner)];
     }
 
     let window = blackman_window(window_size);
-    let mut planner = FftPlanner::new();
     let hop_size = window_size / 2; // 50% overlap
     let num_frames = (samples.len().saturating_sub(window_size)) / hop_size + 1;
-    let mut profile = Vec::with_capacity(num_frames);
 
-    for i in 0..num_frames {
-        let start = i * hop_size;
-        let end = (start + window_size).min(samples.len());
-        if end - start == window_size {
-            profile.push(calculate_peak_power_db(&samples[start..end], &window, &mut planner));
-        }
+    // Frames are independent, so spread them across threads with one planner per worker
+    (0..num_frames)
+        .into_par_iter()
+        .map_init(FftPlanner::new, |planner, i| {
+            let start = i * hop_size;
+            calculate_power_db(&samples[start..start + window_size], &window, metric, notches, dc_guard, planner)
+        })
+        .collect()
+}
+
+/// Power in dB at `freq_hz` (offset from the tuned center) using the Goertzel algorithm
+/// Equivalent to a single DFT bin at an arbitrary frequency, normalized like the Peak metric
+pub fn goertzel_power_db(samples: &[IqSample], window: &[f32], freq_hz: f64, sample_rate: u32) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+
+    let omega = 2.0 * std::f64::consts::PI * freq_hz / sample_rate as f64;
+    let coeff = 2.0 * omega.cos() as f32;
+    let mut s1 = Complex::new(0.0f32, 0.0);
+    let mut s2 = Complex::new(0.0f32, 0.0);
+    for (s, w) in samples.iter().zip(window.iter()) {
+        let s0 = Complex::new(s.i * w, s.q * w) + s1 * coeff - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    // Final step of the filter; its phase differs from the DFT bin but the magnitude matches
+    let rotation = Complex::from_polar(1.0f32, -omega as f32);
+    let power = (s1 - rotation * s2).norm_sqr();
+
+    let len = samples.len() as f32;
+    let power_db = 10.0 * (power / (len * len)).log10();
+    if power_db.is_finite() {
+        power_db
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// Power profile at a single frequency over time, with the same framing as `calculate_power_profile`
+pub fn goertzel_power_profile(samples: &[IqSample], window_size: usize, freq_hz: f64, sample_rate: u32) -> Vec<f32> {
+    if samples.len() < window_size {
+        let window = blackman_window(samples.len());
+        return vec![goertzel_power_db(samples, &window, freq_hz, sample_rate)];
+    }
+
+    let window = blackman_window(window_size);
+    let hop_size = window_size / 2; // Must match calculate_power_profile
+    let num_frames = (samples.len().saturating_sub(window_size)) / hop_size + 1;
+    (0..num_frames)
+        .map(|i| {
+            let start = i * hop_size;
+            goertzel_power_db(&samples[start..start + window_size], &window, freq_hz, sample_rate)
+        })
+        .collect()
+}
+
+/// Centered moving average of a power profile over `frames` frames
+/// Averages linear power so silent (-inf dB) frames pull the mean down instead of poisoning it;
+/// the window shrinks at the edges so the output has the same length as the input
+pub fn smooth_profile(power_profile: &[f32], frames: usize) -> Vec<f32> {
+    if frames <= 1 || power_profile.is_empty() {
+        return power_profile.to_vec();
+    }
+
+    let mut prefix = Vec::with_capacity(power_profile.len() + 1);
+    prefix.push(0.0f64);
+    for &db in power_profile {
+        let last = *prefix.last().unwrap();
+        prefix.push(last + 10f64.powf(db as f64 / 10.0));
+    }
+
+    let before = (frames - 1) / 2;
+    let after = frames / 2;
+    (0..power_profile.len())
+        .map(|idx| {
+            let start = idx.saturating_sub(before);
+            let end = (idx + after + 1).min(power_profile.len());
+            let mean = (prefix[end] - prefix[start]) / (end - start) as f64;
+            (10.0 * mean.log10()) as f32
+        })
+        .collect()
+}
+
+/// Spectral flatness per frame: geometric over arithmetic mean of the windowed power spectrum
+/// Near 1 for white noise and near 0 for tones or other structured signals, independent of level.
+/// Frames match `calculate_power_profile`; the DC bin is skipped and silent frames count as flat.
+pub fn spectral_flatness_profile(samples: &[IqSample], window_size: usize) -> Vec<f32> {
+    if samples.len() < window_size || window_size < 2 {
+        return vec![];
+    }
+
+    let window = blackman_window(window_size);
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(window_size);
+    let hop_size = window_size / 2; // Must match calculate_power_profile
+    let num_frames = (samples.len() - window_size) / hop_size + 1;
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); window_size];
+
+    (0..num_frames)
+        .map(|frame| {
+            let start = frame * hop_size;
+            for ((slot, s), w) in buffer.iter_mut().zip(&samples[start..start + window_size]).zip(&window) {
+                *slot = Complex::new(s.i * w, s.q * w);
+            }
+            fft.process(&mut buffer);
+
+            let bins = &buffer[1..];
+            let arithmetic = bins.iter().map(|c| c.norm_sqr() as f64).sum::<f64>() / bins.len() as f64;
+            if arithmetic <= 0.0 {
+                return 1.0;
+            }
+            // Floor each bin far below the mean so an exactly-zero bin doesn't send the log to -inf
+            let floor = arithmetic * 1e-12;
+            let log_mean = bins.iter().map(|c| (c.norm_sqr() as f64).max(floor).ln()).sum::<f64>() / bins.len() as f64;
+            (log_mean.exp() / arithmetic) as f32
+        })
+        .collect()
+}
+
+/// Detect segments whose spectral flatness stays below `max_flatness` (structured rather than noise-like)
+/// Peak power for each segment is taken from `power_profile`, which must share the flatness frames
+#[allow(clippy::too_many_arguments)]
+pub fn detect_segments_flatness(
+    flatness: &[f32],
+    power_profile: &[f32],
+    total_samples: usize,
+    window_size: usize,
+    max_flatness: f32,
+    min_duration_samples: usize,
+    max_gap_samples: usize,
+) -> Vec<Segment> {
+    let presence: Vec<bool> = flatness.iter().map(|&f| f < max_flatness).collect();
+    let hop_size = window_size / 2; // Must match calculate_power_profile
+
+    let segments = presence_segments(&presence, hop_size, window_size, total_samples);
+    finalize_segments(segments, power_profile, window_size, min_duration_samples, max_gap_samples)
+}
+
+/// Detection algorithm applied to the power profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetectorMode {
+    /// Global auto-threshold with hysteresis
+    Threshold,
+    /// Cell-averaging CFAR with guard/training cells and false-alarm probability
+    Cfar { guard: usize, train: usize, pfa: f32 },
+    /// Normalized cross-correlation against a reference waveform exceeding `threshold` (0-1)
+    Matched { threshold: f32 },
+    /// Connected regions of the spectrogram exceeding each bin's noise floor by `margin_db`
+    Spectrogram { margin_db: f32 },
+    /// Frames whose spectral flatness falls below `max_flatness` (0-1; white noise is ~0.56)
+    Flatness { max_flatness: f32 },
+    /// Global auto-threshold on the power at a single frequency (Hz from center), via Goertzel
+    Goertzel { target_hz: f64 },
+}
+
+/// How `auto_threshold` estimates the noise floor from the power profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseEstimator {
+    /// 10th percentile of frame powers
+    Percentile,
+    /// Most common power level (histogram mode); robust when signals are frequent
+    Mode,
+}
+
+/// Histogram bin width in dB for mode-based noise floor estimation
+const NOISE_HISTOGRAM_BIN_DB: f32 = 1.0;
+
+/// Estimate the noise floor as the center of the most populated power histogram bin
+/// Counts are summed over neighboring bins so a noise cluster spread over a few dB
+/// isn't outvoted by a steady carrier concentrated in a single bin
+pub fn histogram_mode(power_profile: &[f32]) -> Option<f32> {
+    let finite: Vec<f32> = power_profile.iter().copied().filter(|p| p.is_finite()).collect();
+    let min = finite.iter().copied().reduce(f32::min)?;
+    let max = finite.iter().copied().reduce(f32::max)?;
+
+    let num_bins = ((max - min) / NOISE_HISTOGRAM_BIN_DB) as usize + 1;
+    let mut counts = vec![0usize; num_bins];
+    for p in &finite {
+        counts[((p - min) / NOISE_HISTOGRAM_BIN_DB) as usize] += 1;
     }
-    profile
+
+    // First (lowest-power) bin wins ties
+    let mode_bin = (0..num_bins)
+        .map(|bin| counts[bin.saturating_sub(1)..(bin + 2).min(num_bins)].iter().sum::<usize>())
+        .enumerate()
+        .fold((0, 0), |best, (bin, count)| if count > best.1 { (bin, count) } else { best })
+        .0;
+    Some(min + (mode_bin as f32 + 0.5) * NOISE_HISTOGRAM_BIN_DB)
 }
 
 /// Result of auto-threshold analysis
@@ -96,77 +393,130 @@ pub struct ThresholdAnalysis {
     pub threshold: f32,
     pub noise_floor: f32,
     pub p95: f32,
+    /// Profile extremes, mean and standard deviation (dB), all over finite frames only
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub std_dev: f32,
 }
 
 /// Auto-detect threshold based on noise floor analysis
 /// Uses FFT peak power and percentile approach for narrowband burst detection
-pub fn auto_threshold(samples: &[IqSample], window_size: usize) -> ThresholdAnalysis {
-    let mut power_profile = calculate_peak_power_profile(samples, window_size);
+pub fn auto_threshold(power_profile: &[f32], estimator: NoiseEstimator) -> ThresholdAnalysis {
+    // Silent (-inf) and corrupt (NaN) frames carry no level and would drag the percentiles to -inf
+    let mut levels: Vec<f32> = power_profile.iter().copied().filter(|p| p.is_finite()).collect();
 
-    if power_profile.is_empty() {
+    if levels.is_empty() {
         return ThresholdAnalysis {
             threshold: -60.0,
             noise_floor: -70.0,
             p95: -50.0,
+            min: f32::NEG_INFINITY,
+            max: f32::NEG_INFINITY,
+            mean: f32::NEG_INFINITY,
+            std_dev: 0.0,
         };
     }
 
     // Sort to find percentiles
-    power_profile.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    levels.sort_by(|a, b| a.total_cmp(b));
 
-    // Find 10th percentile as noise floor estimate (lowest power periods)
-    let p10_idx = (power_profile.len() as f32 * 0.10) as usize;
-    let noise_floor = power_profile[p10_idx];
+    let noise_floor = match estimator {
+        NoiseEstimator::Percentile => {
+            // Find 10th percentile as noise floor estimate (lowest power periods)
+            let p10_idx = (levels.len() as f32 * 0.10) as usize;
+            levels[p10_idx]
+        }
+        NoiseEstimator::Mode => histogram_mode(&levels).unwrap_or(levels[0]),
+    };
 
     // Find 99th percentile to catch burst peaks
-    let p99_idx = (power_profile.len() as f32 * 0.99) as usize;
-    let p95 = power_profile[p99_idx.min(power_profile.len() - 1)];
+    let p99_idx = (levels.len() as f32 * 0.99) as usize;
+    let p95 = levels[p99_idx.min(levels.len() - 1)];
 
     // Threshold is 70% of the way from noise floor to peak
     // This catches bursts while rejecting noise
     let threshold = noise_floor + (p95 - noise_floor) * 0.7;
 
+    let mean = levels.iter().map(|&p| p as f64).sum::<f64>() / levels.len() as f64;
+    let variance = levels.iter().map(|&p| (p as f64 - mean).powi(2)).sum::<f64>() / levels.len() as f64;
+    let (mean, std_dev) = (mean as f32, variance.sqrt() as f32);
+
     ThresholdAnalysis {
         threshold,
         noise_floor,
         p95,
+        min: levels[0],
+        max: levels[levels.len() - 1],
+        mean,
+        std_dev,
     }
 }
 
 /// Detect transmission segments based on power threshold
 /// Uses FFT peak power with 50% overlap and hysteresis: triggers ON at threshold, OFF at threshold - 3dB
+/// State changes require `debounce` consecutive frames past the relevant threshold
+#[allow(clippy::too_many_arguments)]
 pub fn detect_segments(
-    samples: &[IqSample],
+    power_profile: &[f32],
+    total_samples: usize,
     window_size: usize,
     threshold_db: f32,
     min_duration_samples: usize,
     max_gap_samples: usize,
+    debounce: usize,
 ) -> Vec<Segment> {
-    let power_profile = calculate_peak_power_profile(samples, window_size);
-
     if power_profile.is_empty() {
         return vec![];
     }
 
-    let hop_size = window_size / 2; // Must match calculate_peak_power_profile
+    let hop_size = window_size / 2; // Must match calculate_power_profile
     let threshold_on = threshold_db;
     let threshold_off = threshold_db - 3.0; // Hysteresis
+    let debounce = debounce.max(1);
 
     let mut segments = Vec::new();
     let mut in_transmission = false;
     let mut start_idx = 0;
 
+    // Consecutive frames past the threshold for the pending state change
+    let mut run_length = 0;
+    let mut run_start = 0;
+
     for (idx, &power) in power_profile.iter().enumerate() {
-        if !in_transmission && power > threshold_on {
+        let crossing = if in_transmission {
+            power < threshold_off
+        } else {
+            power > threshold_on
+        };
+
+        if !crossing {
+            run_length = 0;
+            continue;
+        }
+
+        if run_length == 0 {
+            run_start = idx;
+        }
+        run_length += 1;
+        if run_length < debounce {
+            continue;
+        }
+        run_length = 0;
+
+        if !in_transmission {
             // Start of transmission
             in_transmission = true;
-            start_idx = idx;
-        } else if in_transmission && power < threshold_off {
+            start_idx = run_start;
+        } else {
             // End of transmission
             in_transmission = false;
             segments.push(Segment {
                 start_sample: start_idx * hop_size,
-                end_sample: idx * hop_size + window_size,
+                end_sample: run_start * hop_size + window_size,
+                peak_power_db: f32::NEG_INFINITY,
+                crest_db: None,
+                band: None,
             });
         }
     }
@@ -175,22 +525,514 @@ pub fn detect_segments(
     if in_transmission {
         segments.push(Segment {
             start_sample: start_idx * hop_size,
-            end_sample: samples.len(),
+            end_sample: total_samples,
+            peak_power_db: f32::NEG_INFINITY,
+            crest_db: None,
+            band: None,
         });
     }
 
-    // Merge segments that are close together
-    let merged = merge_segments(segments, max_gap_samples);
+    finalize_segments(segments, power_profile, window_size, min_duration_samples, max_gap_samples)
+}
+
+/// Cell-averaging CFAR threshold (dB) for each frame of a power profile
+/// Each frame's threshold is the mean linear power of `train` cells on either side,
+/// skipping `guard` cells adjacent to it, scaled by a factor derived from the desired
+/// false-alarm probability so the threshold adapts to local band activity.
+pub fn cfar_thresholds(power_profile: &[f32], guard: usize, train: usize, pfa: f32) -> Vec<f32> {
+    let linear: Vec<f64> = power_profile.iter().map(|&db| 10f64.powf(db as f64 / 10.0)).collect();
+    let len = linear.len();
+
+    // Prefix sums make each training-cell mean O(1) regardless of window length
+    let mut prefix = Vec::with_capacity(len + 1);
+    prefix.push(0.0f64);
+    for &p in &linear {
+        prefix.push(prefix.last().unwrap() + p);
+    }
+
+    (0..len)
+        .map(|idx| {
+            let leading = idx.saturating_sub(guard + train)..idx.saturating_sub(guard);
+            let lagging = (idx + guard + 1).min(len)..(idx + guard + train + 1).min(len);
+
+            let count = leading.len() + lagging.len();
+            if count == 0 {
+                return f32::INFINITY;
+            }
+            let sum = (prefix[leading.end] - prefix[leading.start]) + (prefix[lagging.end] - prefix[lagging.start]);
+
+            // CA-CFAR scaling: alpha = N * (Pfa^(-1/N) - 1)
+            let n = count as f64;
+            let alpha = n * ((pfa as f64).powf(-1.0 / n) - 1.0);
+
+            (10.0 * (alpha * sum / n).log10()) as f32
+        })
+        .collect()
+}
+
+/// Cell-averaging CFAR presence decision for each frame of a power profile
+pub fn cfar_presence(power_profile: &[f32], guard: usize, train: usize, pfa: f32) -> Vec<bool> {
+    power_profile
+        .iter()
+        .zip(cfar_thresholds(power_profile, guard, train, pfa))
+        .map(|(&power, threshold)| power > threshold)
+        .collect()
+}
+
+/// Detect transmission segments using a cell-averaging CFAR detector
+/// Adapts to local noise level instead of a single global threshold
+#[allow(clippy::too_many_arguments)]
+pub fn detect_segments_cfar(
+    power_profile: &[f32],
+    total_samples: usize,
+    window_size: usize,
+    guard: usize,
+    train: usize,
+    pfa: f32,
+    min_duration_samples: usize,
+    max_gap_samples: usize,
+) -> Vec<Segment> {
+    let presence = cfar_presence(power_profile, guard, train, pfa);
+    let hop_size = window_size / 2; // Must match calculate_power_profile
+
+    let segments = presence_segments(&presence, hop_size, window_size, total_samples);
+    finalize_segments(segments, power_profile, window_size, min_duration_samples, max_gap_samples)
+}
+
+/// Convert runs of present frames into segments
+/// `extent` is how many samples past its start a frame's evidence covers
+fn presence_segments(presence: &[bool], hop_size: usize, extent: usize, total_samples: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut start_idx = None;
+
+    for (idx, &present) in presence.iter().chain(std::iter::once(&false)).enumerate() {
+        match (start_idx, present) {
+            (None, true) => start_idx = Some(idx),
+            (Some(start), false) => {
+                segments.push(Segment {
+                    start_sample: start * hop_size,
+                    end_sample: ((idx - 1) * hop_size + extent).min(total_samples),
+                    peak_power_db: f32::NEG_INFINITY,
+                    crest_db: None,
+                    band: None,
+                });
+                start_idx = None;
+            }
+            _ => {}
+        }
+    }
+
+    segments
+}
+
+/// Normalized cross-correlation of `samples` against `template`, reduced to one value per hop
+/// Each value is the maximum |<x, t>| / (|x| |t|) over lags starting within that hop, in [0, 1].
+/// Computed block-wise with overlap-save FFT correlation.
+pub fn matched_filter_profile(samples: &[IqSample], template: &[IqSample], hop_size: usize) -> Vec<f32> {
+    let template_len = template.len();
+    if template_len == 0 || samples.len() < template_len {
+        return vec![];
+    }
+    let num_lags = samples.len() - template_len + 1;
+
+    let template_energy: f64 = template.iter().map(|s| (s.i * s.i + s.q * s.q) as f64).sum();
+    if template_energy == 0.0 {
+        return vec![0.0; num_lags.div_ceil(hop_size)];
+    }
+
+    // Sliding window energy of the input via prefix sums
+    let mut energy_prefix = Vec::with_capacity(samples.len() + 1);
+    energy_prefix.push(0.0f64);
+    for s in samples {
+        energy_prefix.push(energy_prefix.last().unwrap() + (s.i * s.i + s.q * s.q) as f64);
+    }
+
+    let fft_size = (template_len * 4).next_power_of_two().max(8192);
+    let step = fft_size - template_len + 1;
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(fft_size);
+    let ifft = planner.plan_fft_inverse(fft_size);
+
+    let mut template_spectrum: Vec<Complex<f32>> = template
+        .iter()
+        .map(|s| Complex::new(s.i, s.q))
+        .chain(std::iter::repeat(Complex::new(0.0, 0.0)))
+        .take(fft_size)
+        .collect();
+    fft.process(&mut template_spectrum);
+
+    let mut correlation = Vec::with_capacity(num_lags);
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    let mut block_start = 0;
+    while block_start < num_lags {
+        for (k, slot) in buffer.iter_mut().enumerate() {
+            *slot = samples
+                .get(block_start + k)
+                .map(|s| Complex::new(s.i, s.q))
+                .unwrap_or(Complex::new(0.0, 0.0));
+        }
+        fft.process(&mut buffer);
+        for (x, t) in buffer.iter_mut().zip(&template_spectrum) {
+            *x *= t.conj();
+        }
+        ifft.process(&mut buffer);
 
-    // Filter out short segments
+        // Lags 0..step are free of circular wraparound
+        for (n, c) in buffer.iter().take(step.min(num_lags - block_start)).enumerate() {
+            let lag = block_start + n;
+            let window_energy = energy_prefix[lag + template_len] - energy_prefix[lag];
+            let norm = (window_energy * template_energy).sqrt() * fft_size as f64;
+            let ncc = if norm > 0.0 { (c.norm() as f64 / norm) as f32 } else { 0.0 };
+            correlation.push(ncc.min(1.0));
+        }
+        block_start += step;
+    }
+
+    correlation
+        .chunks(hop_size.max(1))
+        .map(|chunk| chunk.iter().copied().fold(0.0f32, f32::max))
+        .collect()
+}
+
+/// Detect occurrences of a reference waveform via normalized cross-correlation
+/// Each segment spans from the first matching lag to the end of the last match
+#[allow(clippy::too_many_arguments)]
+pub fn detect_segments_matched(
+    correlation_profile: &[f32],
+    power_profile: &[f32],
+    total_samples: usize,
+    window_size: usize,
+    template_len: usize,
+    match_threshold: f32,
+    min_duration_samples: usize,
+    max_gap_samples: usize,
+) -> Vec<Segment> {
+    let presence: Vec<bool> = correlation_profile.iter().map(|&c| c > match_threshold).collect();
+    let hop_size = window_size / 2; // Must match calculate_power_profile
+
+    let segments = presence_segments(&presence, hop_size, hop_size + template_len - 1, total_samples);
+    finalize_segments(segments, power_profile, window_size, min_duration_samples, max_gap_samples)
+}
+
+/// Merge nearby segments, drop any shorter than the minimum duration,
+/// and record each segment's peak power from the profile
+fn finalize_segments(
+    segments: Vec<Segment>,
+    power_profile: &[f32],
+    window_size: usize,
+    min_duration_samples: usize,
+    max_gap_samples: usize,
+) -> Vec<Segment> {
+    merge_segments(segments, max_gap_samples)
+        .into_iter()
+        .filter(|s| s.duration_samples() >= min_duration_samples)
+        .map(|mut s| {
+            s.peak_power_db = power_profile[s.frame_range(window_size, power_profile.len())]
+                .iter()
+                .copied()
+                .fold(f32::NEG_INFINITY, f32::max);
+            s
+        })
+        .collect()
+}
+
+/// FFT size for time-frequency segmentation (frequency resolution is sample_rate / size)
+const SPECTROGRAM_FFT_SIZE: usize = 256;
+
+/// Regions with fewer cells are isolated noise spikes
+const MIN_BLOB_CELLS: usize = 4;
+
+/// Cells this far below a frame's strongest cell are treated as window leakage (Blackman sidelobes are -58 dB)
+const SIDELOBE_REJECTION_DB: f32 = 50.0;
+
+/// Connected region of above-threshold spectrogram cells (frame and bin ranges inclusive)
+#[derive(Debug, Clone, Copy)]
+struct Blob {
+    first_frame: usize,
+    last_frame: usize,
+    low_bin: usize,
+    high_bin: usize,
+    peak_db: f32,
+    cells: usize,
+}
+
+impl Blob {
+    fn cell(frame: usize, bin: usize, db: f32) -> Self {
+        Self {
+            first_frame: frame,
+            last_frame: frame,
+            low_bin: bin,
+            high_bin: bin,
+            peak_db: db,
+            cells: 1,
+        }
+    }
+
+    fn absorb(&mut self, other: &Blob) {
+        self.first_frame = self.first_frame.min(other.first_frame);
+        self.last_frame = self.last_frame.max(other.last_frame);
+        self.low_bin = self.low_bin.min(other.low_bin);
+        self.high_bin = self.high_bin.max(other.high_bin);
+        self.peak_db = self.peak_db.max(other.peak_db);
+        self.cells += other.cells;
+    }
+}
+
+/// Frames in the per-bin running median that suppresses onset splatter (a sharp edge smears across
+/// every bin for the couple of frames whose window straddles it)
+const SPLATTER_MEDIAN_FRAMES: usize = 5;
+
+/// Compute each frame's spectrum in dB (bins ordered -fs/2..+fs/2), median-filtered over time per bin,
+/// passing it to `visit` with the index of the frame at the center of the median window
+fn for_each_spectrum_frame(samples: &[IqSample], fft_size: usize, mut visit: impl FnMut(usize, &[f32])) {
+    let hop_size = fft_size / 2;
+    let window = blackman_window(fft_size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let norm = (fft_size * fft_size) as f32;
+    let half = fft_size / 2;
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    let mut recent: std::collections::VecDeque<Vec<f32>> = std::collections::VecDeque::new();
+    let mut smoothed = vec![0.0f32; fft_size];
+    let mut column = [0.0f32; SPLATTER_MEDIAN_FRAMES];
+
+    let num_frames = (samples.len() - fft_size) / hop_size + 1;
+    for frame in 0..num_frames {
+        let start = frame * hop_size;
+        for ((slot, s), w) in buffer.iter_mut().zip(&samples[start..start + fft_size]).zip(&window) {
+            *slot = Complex::new(s.i * w, s.q * w);
+        }
+        fft.process(&mut buffer);
+
+        let mut db = if recent.len() == SPLATTER_MEDIAN_FRAMES {
+            recent.pop_front().unwrap_or_default()
+        } else {
+            vec![0.0f32; fft_size]
+        };
+        for (j, value) in db.iter_mut().enumerate() {
+            *value = 10.0 * (buffer[(j + half) % fft_size].norm_sqr() / norm).max(1e-30).log10();
+        }
+        recent.push_back(db);
+        if recent.len() < SPLATTER_MEDIAN_FRAMES {
+            continue;
+        }
+
+        for (bin, value) in smoothed.iter_mut().enumerate() {
+            for (slot, frame_db) in column.iter_mut().zip(&recent) {
+                *slot = frame_db[bin];
+            }
+            column.sort_by(f32::total_cmp);
+            *value = column[SPLATTER_MEDIAN_FRAMES / 2];
+        }
+        visit(frame - SPLATTER_MEDIAN_FRAMES / 2, &smoothed);
+    }
+}
+
+/// Level of each frequency bin at the given quantile over the whole recording, from 0.5 dB histograms
+fn bin_level_quantiles(samples: &[IqSample], fft_size: usize, quantile: f32) -> Vec<f32> {
+    const HIST_MIN_DB: f32 = -200.0;
+    const HIST_BINS: usize = 500;
+    let mut histograms = vec![[0u32; HIST_BINS]; fft_size];
+    let mut num_frames = 0;
+    for_each_spectrum_frame(samples, fft_size, |_, db| {
+        for (hist, &value) in histograms.iter_mut().zip(db) {
+            let idx = (((value - HIST_MIN_DB) * 2.0) as usize).min(HIST_BINS - 1);
+            hist[idx] += 1;
+        }
+        num_frames += 1;
+    });
+    let target = (num_frames as f32 * quantile).ceil() as u32;
+    histograms
+        .iter()
+        .map(|hist| {
+            let mut seen = 0;
+            let idx = hist
+                .iter()
+                .position(|&count| {
+                    seen += count;
+                    seen >= target
+                })
+                .unwrap_or(0);
+            HIST_MIN_DB + idx as f32 / 2.0
+        })
+        .collect()
+}
+
+/// Noise floor quantile for occupancy; low so that bins busy most of the time still show a floor
+const OCCUPANCY_FLOOR_QUANTILE: f32 = 0.1;
+
+/// How busy each frequency bin was over a recording
+pub struct Occupancy {
+    /// Fraction of frames each bin was active, bins ordered -fs/2..+fs/2
+    pub bin_fractions: Vec<f32>,
+    /// Fraction of frames in which any two adjacent bins were active
+    pub duty_cycle: f32,
+}
+
+/// Measure spectral occupancy: a spectrogram cell is active when it exceeds its bin's noise floor
+/// by `margin_db` and isn't window sidelobe leakage.
+/// Returns None when the recording is too short for the FFT size.
+pub fn spectral_occupancy(samples: &[IqSample], fft_size: usize, margin_db: f32) -> Option<Occupancy> {
+    if samples.len() < fft_size * SPLATTER_MEDIAN_FRAMES {
+        return None;
+    }
+    // A low percentile survives bins that are busy most of the time; lift it by the typical
+    // percentile-to-median gap of noise (taken across bins, so busy bins don't skew it)
+    let lows = bin_level_quantiles(samples, fft_size, OCCUPANCY_FLOOR_QUANTILE);
+    let medians = bin_level_quantiles(samples, fft_size, 0.5);
+    let mut gaps: Vec<f32> = medians.iter().zip(&lows).map(|(median, low)| median - low).collect();
+    gaps.sort_by(f32::total_cmp);
+    let noise_gap = gaps[gaps.len() / 2];
+    let thresholds: Vec<f32> = lows.iter().map(|low| low + noise_gap + margin_db).collect();
+
+    let mut active = vec![0usize; fft_size];
+    let mut busy_frames = 0usize;
+    let mut num_frames = 0usize;
+    for_each_spectrum_frame(samples, fft_size, |_, db| {
+        let leakage_db = db.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)) - SIDELOBE_REJECTION_DB;
+        // A lone active bin is usually a noise spike; real signals span neighbouring bins
+        let mut busy = false;
+        let mut previous_active = false;
+        for ((count, &value), &threshold) in active.iter_mut().zip(db).zip(&thresholds) {
+            let is_active = value > threshold && value > leakage_db;
+            *count += is_active as usize;
+            busy |= is_active && previous_active;
+            previous_active = is_active;
+        }
+        busy_frames += busy as usize;
+        num_frames += 1;
+    });
+
+    Some(Occupancy {
+        bin_fractions: active.iter().map(|&count| count as f32 / num_frames as f32).collect(),
+        duty_cycle: busy_frames as f32 / num_frames as f32,
+    })
+}
+
+/// Detect transmissions as connected regions of the time-frequency plane
+/// Each spectrogram cell is compared against its own frequency bin's noise floor (the bin's median
+/// level over the recording) plus `margin_db`; 8-connected regions become segments carrying their
+/// band, so simultaneous signals on different frequencies are sliced separately.
+/// Regions overlapping in frequency and separated by at most `max_gap_samples` are merged.
+pub fn detect_segments_spectrogram(
+    samples: &[IqSample],
+    sample_rate: u32,
+    margin_db: f32,
+    min_duration_samples: usize,
+    max_gap_samples: usize,
+) -> Vec<Segment> {
+    let fft_size = SPECTROGRAM_FFT_SIZE;
+    if samples.len() < fft_size * SPLATTER_MEDIAN_FRAMES {
+        return Vec::new();
+    }
+    let hop_size = fft_size / 2;
+
+    // Pass 1: per-bin median level
+    let thresholds: Vec<f32> = bin_level_quantiles(samples, fft_size, 0.5)
+        .into_iter()
+        .map(|floor| floor + margin_db)
+        .collect();
+
+    // Pass 2: streaming connected-component labeling, one frame (row) at a time
+    let mut parent: Vec<usize> = Vec::new();
+    let mut blobs: Vec<Blob> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; fft_size];
+    let mut current: Vec<Option<usize>> = vec![None; fft_size];
+
+    fn find(parent: &mut [usize], mut label: usize) -> usize {
+        while parent[label] != label {
+            parent[label] = parent[parent[label]];
+            label = parent[label];
+        }
+        label
+    }
+
+    for_each_spectrum_frame(samples, fft_size, |frame, db| {
+        // Ignore window sidelobe leakage from strong signals, which would bridge every bin
+        let leakage_db = db.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b)) - SIDELOBE_REJECTION_DB;
+        for bin in 0..fft_size {
+            current[bin] = None;
+            if db[bin] <= thresholds[bin] || db[bin] <= leakage_db {
+                continue;
+            }
+
+            let neighbours = [
+                bin.checked_sub(1).and_then(|b| current[b]),
+                bin.checked_sub(1).and_then(|b| previous[b]),
+                previous[bin],
+                previous.get(bin + 1).copied().flatten(),
+            ];
+            let mut label = None;
+            for neighbour in neighbours.into_iter().flatten() {
+                let root = find(&mut parent, neighbour);
+                match label {
+                    None => label = Some(root),
+                    Some(existing) if existing != root => {
+                        parent[root] = existing;
+                        let absorbed = blobs[root];
+                        blobs[existing].absorb(&absorbed);
+                    }
+                    _ => {}
+                }
+            }
+
+            let cell = Blob::cell(frame, bin, db[bin]);
+            let label = match label {
+                Some(label) => {
+                    blobs[label].absorb(&cell);
+                    label
+                }
+                None => {
+                    parent.push(blobs.len());
+                    blobs.push(cell);
+                    blobs.len() - 1
+                }
+            };
+            current[bin] = Some(label);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    });
+
+    let mut regions: Vec<Blob> = (0..blobs.len())
+        .filter(|&label| find(&mut parent, label) == label && blobs[label].cells >= MIN_BLOB_CELLS)
+        .map(|label| blobs[label])
+        .collect();
+    regions.sort_by_key(|blob| blob.first_frame);
+
+    // Bridge short silences within the same band
+    let max_gap_frames = max_gap_samples / hop_size;
+    let mut merged: Vec<Blob> = Vec::new();
+    for blob in regions {
+        let target = merged.iter_mut().find(|m| {
+            blob.low_bin <= m.high_bin
+                && m.low_bin <= blob.high_bin
+                && blob.first_frame <= m.last_frame + max_gap_frames
+        });
+        match target {
+            Some(m) => m.absorb(&blob),
+            None => merged.push(blob),
+        }
+    }
+
+    let bin_hz = sample_rate as f64 / fft_size as f64;
+    let half = (fft_size / 2) as f64;
     merged
         .into_iter()
+        .map(|blob| Segment {
+            start_sample: blob.first_frame * hop_size,
+            end_sample: (blob.last_frame * hop_size + fft_size).min(samples.len()),
+            peak_power_db: blob.peak_db,
+            crest_db: None,
+            band: Some(Band {
+                center_hz: ((blob.low_bin + blob.high_bin) as f64 / 2.0 - half) * bin_hz,
+                bandwidth_hz: (blob.high_bin - blob.low_bin + 1) as f64 * bin_hz,
+            }),
+        })
         .filter(|s| s.duration_samples() >= min_duration_samples)
         .collect()
 }
 
-/// Merge segments that are separated by less than max_gap samples
-fn merge_segments(segments: Vec<Segment>, max_gap: usize) -> Vec<Segment> {
+/// Merge segments that are separated by less than max_gap samples, keeping the stronger peak
+pub fn merge_segments(segments: Vec<Segment>, max_gap: usize) -> Vec<Segment> {
     if segments.is_empty() {
         return segments;
     }
@@ -202,6 +1044,7 @@ fn merge_segments(segments: Vec<Segment>, max_gap: usize) -> Vec<Segment> {
         if segment.start_sample <= current.end_sample + max_gap {
             // Merge
             current.end_sample = segment.end_sample;
+            current.peak_power_db = current.peak_power_db.max(segment.peak_power_db);
         } else {
             merged.push(current);
             current = segment;
@@ -212,6 +1055,107 @@ fn merge_segments(segments: Vec<Segment>, max_gap: usize) -> Vec<Segment> {
     merged
 }
 
+/// How to handle neighbouring segments whose padded ranges overlap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlapPolicy {
+    /// Leave them; the shared samples appear in both slices
+    Allow,
+    /// Join them into one segment
+    Merge,
+    /// Move their common boundary to the middle of the overlap
+    Trim,
+}
+
+/// Resolve overlaps between consecutive segments (ordered by start) so no sample is in two of them
+pub fn resolve_overlaps(segments: Vec<Segment>, policy: OverlapPolicy) -> Vec<Segment> {
+    if policy == OverlapPolicy::Allow {
+        return segments;
+    }
+
+    let mut resolved: Vec<Segment> = Vec::with_capacity(segments.len());
+    for mut segment in segments {
+        if let Some(previous) = resolved.last_mut() {
+            if segment.start_sample < previous.end_sample {
+                if policy == OverlapPolicy::Merge {
+                    previous.end_sample = previous.end_sample.max(segment.end_sample);
+                    previous.peak_power_db = previous.peak_power_db.max(segment.peak_power_db);
+                    continue;
+                }
+                let boundary = ((segment.start_sample + previous.end_sample) / 2).min(segment.end_sample);
+                previous.end_sample = boundary;
+                segment.start_sample = boundary;
+                if segment.start_sample == segment.end_sample {
+                    continue;
+                }
+            }
+        }
+        resolved.push(segment);
+    }
+    resolved
+}
+
+/// Shrink each segment to its first and last profile frames at or above `threshold_off_db`
+/// Segments with no such frame are left unchanged
+pub fn trim_segment_silence(segments: Vec<Segment>, power_profile: &[f32], window_size: usize, threshold_off_db: f32) -> Vec<Segment> {
+    let hop_size = (window_size / 2).max(1); // Must match calculate_power_profile
+    segments
+        .into_iter()
+        .map(|mut s| {
+            let first_frame = s.start_sample.div_ceil(hop_size);
+            let last_frame = (s.end_sample.saturating_sub(window_size) / hop_size).min(power_profile.len().saturating_sub(1));
+            if first_frame > last_frame {
+                return s;
+            }
+            let frames = &power_profile[first_frame..=last_frame];
+            let (Some(first), Some(last)) = (
+                frames.iter().position(|&p| p >= threshold_off_db),
+                frames.iter().rposition(|&p| p >= threshold_off_db),
+            ) else {
+                return s;
+            };
+            s.start_sample = (first_frame + first) * hop_size;
+            s.end_sample = ((first_frame + last) * hop_size + window_size).min(s.end_sample);
+            s
+        })
+        .collect()
+}
+
+/// Number of samples covered by at least one segment, counting overlaps once
+pub fn covered_samples(segments: &[Segment]) -> usize {
+    let mut spans: Vec<(usize, usize)> = segments.iter().map(|s| (s.start_sample, s.end_sample)).collect();
+    spans.sort_unstable();
+    let mut covered = 0;
+    let mut reached = 0;
+    for (start, end) in spans {
+        let start = start.max(reached);
+        if end > start {
+            covered += end - start;
+            reached = end;
+        }
+    }
+    covered
+}
+
+/// Cut segments longer than `max_samples` into consecutive pieces of at most that length
+/// Pieces keep the original segment's peak, crest and band
+pub fn split_segments(segments: Vec<Segment>, max_samples: usize) -> Vec<Segment> {
+    let max_samples = max_samples.max(1);
+    let mut pieces = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let mut start = segment.start_sample;
+        while start < segment.end_sample {
+            let end = (start + max_samples).min(segment.end_sample);
+            pieces.push(Segment {
+                start_sample: start,
+                end_sample: end,
+                ..segment.clone()
+            });
+            start = end;
+        }
+    }
+    pieces
+}
+
 /// Add padding to segments, clamping to valid bounds
 pub fn add_padding(segments: Vec<Segment>, padding_samples: usize, total_samples: usize) -> Vec<Segment> {
     segments
@@ -223,3 +1167,157 @@ pub fn add_padding(segments: Vec<Segment>, padding_samples: usize, total_samples
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_estimator_finds_noise_under_mostly_signal() {
+        // 8% of frames are noise near -80 dB; the rest is signal spread over -50..-10 dB
+        let noise = (0..80).map(|n| -80.0 + (n % 9) as f32 * 0.1 - 0.4);
+        let signal = (0..920).map(|n| -50.0 + n as f32 * 40.0 / 920.0);
+        let profile: Vec<f32> = noise.chain(signal).collect();
+
+        let mode = auto_threshold(&profile, NoiseEstimator::Mode);
+        assert!((mode.noise_floor + 80.0).abs() <= 1.0, "mode floor {}", mode.noise_floor);
+        // The 10th percentile lands in the signal cluster instead
+        let percentile = auto_threshold(&profile, NoiseEstimator::Percentile);
+        assert!(percentile.noise_floor > -55.0, "percentile floor {}", percentile.noise_floor);
+    }
+
+    #[test]
+    fn auto_threshold_ignores_non_finite_frames() {
+        // Digital silence (-inf) and corrupt frames (NaN) among noise at -60 dB and a burst at -20 dB
+        let mut profile: Vec<f32> = (0..1000).map(|n| if (400..500).contains(&n) { -20.0 } else { -60.0 + (n % 7) as f32 * 0.5 }).collect();
+        profile[..300].iter_mut().for_each(|p| *p = f32::NEG_INFINITY);
+        profile[700] = f32::NAN;
+        profile[701] = f32::INFINITY;
+
+        for estimator in [NoiseEstimator::Percentile, NoiseEstimator::Mode] {
+            let analysis = auto_threshold(&profile, estimator);
+            assert!((-61.0..=-56.0).contains(&analysis.noise_floor), "{:?} floor {}", estimator, analysis.noise_floor);
+            assert!((-50.0..=-20.0).contains(&analysis.threshold), "{:?} threshold {}", estimator, analysis.threshold);
+            assert!(analysis.mean.is_finite() && analysis.std_dev.is_finite());
+            assert_eq!((analysis.min, analysis.max), (-60.0, -20.0));
+        }
+    }
+
+    #[test]
+    fn nan_sample_does_not_poison_power_profile() {
+        let mut samples: Vec<IqSample> = (0..4800).map(|n| IqSample::new(0.01 * ((n * 7919) % 13) as f32 - 0.06, 0.0)).collect();
+        samples[1000].i = f32::NAN;
+        let profile = calculate_power_profile(&samples, 48, PowerMetric::Peak, &[], 1);
+        assert!(profile.iter().all(|p| !p.is_nan()));
+        let analysis = auto_threshold(&profile, NoiseEstimator::Percentile);
+        assert!(analysis.threshold.is_finite() && analysis.noise_floor.is_finite());
+    }
+
+    #[test]
+    fn smoothing_keeps_fading_burst_in_one_segment() {
+        // A burst at -20 dB from frame 20 to 80, fading for three frames in every eight to well below release
+        let profile: Vec<f32> = (0..100)
+            .map(|n| match n {
+                20..80 if n % 8 >= 5 => -45.0,
+                20..80 => -20.0,
+                _ => -60.0,
+            })
+            .collect();
+        let window = 48;
+        let total = profile.len() * window / 2 + window;
+        let detect = |profile: &[f32]| detect_segments(profile, total, window, -30.0, 0, 0, 1);
+
+        assert!(detect(&profile).len() > 1, "fades should split the raw profile");
+        let smoothed = detect(&smooth_profile(&profile, 9));
+        assert_eq!(smoothed.len(), 1);
+        assert!(smoothed[0].start_sample <= 20 * window / 2 && smoothed[0].end_sample >= 80 * window / 2);
+    }
+
+    #[test]
+    fn flatness_separates_white_noise_from_tone() {
+        let mut state = 0x9e37_79b9_u32;
+        let mut noise = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        };
+        let window = 64;
+        // Noise, then a tone 40 dB above it, then noise again
+        let samples: Vec<IqSample> = (0..9600)
+            .map(|n| {
+                let mut sample = IqSample::new(0.01 * noise(), 0.01 * noise());
+                if (3200..6400).contains(&n) {
+                    let phase = 2.0 * std::f32::consts::PI * 0.1 * n as f32;
+                    sample.i += phase.cos();
+                    sample.q += phase.sin();
+                }
+                sample
+            })
+            .collect();
+
+        let flatness = spectral_flatness_profile(&samples, window);
+        let frame = |sample: usize| sample / (window / 2);
+        let mean = |frames: &[f32]| frames.iter().sum::<f32>() / frames.len() as f32;
+        let noise_flatness = mean(&flatness[..frame(3200) - 2]);
+        let tone_flatness = mean(&flatness[frame(3200)..frame(6400) - 2]);
+        assert!(noise_flatness > 0.3, "noise flatness {}", noise_flatness);
+        assert!(tone_flatness < 0.05, "tone flatness {}", tone_flatness);
+
+        let power = calculate_power_profile(&samples, window, PowerMetric::Peak, &[], 1);
+        let segments = detect_segments_flatness(&flatness, &power, samples.len(), window, 0.2, 0, 0);
+        assert_eq!(segments.len(), 1);
+        assert!(segments[0].start_sample.abs_diff(3200) <= window && segments[0].end_sample.abs_diff(6400) <= window);
+    }
+
+    #[test]
+    fn goertzel_matches_the_fft_bin() {
+        let (window_size, rate) = (64, 48_000);
+        // A tone on bin 8 over a weaker tone on bin -5 and some deterministic clutter
+        let samples: Vec<IqSample> = (0..window_size)
+            .map(|n| {
+                let at = |bin: f32| 2.0 * std::f32::consts::PI * bin * n as f32 / window_size as f32;
+                let clutter = 0.01 * ((n * 7919) % 13) as f32 - 0.06;
+                IqSample::new(at(8.0).cos() + 0.1 * at(-5.0).cos() + clutter, at(8.0).sin() + 0.1 * at(-5.0).sin())
+            })
+            .collect();
+        let window = blackman_window(window_size);
+        let mut buffer: Vec<Complex<f32>> = samples.iter().zip(&window).map(|(s, w)| Complex::new(s.i * w, s.q * w)).collect();
+        FftPlanner::new().plan_fft_forward(window_size).process(&mut buffer);
+
+        for bin in [8i32, -5, 3] {
+            let fft_db = 10.0 * (buffer[bin.rem_euclid(window_size as i32) as usize].norm_sqr() / (window_size * window_size) as f32).log10();
+            let freq_hz = bin as f64 * rate as f64 / window_size as f64;
+            let goertzel_db = goertzel_power_db(&samples, &window, freq_hz, rate);
+            assert!((goertzel_db - fft_db).abs() < 0.01, "bin {}: Goertzel {} dB, FFT {} dB", bin, goertzel_db, fft_db);
+        }
+        // The strongest bin is what the peak metric reports
+        let peak_db = calculate_power_db(&samples, &window, PowerMetric::Peak, &[], 1, &mut FftPlanner::new());
+        assert!((goertzel_power_db(&samples, &window, 6000.0, rate) - peak_db).abs() < 0.01);
+    }
+
+    #[test]
+    fn parallel_power_profile_matches_serial() {
+        let samples: Vec<IqSample> = (0..20_000)
+            .map(|n| {
+                let phase = 0.3 * n as f32;
+                let envelope = if (5_000..12_000).contains(&n) { 1.0 } else { 0.01 };
+                IqSample::new(envelope * phase.cos() + 0.001 * ((n * 7919) % 13) as f32, envelope * phase.sin())
+            })
+            .collect();
+        let window_size = 256;
+        let window = blackman_window(window_size);
+        let notches = [0.1];
+
+        for metric in [PowerMetric::Peak, PowerMetric::Total] {
+            let mut planner = FftPlanner::new();
+            let serial: Vec<f32> = (0..(samples.len() - window_size) / (window_size / 2) + 1)
+                .map(|frame| {
+                    let start = frame * window_size / 2;
+                    calculate_power_db(&samples[start..start + window_size], &window, metric, &notches, 2, &mut planner)
+                })
+                .collect();
+            assert_eq!(calculate_power_profile(&samples, window_size, metric, &notches, 2), serial, "{:?}", metric);
+        }
+    }
+}
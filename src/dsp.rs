@@ -0,0 +1,558 @@
+use crate::detector::blackman_window;
+use crate::input::IqSample;
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::f32::consts::PI;
+
+/// Number of taps in the FIR Hilbert transformer (odd, so the group delay is whole samples)
+const HILBERT_TAPS: usize = 127;
+
+/// Design a Blackman-windowed FIR Hilbert transformer
+/// Ideal response is 2/(pi*n) for odd n and 0 for even n, centered on the middle tap
+fn hilbert_taps(num_taps: usize) -> Vec<f32> {
+    let center = (num_taps / 2) as isize;
+    let window = blackman_window(num_taps);
+    (0..num_taps)
+        .map(|k| {
+            let n = k as isize - center;
+            if n % 2 == 0 {
+                0.0
+            } else {
+                2.0 / (PI * n as f32) * window[k]
+            }
+        })
+        .collect()
+}
+
+/// Convert a real-valued signal to analytic IQ using an FIR Hilbert transform
+/// I is the input (delayed to match the filter's group delay), Q is the Hilbert output
+pub fn hilbert_to_iq(real: &[f32]) -> Vec<IqSample> {
+    let taps = hilbert_taps(HILBERT_TAPS);
+    let delay = HILBERT_TAPS / 2;
+
+    (0..real.len())
+        .map(|n| {
+            // Output n is aligned with input n: convolve around the center tap
+            let q = taps
+                .iter()
+                .enumerate()
+                .filter(|(_, &h)| h != 0.0)
+                .filter_map(|(k, &h)| {
+                    let idx = (n + delay).checked_sub(k)?;
+                    real.get(idx).map(|&x| x * h)
+                })
+                .sum();
+            IqSample::new(real[n], q)
+        })
+        .collect()
+}
+
+/// Band-limited resampling of a short IQ signal by zero-padding or truncating its spectrum
+/// Intended for reference waveforms; the whole signal is transformed at once
+pub fn resample(samples: &[IqSample], from_rate: u32, to_rate: u32) -> Vec<IqSample> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let in_len = samples.len();
+    let out_len = ((in_len as u64 * to_rate as u64) / from_rate as u64).max(1) as usize;
+
+    let mut planner = FftPlanner::<f32>::new();
+    let mut spectrum: Vec<Complex<f32>> = samples.iter().map(|s| Complex::new(s.i, s.q)).collect();
+    planner.plan_fft_forward(in_len).process(&mut spectrum);
+
+    // Keep the lowest frequencies common to both lengths (positive bins first, negative bins at the end)
+    let keep = in_len.min(out_len);
+    let positive = keep.div_ceil(2);
+    let negative = keep / 2;
+    let mut resampled = vec![Complex::new(0.0f32, 0.0); out_len];
+    resampled[..positive].copy_from_slice(&spectrum[..positive]);
+    resampled[out_len - negative..].copy_from_slice(&spectrum[in_len - negative..]);
+
+    planner.plan_fft_inverse(out_len).process(&mut resampled);
+
+    // Unnormalized forward/inverse pair scales by in_len
+    let scale = 1.0 / in_len as f32;
+    resampled.iter().map(|c| IqSample::new(c.re * scale, c.im * scale)).collect()
+}
+
+/// Occupied spectrum of a signal, relative to the tuned center frequency
+#[derive(Debug, Clone, Copy)]
+pub struct SpectrumEstimate {
+    /// Midpoint of the occupied band (Hz offset from baseband DC)
+    pub center_hz: f64,
+    /// Width of the contiguous band within `drop_db` of the peak bin (Hz)
+    pub bandwidth_hz: f64,
+}
+
+/// Estimate occupied bandwidth from the averaged FFT power spectrum
+/// The band grows outward from the peak bin until power falls `drop_db` below the peak;
+/// if it reaches both edges the signal is treated as occupying the whole sample rate
+pub fn estimate_spectrum(samples: &[IqSample], sample_rate: u32, drop_db: f32) -> Option<SpectrumEstimate> {
+    const MAX_FFT_SIZE: usize = 1024;
+    let fft_size = samples.len().min(MAX_FFT_SIZE);
+    if fft_size < 16 {
+        return None;
+    }
+
+    let window = blackman_window(fft_size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let hop_size = fft_size / 2;
+
+    // Welch average of windowed frames
+    let mut power = vec![0.0f32; fft_size];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    let mut frames = 0;
+    let mut start = 0;
+    while start + fft_size <= samples.len() {
+        for ((slot, s), w) in buffer.iter_mut().zip(&samples[start..start + fft_size]).zip(&window) {
+            *slot = Complex::new(s.i * w, s.q * w);
+        }
+        fft.process(&mut buffer);
+        for (p, c) in power.iter_mut().zip(&buffer) {
+            *p += c.norm_sqr();
+        }
+        frames += 1;
+        start += hop_size;
+    }
+    if frames == 0 {
+        return None;
+    }
+
+    // Reorder bins from -fs/2 to +fs/2
+    let half = fft_size / 2;
+    let shifted: Vec<f32> = (0..fft_size).map(|j| power[(j + half) % fft_size]).collect();
+
+    // Peak bin, skipping DC as the detector does
+    let peak = (0..fft_size)
+        .filter(|&j| j != half)
+        .max_by(|&a, &b| shifted[a].total_cmp(&shifted[b]))?;
+    if shifted[peak] <= 0.0 {
+        return None;
+    }
+    let floor = shifted[peak] * 10f32.powf(-drop_db / 10.0);
+
+    let mut low = peak;
+    while low > 0 && shifted[low - 1] >= floor {
+        low -= 1;
+    }
+    let mut high = peak;
+    while high + 1 < fft_size && shifted[high + 1] >= floor {
+        high += 1;
+    }
+
+    let bin_hz = sample_rate as f64 / fft_size as f64;
+    if low == 0 && high == fft_size - 1 {
+        return Some(SpectrumEstimate {
+            center_hz: 0.0,
+            bandwidth_hz: sample_rate as f64,
+        });
+    }
+
+    let center_bin = (low + high) as f64 / 2.0 - half as f64;
+    Some(SpectrumEstimate {
+        center_hz: center_bin * bin_hz,
+        bandwidth_hz: (high - low + 1) as f64 * bin_hz,
+    })
+}
+
+/// Spectral line must stand this far above the median of the search band (linear power ratio)
+const SYMBOL_LINE_MIN_RATIO: f32 = 20.0;
+
+/// Longest stretch of samples analyzed for the symbol rate
+const SYMBOL_RATE_MAX_FFT: usize = 1 << 20;
+
+/// Envelope magnitude must vary at least this much (std dev over mean) to carry symbols;
+/// constant-envelope signals (tones, FM, chirps) fall well below it
+const SYMBOL_RATE_MIN_ENVELOPE_VARIATION: f32 = 0.1;
+
+/// Fewest symbols the analyzed stretch must span for a rate to be reported
+const SYMBOL_RATE_MIN_SYMBOLS: usize = 16;
+
+/// Highest harmonic number checked when looking for the fundamental below the strongest line
+const SYMBOL_RATE_MAX_HARMONIC: usize = 8;
+
+/// Rough non-data-aided symbol rate estimate (Hz) from the envelope's spectral line
+/// Symbol transitions modulate the magnitude-squared envelope at the baud rate; squaring its
+/// first difference turns each transition into a pulse, so the pulse train's fundamental shows up
+/// as a line in its spectrum even for rectangular (OOK/NRZ) pulses. Returns None when no line stands
+/// clearly above the surrounding spectrum, as for analog or constant-envelope signals.
+/// The outer 10% at each end is skipped so the burst's own rise and fall (and padding) don't dominate.
+pub fn estimate_symbol_rate(samples: &[IqSample], sample_rate: u32) -> Option<f64> {
+    let trim = samples.len() / 10;
+    let samples = &samples[trim..samples.len() - trim];
+    let len = samples.len().min(SYMBOL_RATE_MAX_FFT);
+    if len < 64 {
+        return None;
+    }
+    let fft_size = len.next_power_of_two();
+
+    let envelope: Vec<f32> = samples[..len].iter().map(|s| s.i * s.i + s.q * s.q).collect();
+    let magnitude_mean = envelope.iter().map(|e| e.sqrt()).sum::<f32>() / len as f32;
+    let magnitude_var = envelope.iter().map(|e| (e.sqrt() - magnitude_mean).powi(2)).sum::<f32>() / len as f32;
+    if magnitude_mean <= 0.0 || magnitude_var.sqrt() < SYMBOL_RATE_MIN_ENVELOPE_VARIATION * magnitude_mean {
+        return None;
+    }
+
+    let pulses: Vec<f32> = envelope.windows(2).map(|w| (w[1] - w[0]) * (w[1] - w[0])).collect();
+    let mean = pulses.iter().sum::<f32>() / pulses.len() as f32;
+
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    for (slot, &p) in buffer.iter_mut().zip(&pulses) {
+        *slot = Complex::new(p - mean, 0.0);
+    }
+    FftPlanner::<f32>::new().plan_fft_forward(fft_size).process(&mut buffer);
+
+    // Real input: search positive frequencies above the lowest rate that fits enough symbols
+    let min_bin = SYMBOL_RATE_MIN_SYMBOLS * fft_size / len;
+    let max_bin = fft_size / 2;
+    if max_bin <= min_bin + 1 {
+        return None;
+    }
+    let power: Vec<f32> = buffer[min_bin..max_bin].iter().map(|c| c.norm_sqr()).collect();
+    let (strongest, &peak) = power.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+
+    let mut sorted = power.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+    if median <= 0.0 || peak < median * SYMBOL_LINE_MIN_RATIO {
+        return None;
+    }
+
+    // Short transition pulses put equal lines at every harmonic, so the strongest may not be the
+    // fundamental; take the lowest sub-multiple that still carries a comparable line
+    let local_max = |center: usize| {
+        let range = center.saturating_sub(2)..(center + 3).min(power.len());
+        range.max_by(|&a, &b| power[a].total_cmp(&power[b]))
+    };
+    let peak_idx = (2..=SYMBOL_RATE_MAX_HARMONIC)
+        .rev()
+        .filter_map(|k| {
+            let candidate = (strongest + min_bin) / k;
+            (candidate > min_bin).then(|| local_max(candidate - min_bin)).flatten()
+        })
+        .find(|&idx| power[idx] >= 0.5 * peak)
+        .unwrap_or(strongest);
+    let peak = power[peak_idx];
+
+    // Interpolate the peak between bins with a parabola through its neighbors
+    let offset = if peak_idx > 0 && peak_idx + 1 < power.len() {
+        let (a, b, c) = (power[peak_idx - 1], peak, power[peak_idx + 1]);
+        let denom = a - 2.0 * b + c;
+        if denom != 0.0 { (0.5 * (a - c) / denom).clamp(-0.5, 0.5) } else { 0.0 }
+    } else {
+        0.0
+    };
+    let bin = (peak_idx + min_bin) as f64 + offset as f64;
+    Some(bin * sample_rate as f64 / fft_size as f64)
+}
+
+/// Audio demodulation applied to each slice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Demodulation {
+    /// Frequency modulation via phase differentiation
+    Fm,
+    /// Amplitude modulation via envelope detection
+    Am,
+}
+
+/// Taper the first and last `fade_len` samples with a raised-cosine ramp
+/// The ramp is shortened to half the slice when the slice is too short for both fades
+pub fn apply_fade(samples: &mut [IqSample], fade_len: usize) {
+    let fade_len = fade_len.min(samples.len() / 2);
+    let len = samples.len();
+    for n in 0..fade_len {
+        let gain = 0.5 * (1.0 - (std::f32::consts::PI * (n as f32 + 0.5) / fade_len as f32).cos());
+        for sample in [n, len - 1 - n] {
+            samples[sample].i *= gain;
+            samples[sample].q *= gain;
+        }
+    }
+}
+
+/// -3 dB width of each `notch_filter` stop band
+const NOTCH_BANDWIDTH_HZ: f64 = 100.0;
+
+/// Remove a narrow band around each of `freqs_hz` (relative to the signal's center) in place
+/// Each notch is a complex one-zero, one-pole filter: the zero sits on the unit circle at the notch
+/// frequency and the pole just inside it, so the response away from the notch is close to flat
+pub fn notch_filter(samples: &mut [IqSample], sample_rate: u32, freqs_hz: &[f64]) {
+    let radius = (1.0 - std::f64::consts::PI * NOTCH_BANDWIDTH_HZ / sample_rate as f64).max(0.0) as f32;
+    for &freq_hz in freqs_hz {
+        let zero = Complex::from_polar(1.0f32, (2.0 * std::f64::consts::PI * freq_hz / sample_rate as f64) as f32);
+        let pole = zero * radius;
+        let mut prev_in = Complex::new(0.0f32, 0.0);
+        let mut prev_out = Complex::new(0.0f32, 0.0);
+        for sample in samples.iter_mut() {
+            let x = Complex::new(sample.i, sample.q);
+            let y = x - zero * prev_in + pole * prev_out;
+            prev_in = x;
+            prev_out = y;
+            *sample = IqSample::new(y.re, y.im);
+        }
+    }
+}
+
+/// Carrier frequency offset in Hz from the mean phase advance between consecutive samples
+/// The lag products are summed before taking their angle, so strong samples dominate and
+/// noise-only stretches (such as padding) contribute little. None for silent or too-short input.
+pub fn estimate_cfo(samples: &[IqSample], sample_rate: u32) -> Option<f64> {
+    let (re, im) = samples.windows(2).fold((0.0f64, 0.0f64), |(re, im), pair| {
+        let (a, b) = (pair[0], pair[1]);
+        // b * conj(a)
+        (
+            re + (b.i * a.i + b.q * a.q) as f64,
+            im + (b.q * a.i - b.i * a.q) as f64,
+        )
+    });
+    if re == 0.0 && im == 0.0 {
+        return None;
+    }
+    Some(im.atan2(re) * sample_rate as f64 / (2.0 * std::f64::consts::PI))
+}
+
+/// Shift a signal down by `offset_hz` in place with a phase-continuous oscillator
+pub fn remove_frequency_offset(samples: &mut [IqSample], sample_rate: u32, offset_hz: f64) {
+    let step = -2.0 * std::f64::consts::PI * offset_hz / sample_rate as f64;
+    let mut phase = 0.0f64;
+    for sample in samples.iter_mut() {
+        let (sin, cos) = (phase as f32).sin_cos();
+        *sample = IqSample::new(sample.i * cos - sample.q * sin, sample.i * sin + sample.q * cos);
+        phase = (phase + step) % (2.0 * std::f64::consts::PI);
+    }
+}
+
+/// Demodulate IQ to audio at `audio_rate` (or the input rate, if lower)
+/// Returns the audio samples, DC-free and peak-normalized so quiet and loud slices play at similar levels,
+/// and their sample rate
+pub fn demodulate(samples: &[IqSample], sample_rate: u32, audio_rate: u32, mode: Demodulation) -> (Vec<f32>, u32) {
+    let baseband = match mode {
+        Demodulation::Fm => fm_discriminator(samples),
+        Demodulation::Am => samples.iter().map(|s| (s.i * s.i + s.q * s.q).sqrt()).collect(),
+    };
+    let out_rate = audio_rate.min(sample_rate);
+    let mut audio = lowpass_decimate(&baseband, sample_rate, out_rate);
+    remove_dc(&mut audio);
+    normalize_peak(&mut audio, 0.9);
+    (audio, out_rate)
+}
+
+/// Instantaneous frequency as the phase step between consecutive samples, scaled so +/-fs/2 is +/-1
+fn fm_discriminator(samples: &[IqSample]) -> Vec<f32> {
+    samples
+        .windows(2)
+        .map(|pair| {
+            let (a, b) = (pair[0], pair[1]);
+            // b * conj(a)
+            let re = b.i * a.i + b.q * a.q;
+            let im = b.q * a.i - b.i * a.q;
+            im.atan2(re) / PI
+        })
+        .collect()
+}
+
+/// Low-pass filter to 0.4 of `to_rate` and decimate, evaluating the FIR only at output samples
+fn lowpass_decimate(signal: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if to_rate >= from_rate || signal.is_empty() {
+        return signal.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let cutoff = 0.4 / ratio as f32; // cycles per input sample
+    // Blackman transition width is ~5.5/N, so this keeps the stopband below the output Nyquist
+    let num_taps = (24.0 * ratio).ceil() as usize | 1;
+    let center = (num_taps / 2) as isize;
+    let window = blackman_window(num_taps);
+    let taps: Vec<f32> = (0..num_taps)
+        .map(|k| {
+            let n = (k as isize - center) as f32;
+            let sinc = if n == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * n).sin() / (PI * n)
+            };
+            sinc * window[k]
+        })
+        .collect();
+
+    let out_len = (signal.len() as f64 / ratio) as usize;
+    (0..out_len)
+        .map(|m| {
+            let position = (m as f64 * ratio).round() as isize;
+            taps.iter()
+                .enumerate()
+                .filter_map(|(k, &h)| {
+                    let idx = position + k as isize - center;
+                    (idx >= 0).then(|| signal.get(idx as usize).map(|&x| x * h)).flatten()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+fn remove_dc(signal: &mut [f32]) {
+    if signal.is_empty() {
+        return;
+    }
+    let mean = signal.iter().sum::<f32>() / signal.len() as f32;
+    signal.iter_mut().for_each(|x| *x -= mean);
+}
+
+/// Scale so the largest magnitude equals `target`, leaving silence untouched
+fn normalize_peak(signal: &mut [f32], target: f32) {
+    let peak = signal.iter().fold(0.0f32, |acc, x| acc.max(x.abs()));
+    if peak > 0.0 {
+        signal.iter_mut().for_each(|x| *x *= target / peak);
+    }
+}
+
+/// Splits a wideband IQ stream into equally spaced narrowband channels
+/// Each channel is shifted to baseband with an NCO, low-pass filtered and decimated.
+/// Filter history and NCO/decimation phase persist across calls so chunk boundaries are seamless
+pub struct Channelizer {
+    sample_rate: u32,
+    offsets_hz: Vec<f64>,
+    decimation: usize,
+    taps: Vec<f32>,
+    /// Last `taps.len() - 1` input samples from previous chunks
+    history: Vec<IqSample>,
+    /// Absolute stream index of `history[0]`
+    history_start: u64,
+    /// Absolute stream index of the input sample that produces the next output
+    next_output: u64,
+}
+
+impl Channelizer {
+    /// `count` channels of `bandwidth_hz` each, centered on the tuned frequency
+    pub fn new(sample_rate: u32, count: usize, bandwidth_hz: u32) -> Result<Self, String> {
+        if count == 0 || bandwidth_hz == 0 {
+            return Err("channel count and bandwidth must be positive".to_string());
+        }
+        if count as u64 * bandwidth_hz as u64 > sample_rate as u64 {
+            return Err(format!(
+                "{} channels of {} Hz exceed the {} Hz sample rate",
+                count, bandwidth_hz, sample_rate
+            ));
+        }
+
+        let offsets_hz = (0..count)
+            .map(|k| (k as f64 - (count - 1) as f64 / 2.0) * bandwidth_hz as f64)
+            .collect();
+
+        // Leave 25% of the output rate for the filter's transition band
+        let decimation = ((sample_rate as f64 / (1.25 * bandwidth_hz as f64)) as usize).max(1);
+
+        let ratio = sample_rate as f32 / bandwidth_hz as f32;
+        let num_taps = (16.0 * ratio).ceil() as usize | 1;
+        let cutoff = 0.5 / ratio; // cycles per input sample
+        let center = (num_taps / 2) as isize;
+        let window = blackman_window(num_taps);
+        let taps = (0..num_taps)
+            .map(|k| {
+                let n = (k as isize - center) as f32;
+                let sinc = if n == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * n).sin() / (PI * n)
+                };
+                sinc * window[k]
+            })
+            .collect();
+
+        Ok(Self {
+            sample_rate,
+            offsets_hz,
+            decimation,
+            taps,
+            history: Vec::new(),
+            history_start: 0,
+            next_output: 0,
+        })
+    }
+
+    /// Channel center frequencies relative to the tuned frequency
+    pub fn offsets_hz(&self) -> &[f64] {
+        &self.offsets_hz
+    }
+
+    /// Sample rate of each channel's output
+    pub fn output_rate(&self) -> u32 {
+        self.sample_rate / self.decimation as u32
+    }
+
+    /// Feed a chunk of wideband samples, returning the new samples for each channel
+    pub fn process(&mut self, chunk: &[IqSample]) -> Vec<Vec<IqSample>> {
+        let mut input = std::mem::take(&mut self.history);
+        input.extend_from_slice(chunk);
+        let input_start = self.history_start;
+        let input_end = input_start + input.len() as u64;
+
+        let mut outputs = Vec::with_capacity(self.offsets_hz.len());
+        for &offset_hz in &self.offsets_hz {
+            // Mix down by the channel offset; phase derives from the absolute index so it is continuous
+            let cycles_per_sample = -offset_hz / self.sample_rate as f64;
+            let start_phase = (cycles_per_sample * input_start as f64).fract() * 2.0 * std::f64::consts::PI;
+            let step = cycles_per_sample * 2.0 * std::f64::consts::PI;
+            let mixed: Vec<Complex<f32>> = input
+                .iter()
+                .enumerate()
+                .map(|(n, s)| {
+                    let phase = start_phase + step * n as f64;
+                    let rotator = Complex::new(phase.cos() as f32, phase.sin() as f32);
+                    Complex::new(s.i, s.q) * rotator
+                })
+                .collect();
+
+            let mut channel = Vec::new();
+            let mut position = self.next_output;
+            while position < input_end {
+                let newest = (position - input_start) as usize;
+                let acc: Complex<f32> = self
+                    .taps
+                    .iter()
+                    .enumerate()
+                    .take(newest + 1)
+                    .map(|(k, &h)| mixed[newest - k] * h)
+                    .sum();
+                channel.push(IqSample::new(acc.re, acc.im));
+                position += self.decimation as u64;
+            }
+            outputs.push(channel);
+        }
+
+        while self.next_output < input_end {
+            self.next_output += self.decimation as u64;
+        }
+
+        let keep = (self.taps.len() - 1).min(input.len());
+        self.history_start = input_end - keep as u64;
+        self.history = input.split_off(input.len() - keep);
+        outputs
+    }
+}
+
+/// Shift a band to baseband and remove everything outside it, via one FFT of the whole signal
+pub fn extract_band(samples: &[IqSample], sample_rate: u32, center_hz: f64, bandwidth_hz: f64) -> Vec<IqSample> {
+    let len = samples.len();
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let mut planner = FftPlanner::<f32>::new();
+    let mut spectrum: Vec<Complex<f32>> = samples.iter().map(|s| Complex::new(s.i, s.q)).collect();
+    planner.plan_fft_forward(len).process(&mut spectrum);
+
+    // Move the band's bins down to DC, keeping only those within half the bandwidth
+    let bin_hz = sample_rate as f64 / len as f64;
+    let shift = (center_hz / bin_hz).round() as isize;
+    let half_width = ((bandwidth_hz / 2.0 / bin_hz).ceil() as isize).min(len as isize / 2);
+    let mut shifted = vec![Complex::new(0.0f32, 0.0); len];
+    for k in -half_width..=half_width {
+        let src = (shift + k).rem_euclid(len as isize) as usize;
+        let dst = k.rem_euclid(len as isize) as usize;
+        shifted[dst] = spectrum[src];
+    }
+
+    planner.plan_fft_inverse(len).process(&mut shifted);
+    let scale = 1.0 / len as f32;
+    shifted.iter().map(|c| IqSample::new(c.re * scale, c.im * scale)).collect()
+}
@@ -0,0 +1,185 @@
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::report::RunSummary;
+
+/// Timeout for each connection attempt, kept short so slicing isn't held up
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Minimum time between reconnection attempts after a failure
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest a send may block; a consumer that stops reading is dropped rather than stall detection
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// A live detection event, sent as one line of JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    /// A transmission crossed the detection threshold
+    Start {
+        time: String,
+        peak_db: f32,
+        channel_hz: Option<f64>,
+    },
+    /// A transmission ended; `filename` is null when it was too short to keep
+    End {
+        time: String,
+        filename: Option<&'a str>,
+        duration_ms: f32,
+        peak_db: f32,
+        channel_hz: Option<f64>,
+    },
+    /// The run finished (stdout only)
+    Summary {
+        time: String,
+        #[serde(flatten)]
+        summary: RunSummary,
+    },
+}
+
+impl Event<'_> {
+    /// Current local time as RFC3339, for the `time` field
+    pub fn now() -> String {
+        Local::now().to_rfc3339()
+    }
+}
+
+/// Newline-delimited JSON event feed over TCP and/or stdout
+/// Connection and send failures are logged and retried later; they never stop slicing
+pub struct EventSink {
+    tcp: Option<TcpFeed>,
+    stdout: bool,
+}
+
+impl EventSink {
+    /// Feed events to `addr` (if given) and to stdout (if `stdout`); None when neither is wanted
+    pub fn new(addr: Option<&str>, stdout: bool) -> Option<Self> {
+        if addr.is_none() && !stdout {
+            return None;
+        }
+        Some(Self {
+            tcp: addr.map(TcpFeed::new),
+            stdout,
+        })
+    }
+
+    /// Send one event to every destination
+    pub fn send(&mut self, event: &Event) {
+        let mut line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Could not encode event: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+        if self.stdout {
+            write_stdout(&line);
+        }
+        if let Some(tcp) = self.tcp.as_mut() {
+            tcp.send(&line);
+        }
+    }
+}
+
+/// Write one event line to stdout and flush it, so each line reaches a pipeline reader immediately
+pub fn print_event(event: &Event) {
+    match serde_json::to_vec(event) {
+        Ok(mut line) => {
+            line.push(b'\n');
+            write_stdout(&line);
+        }
+        Err(e) => warn!("Could not encode event: {}", e),
+    }
+}
+
+fn write_stdout(line: &[u8]) {
+    let mut stdout = std::io::stdout().lock();
+    if let Err(e) = stdout.write_all(line).and_then(|()| stdout.flush()) {
+        debug!("Could not write event to stdout: {}", e);
+    }
+}
+
+/// TCP connection to an event consumer, reconnected after failures
+struct TcpFeed {
+    addr: String,
+    stream: Option<TcpStream>,
+    last_attempt: Option<Instant>,
+}
+
+impl TcpFeed {
+    fn new(addr: &str) -> Self {
+        let mut feed = Self {
+            addr: addr.to_string(),
+            stream: None,
+            last_attempt: None,
+        };
+        feed.connect();
+        feed
+    }
+
+    fn connect(&mut self) {
+        self.last_attempt = Some(Instant::now());
+        let result = self
+            .addr
+            .to_socket_addrs()
+            .and_then(|mut addrs| {
+                addrs
+                    .next()
+                    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address resolved"))
+            })
+            .and_then(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT));
+        match result {
+            Ok(stream) => {
+                let _ = stream.set_nodelay(true);
+                if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                    warn!("Could not set a write timeout on event sink {}: {}", self.addr, e);
+                    return;
+                }
+                debug!("Connected to event sink at {}", self.addr);
+                self.stream = Some(stream);
+            }
+            Err(e) => warn!("Could not connect to event sink {}: {}", self.addr, e),
+        }
+    }
+
+    /// Send one encoded line, reconnecting first if the connection was lost and the retry interval has passed
+    /// A send that can't complete within `WRITE_TIMEOUT` drops the connection
+    fn send(&mut self, line: &[u8]) {
+        if self.stream.is_none() && self.last_attempt.is_none_or(|t| t.elapsed() >= RETRY_INTERVAL) {
+            self.connect();
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+        if let Err(e) = stream.write_all(line) {
+            warn!("Lost connection to event sink {}: {}", self.addr, e);
+            self.stream = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stalled_consumer_is_dropped_instead_of_blocking() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut feed = TcpFeed::new(&listener.local_addr().unwrap().to_string());
+        // Accepted but never read, so the socket buffers fill up
+        let (_consumer, _) = listener.accept().unwrap();
+        assert!(feed.stream.is_some());
+
+        let started = Instant::now();
+        feed.send(&vec![b'x'; 64 * 1024 * 1024]);
+        assert!(feed.stream.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5), "send blocked for {:?}", started.elapsed());
+    }
+}
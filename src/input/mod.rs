@@ -1,7 +1,11 @@
+use std::path::Path;
+use chrono::NaiveDateTime;
+
 pub mod wav;
+pub mod raw;
 pub mod stream;
 
-pub use stream::StreamFormat;
+pub use stream::{Endian, StreamFormat};
 
 /// IQ sample pair (In-phase, Quadrature)
 #[derive(Debug, Clone, Copy)]
@@ -14,6 +18,49 @@ impl IqSample {
     pub fn new(i: f32, q: f32) -> Self {
         Self { i, q }
     }
+
+    /// Whether I or Q is at (or within rounding of) full scale, i.e. the ADC clipped
+    pub fn is_saturated(&self) -> bool {
+        is_full_scale(self.i) || is_full_scale(self.q)
+    }
+}
+
+/// Replace NaN/Inf components (from corrupt float data) with 0.0, returning how many were replaced
+pub fn replace_non_finite(samples: &mut [IqSample]) -> usize {
+    let mut replaced = 0;
+    for value in samples.iter_mut().flat_map(|s| [&mut s.i, &mut s.q]) {
+        if !value.is_finite() {
+            *value = 0.0;
+            replaced += 1;
+        }
+    }
+    replaced
+}
+
+/// Magnitude at or above which a normalized sample counts as full scale
+const FULL_SCALE: f32 = 0.999;
+
+pub fn is_full_scale(value: f32) -> bool {
+    value.abs() >= FULL_SCALE
+}
+
+/// Whether the path looks like a recording the file mode can read: a WAV or a (possibly gzipped) raw IQ file
+pub fn is_iq_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav")) || raw::raw_format(path).is_some()
+}
+
+/// Read an IQ recording, choosing the reader from the file extension
+pub fn read_iq_file(path: &Path, options: &wav::WavOptions) -> Result<(Vec<IqSample>, IqMetadata), Box<dyn std::error::Error>> {
+    if raw::raw_format(path).is_some() {
+        return raw::read_iq_raw(path, options);
+    }
+    if options.endian != Endian::Little {
+        return Err(format!("{}: --endian applies to headerless raw inputs; WAV samples are always little-endian", path.display()).into());
+    }
+    if raw::is_gzip(path) {
+        return Err(format!("{}: only raw .cf32, .cs16 and .cs8 recordings can be read gzip-compressed", path.display()).into());
+    }
+    wav::read_iq_wav(path, options)
 }
 
 /// Metadata about the IQ source
@@ -22,4 +69,10 @@ pub struct IqMetadata {
     pub sample_rate: u32,
     #[allow(dead_code)]
     pub total_samples: Option<usize>, // None for streams
+    /// Samples whose I or Q hit full scale
+    pub saturated_samples: usize,
+    /// Tuned center frequency in Hz, when the source records it
+    pub center_frequency: Option<f64>,
+    /// Local wall-clock time of the first sample, when the source records it
+    pub start_time: Option<NaiveDateTime>,
 }
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use flate2::read::MultiGzDecoder;
+use log::debug;
+use super::stream::{IqStreamReader, StreamFormat};
+use super::wav::WavOptions;
+use super::{IqMetadata, IqSample};
+
+/// Samples decoded per read
+const READ_CHUNK_SAMPLES: usize = 64 * 1024;
+
+/// Whether the path names a gzip-compressed file
+pub fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// Sample format of a headerless IQ file from its extension (.cf32, .cs16 or .cs8, optionally
+/// followed by .gz); None for anything else
+pub fn raw_format(path: &Path) -> Option<StreamFormat> {
+    let path = if is_gzip(path) { Path::new(path.file_stem()?) } else { path };
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "cf32" | "fc32" => Some(StreamFormat::Float32),
+        "cs16" | "sc16" => Some(StreamFormat::Int16),
+        "cs8" | "sc8" => Some(StreamFormat::Int8),
+        _ => None,
+    }
+}
+
+/// Read a headerless interleaved IQ file, decompressing on the fly if it ends in .gz
+/// The file carries no sample rate, so `options.sample_rate` must be set; `options.endian` gives its byte order.
+/// Raw files always hold I/Q pairs: channels 0 and 1 may be swapped, but `--mono` is rejected.
+pub fn read_iq_raw(path: &Path, options: &WavOptions) -> Result<(Vec<IqSample>, IqMetadata), Box<dyn std::error::Error>> {
+    let format = raw_format(path).ok_or_else(|| format!("{} is not a .cf32, .cs16 or .cs8 file", path.display()))?;
+    let sample_rate = options
+        .sample_rate
+        .ok_or_else(|| format!("{} has no header; give its sample rate with --rate", path.display()))?;
+    if options.mono {
+        return Err(format!("{}: --mono applies to WAV input; raw files hold interleaved I/Q pairs", path.display()).into());
+    }
+    let swap = match (options.i_channel, options.q_channel) {
+        (0, 1) => false,
+        (1, 0) => true,
+        (i, q) => {
+            return Err(format!("{}: raw files have two channels, so --i-channel {} / --q-channel {} is out of range", path.display(), i, q).into())
+        }
+    };
+
+    let file = BufReader::new(File::open(path)?);
+    let source: Box<dyn Read> = if is_gzip(path) {
+        debug!("Decompressing {} as gzip", path.display());
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut reader = IqStreamReader::from_reader(source, format, options.endian)
+        .with_gain(options.gain)
+        .with_conjugate(options.conjugate);
+
+    let mut samples = Vec::new();
+    while let Some(chunk) = reader.read_chunk(READ_CHUNK_SAMPLES)? {
+        samples.extend(chunk);
+    }
+    if swap {
+        samples.iter_mut().for_each(|s| std::mem::swap(&mut s.i, &mut s.q));
+    }
+
+    let metadata = IqMetadata {
+        sample_rate,
+        total_samples: Some(samples.len()),
+        saturated_samples: samples.iter().filter(|s| s.is_saturated()).count(),
+        center_frequency: None,
+        start_time: None,
+    };
+    Ok((samples, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Endian;
+
+    #[test]
+    fn reads_big_endian_raw_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.cs16");
+        let values: [i16; 4] = [16384, -8192, 1, -32768];
+        std::fs::write(&path, values.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>()).unwrap();
+
+        let options = WavOptions {
+            sample_rate: Some(48000),
+            endian: Endian::Big,
+            ..WavOptions::default()
+        };
+        let (samples, _) = read_iq_raw(&path, &options).unwrap();
+        let values: Vec<(f32, f32)> = samples.iter().map(|s| (s.i, s.q)).collect();
+        assert_eq!(values, [(0.5, -0.25), (1.0 / 32768.0, -1.0)]);
+    }
+
+    #[test]
+    fn raw_input_swaps_channels_and_rejects_mono() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.cs8");
+        std::fs::write(&path, [64u8, 0xc0]).unwrap();
+        let options = WavOptions { sample_rate: Some(48000), ..WavOptions::default() };
+
+        let (samples, _) = read_iq_raw(&path, &WavOptions { i_channel: 1, q_channel: 0, ..options.clone() }).unwrap();
+        assert_eq!((samples[0].i, samples[0].q), (-0.5, 0.5));
+        assert!(read_iq_raw(&path, &WavOptions { mono: true, ..options.clone() }).is_err());
+        assert!(read_iq_raw(&path, &WavOptions { q_channel: 2, ..options }).is_err());
+    }
+}
@@ -1,6 +1,13 @@
-use std::io::{Read, BufReader};
+use std::io::{Cursor, Read, BufReader, ErrorKind};
 use std::net::TcpStream;
-use super::IqSample;
+use std::time::{Duration, Instant};
+use super::{replace_non_finite, IqSample};
+use log::{debug, warn};
+
+/// Bytes examined when auto-detecting the stream format
+const FORMAT_SNIFF_BYTES: usize = 64 * 1024;
+/// Longest wait for the first bytes when auto-detecting the stream format
+const FORMAT_SNIFF_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StreamFormat {
@@ -21,35 +28,223 @@ impl StreamFormat {
     }
 }
 
-/// Connect to SDR++ IQ Exporter via TCP
+/// Byte order of multi-byte stream samples
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn i16(self, bytes: [u8; 2]) -> i16 {
+        match self {
+            Endian::Little => i16::from_le_bytes(bytes),
+            Endian::Big => i16::from_be_bytes(bytes),
+        }
+    }
+
+    fn i32(self, bytes: [u8; 4]) -> i32 {
+        match self {
+            Endian::Little => i32::from_le_bytes(bytes),
+            Endian::Big => i32::from_be_bytes(bytes),
+        }
+    }
+
+    fn f32(self, bytes: [u8; 4]) -> f32 {
+        match self {
+            Endian::Little => f32::from_le_bytes(bytes),
+            Endian::Big => f32::from_be_bytes(bytes),
+        }
+    }
+}
+
+impl std::fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StreamFormat::Int8 => "int8",
+            StreamFormat::Int16 => "int16",
+            StreamFormat::Int32 => "int32",
+            StreamFormat::Float32 => "float32",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returned by `read_chunk` when no data arrived within the read timeout
+/// Bytes received so far are kept, so retrying resumes without losing sample alignment
+#[derive(Debug)]
+pub struct ReadTimeout;
+
+impl std::fmt::Display for ReadTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out waiting for stream data")
+    }
+}
+
+impl std::error::Error for ReadTimeout {}
+
+/// Read interleaved IQ from SDR++ IQ Exporter via TCP, or from any byte source such as stdin
 pub struct IqStreamReader {
-    reader: BufReader<TcpStream>,
+    reader: BufReader<Box<dyn Read>>,
     format: StreamFormat,
+    endian: Endian,
+    /// Linear amplitude factor applied after decoding
+    gain: f32,
+    /// Negate Q after decoding to undo spectral inversion
+    conjugate: bool,
+    buffer: Vec<u8>,
+    filled: usize,
+    /// NaN/Inf float values replaced with 0.0 so far
+    non_finite: usize,
 }
 
 impl IqStreamReader {
-    pub fn connect(addr: &str, format: StreamFormat) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn connect(
+        addr: &str,
+        format: StreamFormat,
+        endian: Endian,
+        read_timeout: Option<Duration>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let stream = TcpStream::connect(addr)?;
-        Ok(Self {
-            reader: BufReader::new(stream),
+        // Low-latency: don't let Nagle batch small writes from the sender
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(read_timeout)?;
+        Ok(Self::from_reader(Box::new(stream), format, endian))
+    }
+
+    /// Read samples from standard input, for use at the end of a shell pipeline
+    pub fn stdin(format: StreamFormat, endian: Endian) -> Self {
+        Self::from_reader(Box::new(std::io::stdin()), format, endian)
+    }
+
+    pub fn from_reader(reader: Box<dyn Read>, format: StreamFormat, endian: Endian) -> Self {
+        Self {
+            reader: BufReader::new(reader),
             format,
-        })
+            endian,
+            gain: 1.0,
+            conjugate: false,
+            buffer: Vec::new(),
+            filled: 0,
+            non_finite: 0,
+        }
+    }
+
+    /// Scale every decoded sample by a linear amplitude factor
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Negate Q on every decoded sample, undoing spectral inversion
+    pub fn with_conjugate(mut self, conjugate: bool) -> Self {
+        self.conjugate = conjugate;
+        self
+    }
+
+    /// Sample format currently decoded (the detected one after `detect_format`)
+    pub fn format(&self) -> StreamFormat {
+        self.format
+    }
+
+    /// Guess the sample format from the start of the stream and switch to it
+    /// The examined bytes are kept and decoded as normal afterwards. Returns None (keeping
+    /// the current format) when the data gives no usable clue, e.g. all zeros, or when nothing
+    /// arrives within `FORMAT_SNIFF_TIMEOUT`.
+    pub fn detect_format(&mut self) -> Result<Option<StreamFormat>, Box<dyn std::error::Error>> {
+        self.detect_format_within(FORMAT_SNIFF_TIMEOUT)
+    }
+
+    fn detect_format_within(&mut self, timeout: Duration) -> Result<Option<StreamFormat>, Box<dyn std::error::Error>> {
+        let mut head = vec![0u8; FORMAT_SNIFF_BYTES];
+        let mut filled = 0;
+        let deadline = Instant::now() + timeout;
+        while filled < head.len() {
+            match self.reader.read(&mut head[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                // Classify what arrived so far rather than stall on a slow sender
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if filled > 0 || Instant::now() >= deadline {
+                        break;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        head.truncate(filled);
+
+        let guess = guess_format(&head, self.endian);
+        if let Some(format) = guess {
+            self.format = format;
+        }
+
+        // Put the examined bytes back in front of the rest of the stream
+        let rest = std::mem::replace(&mut self.reader, BufReader::new(Box::new(std::io::empty())));
+        self.reader = BufReader::new(Box::new(Cursor::new(head).chain(rest)));
+        Ok(guess)
     }
 
     /// Read a chunk of IQ samples from the stream
-    /// Returns None on connection close
+    /// At end of stream any complete samples are returned as a short chunk (a trailing partial
+    /// sample is dropped); returns None once nothing remains.
+    /// Returns a `ReadTimeout` error if the read timeout elapses
     pub fn read_chunk(&mut self, num_samples: usize) -> Result<Option<Vec<IqSample>>, Box<dyn std::error::Error>> {
         let bytes_per_sample = self.format.bytes_per_sample();
         let bytes_needed = num_samples * bytes_per_sample;
-        let mut buffer = vec![0u8; bytes_needed];
+        self.buffer.resize(bytes_needed, 0);
+        self.filled = self.filled.min(bytes_needed);
 
-        match self.reader.read_exact(&mut buffer) {
-            Ok(_) => {},
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-            Err(e) => return Err(e.into()),
+        // Resume any partial read left over from a previous timeout
+        while self.filled < bytes_needed {
+            match self.reader.read(&mut self.buffer[self.filled..]) {
+                Ok(0) => {
+                    let complete = self.filled - self.filled % bytes_per_sample;
+                    self.filled = 0;
+                    if complete == 0 {
+                        return Ok(None);
+                    }
+                    let samples = Self::decode(self.format, self.endian, &self.buffer[..complete]);
+                    return Ok(Some(self.sanitize(samples)));
+                }
+                Ok(n) => self.filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Err(Box::new(ReadTimeout));
+                }
+                Err(e) => return Err(e.into()),
+            }
         }
+        self.filled = 0;
+
+        let samples = Self::decode(self.format, self.endian, &self.buffer);
+        Ok(Some(self.sanitize(samples)))
+    }
 
-        let samples = match self.format {
+    /// Zero out NaN/Inf values, warning the first time the stream contains any, then apply gain and conjugation
+    fn sanitize(&mut self, mut samples: Vec<IqSample>) -> Vec<IqSample> {
+        if self.format == StreamFormat::Float32 {
+            let replaced = replace_non_finite(&mut samples);
+            if replaced > 0 && self.non_finite == 0 {
+                warn!("Stream contains non-finite float samples; replacing them with 0");
+            }
+            self.non_finite += replaced;
+        }
+        let q_gain = if self.conjugate { -self.gain } else { self.gain };
+        if self.gain != 1.0 || self.conjugate {
+            for sample in &mut samples {
+                sample.i *= self.gain;
+                sample.q *= q_gain;
+            }
+        }
+        samples
+    }
+
+    /// Decode interleaved I/Q bytes into samples according to the stream format
+    fn decode(format: StreamFormat, endian: Endian, buffer: &[u8]) -> Vec<IqSample> {
+        match format {
             StreamFormat::Int8 => {
                 buffer.chunks_exact(2).map(|chunk| {
                     let i = (chunk[0] as i8) as f32 / 128.0;
@@ -59,27 +254,146 @@ impl IqStreamReader {
             }
             StreamFormat::Int16 => {
                 buffer.chunks_exact(4).map(|chunk| {
-                    let i = i16::from_le_bytes([chunk[0], chunk[1]]) as f32 / 32768.0;
-                    let q = i16::from_le_bytes([chunk[2], chunk[3]]) as f32 / 32768.0;
+                    let i = endian.i16([chunk[0], chunk[1]]) as f32 / 32768.0;
+                    let q = endian.i16([chunk[2], chunk[3]]) as f32 / 32768.0;
                     IqSample::new(i, q)
                 }).collect()
             }
             StreamFormat::Int32 => {
                 buffer.chunks_exact(8).map(|chunk| {
-                    let i = i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f32 / 2147483648.0;
-                    let q = i32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as f32 / 2147483648.0;
+                    let i = endian.i32([chunk[0], chunk[1], chunk[2], chunk[3]]) as f32 / 2147483648.0;
+                    let q = endian.i32([chunk[4], chunk[5], chunk[6], chunk[7]]) as f32 / 2147483648.0;
                     IqSample::new(i, q)
                 }).collect()
             }
             StreamFormat::Float32 => {
                 buffer.chunks_exact(8).map(|chunk| {
-                    let i = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                    let q = f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                    let i = endian.f32([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                    let q = endian.f32([chunk[4], chunk[5], chunk[6], chunk[7]]);
                     IqSample::new(i, q)
                 }).collect()
             }
+        }
+    }
+}
+
+/// Heuristically classify raw stream bytes as one of the supported sample formats
+///
+/// Float32 IQ is almost always finite, within a few units of full scale and rarely integer-valued.
+/// Integer formats are told apart by which byte positions carry little magnitude: in quiet or
+/// moderate signals the high byte of each int16 value, or the top byte of each int32 value,
+/// stays near 0x00/0xFF while the low bytes look random. Int8 shows no such pattern.
+fn guess_format(bytes: &[u8], endian: Endian) -> Option<StreamFormat> {
+    let words = bytes.len() / 4;
+    if words < 64 || bytes.iter().all(|&b| b == 0) {
+        return None;
+    }
+
+    let mut plausible_floats = 0usize;
+    let mut nonzero_floats = 0usize;
+    let mut integral_floats = 0usize;
+    for chunk in bytes.chunks_exact(4) {
+        let value = endian.f32([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        if value == 0.0 {
+            plausible_floats += 1;
+        } else if value.is_finite() && (1e-9..=16.0).contains(&value.abs()) {
+            plausible_floats += 1;
+            nonzero_floats += 1;
+            integral_floats += (value.fract() == 0.0) as usize;
+        }
+    }
+    let plausible = plausible_floats as f64 / words as f64;
+    debug!(
+        "Format detection: {:.1}% plausible float32 values, {} of {} non-zero integral",
+        plausible * 100.0,
+        integral_floats,
+        nonzero_floats
+    );
+    if plausible > 0.95 && integral_floats * 2 < nonzero_floats.max(1) {
+        return Some(StreamFormat::Float32);
+    }
+
+    // Mean magnitude of the byte at each position within a 4-byte word, least significant first
+    let mut magnitude = [0.0f64; 4];
+    for chunk in bytes.chunks_exact(4) {
+        for (k, &b) in chunk.iter().enumerate() {
+            magnitude[k] += (b as i8).unsigned_abs() as f64;
+        }
+    }
+    magnitude.iter_mut().for_each(|m| *m /= words as f64);
+    if endian == Endian::Big {
+        magnitude.reverse();
+    }
+    debug!("Format detection: mean byte magnitude by position {:.1?}", magnitude);
+
+    let loudest = magnitude.iter().cloned().fold(0.0, f64::max);
+    let quiet = magnitude.map(|m| m < 0.5 * loudest);
+    Some(match quiet {
+        [false, true, false, true] => StreamFormat::Int16,
+        [_, _, _, true] => StreamFormat::Int32,
+        _ => StreamFormat::Int8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int16_bytes(values: impl IntoIterator<Item = i16>) -> Vec<u8> {
+        values.into_iter().flat_map(i16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn read_chunk_returns_short_final_chunk() {
+        // 199 samples (one short of two 100-sample chunks), then half a sample
+        let mut bytes = int16_bytes((0..199 * 2).map(|n| n as i16));
+        bytes.extend([0x12, 0x34]);
+        let mut reader = IqStreamReader::from_reader(Box::new(Cursor::new(bytes)), StreamFormat::Int16, Endian::Little);
+
+        assert_eq!(reader.read_chunk(100).unwrap().unwrap().len(), 100);
+        let last = reader.read_chunk(100).unwrap().unwrap();
+        assert_eq!(last.len(), 99);
+        assert_eq!(last[98].i, 396.0 / 32768.0);
+        assert_eq!(last[98].q, 397.0 / 32768.0);
+        assert!(reader.read_chunk(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn decodes_int16_pair_in_either_byte_order() {
+        let bytes = [0x12, 0x34, 0xff, 0x00];
+        let decode = |endian| {
+            let mut reader = IqStreamReader::from_reader(Box::new(Cursor::new(bytes)), StreamFormat::Int16, endian);
+            let sample = reader.read_chunk(1).unwrap().unwrap()[0];
+            (sample.i * 32768.0, sample.q * 32768.0)
         };
+        assert_eq!(decode(Endian::Little), (0x3412 as f32, 0x00ff as f32));
+        assert_eq!(decode(Endian::Big), (0x1234 as f32, -256.0));
+    }
+
+    #[test]
+    fn conjugate_negates_q_only() {
+        let bytes = int16_bytes([1000, 2000, -3000, 4000]);
+        let mut reader = IqStreamReader::from_reader(Box::new(Cursor::new(bytes)), StreamFormat::Int16, Endian::Little)
+            .with_conjugate(true);
+        let values: Vec<(f32, f32)> = reader.read_chunk(2).unwrap().unwrap().iter().map(|s| (s.i * 32768.0, s.q * 32768.0)).collect();
+        assert_eq!(values, [(1000.0, -2000.0), (-3000.0, -4000.0)]);
+    }
+
+    /// A sender that never delivers anything, timing out on every read
+    struct Silent;
+
+    impl Read for Silent {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(5));
+            Err(ErrorKind::TimedOut.into())
+        }
+    }
 
-        Ok(Some(samples))
+    #[test]
+    fn detect_format_gives_up_on_a_silent_sender() {
+        let mut reader = IqStreamReader::from_reader(Box::new(Silent), StreamFormat::Int16, Endian::Little);
+        assert_eq!(reader.detect_format_within(Duration::from_millis(50)).unwrap(), None);
+        assert_eq!(reader.format(), StreamFormat::Int16);
+        assert!(reader.read_chunk(10).unwrap_err().downcast_ref::<ReadTimeout>().is_some());
     }
 }
@@ -1,55 +1,597 @@
-use hound::{WavReader, SampleFormat};
+use hound::{WavReader, WavSpec, SampleFormat};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
-use super::{IqSample, IqMetadata};
+use super::{is_full_scale, Endian, IqSample, IqMetadata};
+use crate::dsp::hilbert_to_iq;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::{debug, warn};
+
+type FileWavReader = WavReader<BufReader<File>>;
+
+/// Largest fmt chunk accepted; WAVE_FORMAT_EXTENSIBLE needs 40 bytes
+const MAX_FMT_CHUNK_BYTES: u32 = 256;
+
+/// Where a WAV file's samples are read from
+enum WavSource {
+    Hound(FileWavReader),
+    /// Data chunk read directly up to end of file, for files whose header length can't be trusted
+    Raw { reader: BufReader<File>, spec: WavSpec, frames: usize },
+}
+
+impl WavSource {
+    fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(source) = open_untrusted_length(path)? {
+            return Ok(source);
+        }
+        Ok(WavSource::Hound(WavReader::open(path)?))
+    }
+
+    fn spec(&self) -> WavSpec {
+        match self {
+            WavSource::Hound(reader) => reader.spec(),
+            WavSource::Raw { spec, .. } => *spec,
+        }
+    }
+
+    fn frames(&self) -> usize {
+        match self {
+            WavSource::Hound(reader) => reader.len() as usize / reader.spec().channels.max(1) as usize,
+            WavSource::Raw { frames, .. } => *frames,
+        }
+    }
+}
+
+/// Check the RIFF data chunk length against the file size, returning a raw source when it can't be right:
+/// files over 4 GiB (the 32-bit length wrapped), streaming placeholders (0 or 0xFFFFFFFF), or a length
+/// running past end of file (an interrupted recording). Returns None for files hound reads correctly.
+/// Pipes and FIFOs can't be seeked or sized, so they are always read as a raw source until end of input.
+fn open_untrusted_length(path: &Path) -> Result<Option<WavSource>, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let metadata = reader.get_ref().metadata()?;
+    let pipe = !metadata.is_file();
+    let file_len = metadata.len();
+
+    let mut header = [0u8; 12];
+    if reader.read_exact(&mut header).is_err() || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        if pipe {
+            // Whatever was read is gone, so hound can't take another look
+            return Err(format!("{} is not a RIFF WAV stream", path.display()).into());
+        }
+        // Not a plain RIFF WAV; let hound report what's wrong with it
+        return Ok(None);
+    }
+
+    let mut spec = None;
+    let mut position = 12u64;
+    loop {
+        let mut chunk = [0u8; 8];
+        if reader.read_exact(&mut chunk).is_err() {
+            if pipe {
+                return Err(format!("{} ended before its data chunk", path.display()).into());
+            }
+            return Ok(None);
+        }
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        position += 8;
+
+        if &chunk[0..4] == b"fmt " {
+            if size > MAX_FMT_CHUNK_BYTES {
+                return Err(format!("{} has a {}-byte fmt chunk, which can't be a valid WAV header", path.display(), size).into());
+            }
+            let mut fmt = vec![0u8; size as usize];
+            reader.read_exact(&mut fmt)?;
+            spec = parse_fmt_chunk(&fmt);
+        } else if &chunk[0..4] == b"data" && pipe {
+            let spec = spec.ok_or_else(|| format!("{} has no usable fmt chunk", path.display()))?;
+            debug!("Reading WAV from a pipe until end of input");
+            return Ok(Some(WavSource::Raw { reader, spec, frames: 0 }));
+        } else if &chunk[0..4] == b"data" {
+            let available = file_len.saturating_sub(position);
+            let trusted = size as u64 == available
+                || (size != 0 && size != u32::MAX && size as u64 <= available && available <= u32::MAX as u64);
+            if trusted {
+                return Ok(None);
+            }
+            let Some(spec) = spec else {
+                return Ok(None);
+            };
+            let frame_bytes = spec.channels as u64 * spec.bits_per_sample.div_ceil(8) as u64;
+            warn!(
+                "WAV header claims {} bytes of data but {} follow the header; reading samples up to end of file",
+                size, available
+            );
+            return Ok(Some(WavSource::Raw {
+                reader,
+                spec,
+                frames: (available / frame_bytes.max(1)) as usize,
+            }));
+        } else if pipe {
+            std::io::copy(&mut (&mut reader).take(size as u64 + (size & 1) as u64), &mut std::io::sink())?;
+        } else {
+            reader.seek(SeekFrom::Current(size as i64 + (size & 1) as i64))?;
+        }
+        position += size as u64 + (size & 1) as u64;
+    }
+}
+
+/// Sample layout from a `fmt ` chunk body, for the formats `read_frames` supports
+fn parse_fmt_chunk(fmt: &[u8]) -> Option<WavSpec> {
+    if fmt.len() < 16 {
+        return None;
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+    let mut format_tag = u16_at(0);
+    // WAVE_FORMAT_EXTENSIBLE keeps the real format in the first two bytes of the subformat GUID
+    if format_tag == 0xFFFE && fmt.len() >= 26 {
+        format_tag = u16_at(24);
+    }
+    let sample_format = match format_tag {
+        1 => SampleFormat::Int,
+        3 => SampleFormat::Float,
+        _ => return None,
+    };
+    Some(WavSpec {
+        channels: u16_at(2),
+        sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+        bits_per_sample: u16_at(14),
+        sample_format,
+    })
+}
+
+/// Options controlling how an input file is interpreted as IQ
+#[derive(Debug, Clone)]
+pub struct WavOptions {
+    /// Treat the I channel as real-valued and synthesize Q via Hilbert transform
+    pub mono: bool,
+    /// Channel index (0-based) holding I
+    pub i_channel: usize,
+    /// Channel index (0-based) holding Q
+    pub q_channel: usize,
+    /// Linear amplitude factor applied to every sample
+    pub gain: f32,
+    /// Negate Q to undo spectral inversion
+    pub conjugate: bool,
+    /// Sample rate of headerless raw inputs, which don't record it
+    pub sample_rate: Option<u32>,
+    /// Byte order of headerless raw inputs; WAV samples are always little-endian
+    pub endian: Endian,
+}
+
+impl Default for WavOptions {
+    fn default() -> Self {
+        Self {
+            mono: false,
+            i_channel: 0,
+            q_channel: 1,
+            gain: 1.0,
+            conjugate: false,
+            sample_rate: None,
+            endian: Endian::Little,
+        }
+    }
+}
+
+/// Returned by `read_iq_wav` for a single-channel WAV read without `--mono`
+#[derive(Debug)]
+pub struct NotIq {
+    pub channels: usize,
+}
+
+impl std::fmt::Display for NotIq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected stereo WAV (I/Q), got {} channels (use --mono for real-valued recordings)", self.channels)
+    }
+}
+
+impl std::error::Error for NotIq {}
 
 /// Read IQ samples from an SDR++ WAV file
 /// SDR++ saves IQ as stereo float32: I=left channel, Q=right channel
-pub fn read_iq_wav<P: AsRef<Path>>(path: P) -> Result<(Vec<IqSample>, IqMetadata), Box<dyn std::error::Error>> {
-    let reader = WavReader::open(path)?;
-    let spec = reader.spec();
+/// Multi-channel files are supported by selecting the I and Q channel indices
+pub fn read_iq_wav<P: AsRef<Path>>(path: P, options: &WavOptions) -> Result<(Vec<IqSample>, IqMetadata), Box<dyn std::error::Error>> {
+    let source = WavSource::open(path.as_ref())?;
+    let spec = source.spec();
+    let channels = spec.channels as usize;
 
     // Validate format
-    if spec.channels != 2 {
-        return Err(format!("Expected stereo WAV (I/Q), got {} channels", spec.channels).into());
+    if options.i_channel >= channels {
+        return Err(format!("--i-channel {} out of range for {}-channel WAV", options.i_channel, channels).into());
+    }
+    if !options.mono {
+        if channels < 2 {
+            return Err(Box::new(NotIq { channels }));
+        }
+        if options.q_channel >= channels {
+            return Err(format!("--q-channel {} out of range for {}-channel WAV", options.q_channel, channels).into());
+        }
     }
 
-    let metadata = IqMetadata {
+    if spec.sample_rate == 0 {
+        return Err(format!("{} has a sample rate of 0 Hz in its header", path.as_ref().display()).into());
+    }
+
+    let mut metadata = IqMetadata {
         sample_rate: spec.sample_rate,
-        total_samples: Some(reader.len() as usize / 2), // stereo samples
+        total_samples: Some(source.frames()),
+        saturated_samples: 0,
+        center_frequency: None,
+        start_time: None,
     };
+    if let Some((center_frequency, start_time)) = parse_sdrpp_filename(path.as_ref()) {
+        debug!("SDR++ recording: center {:.0} Hz, started {:?}", center_frequency, start_time);
+        metadata.center_frequency = Some(center_frequency);
+        metadata.start_time = start_time;
+    }
 
-    let samples = match spec.sample_format {
-        SampleFormat::Float => read_float_samples(reader)?,
-        SampleFormat::Int => read_int_samples(reader, spec.bits_per_sample)?,
-    };
+    if options.mono {
+        let mut real = Vec::new();
+        read_frames(source, |frame| {
+            let value = frame[options.i_channel] * options.gain;
+            metadata.saturated_samples += is_full_scale(value) as usize;
+            real.push(value);
+        })?;
+        metadata.total_samples = Some(real.len());
+        let mut samples = hilbert_to_iq(&real);
+        if options.conjugate {
+            samples.iter_mut().for_each(|s| s.q = -s.q);
+        }
+        return Ok((samples, metadata));
+    }
+
+    let q_gain = if options.conjugate { -options.gain } else { options.gain };
+    let mut samples = Vec::new();
+    read_frames(source, |frame| {
+        let sample = IqSample::new(
+            frame[options.i_channel] * options.gain,
+            frame[options.q_channel] * q_gain,
+        );
+        metadata.saturated_samples += sample.is_saturated() as usize;
+        samples.push(sample);
+    })?;
+    // A pipe's length is only known once it has been read
+    metadata.total_samples = Some(samples.len());
 
     Ok((samples, metadata))
 }
 
-fn read_float_samples(mut reader: WavReader<std::io::BufReader<std::fs::File>>) -> Result<Vec<IqSample>, Box<dyn std::error::Error>> {
-    let mut samples = Vec::new();
+/// Header-level facts about a WAV file, gathered without decoding any samples
+#[derive(Debug, Clone)]
+pub struct WavInfo {
+    pub spec: WavSpec,
+    pub file_bytes: u64,
+    /// Complete sample frames (one sample per channel) in the data chunk
+    pub frames: usize,
+    /// Top-level RIFF chunks in file order, with their declared sizes
+    pub chunks: Vec<(String, u32)>,
+    /// Text fields from LIST/INFO chunks, such as INAM (title) or ICMT (comment)
+    pub info_tags: Vec<(String, String)>,
+    /// Center frequency and start time from an SDR++ recording name
+    pub sdrpp: Option<(f64, Option<NaiveDateTime>)>,
+}
+
+impl WavInfo {
+    pub fn duration_seconds(&self) -> f64 {
+        if self.spec.sample_rate == 0 {
+            return 0.0;
+        }
+        self.frames as f64 / self.spec.sample_rate as f64
+    }
+}
+
+/// Read a WAV file's format, chunk layout and INFO tags without reading samples
+pub fn read_wav_info(path: &Path) -> Result<WavInfo, Box<dyn std::error::Error>> {
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a regular file", path.display()).into());
+    }
+    let source = WavSource::open(path)?;
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut chunks = Vec::new();
+    let mut info_tags = Vec::new();
+    reader.seek(SeekFrom::Start(12))?;
+    let mut chunk = [0u8; 8];
+    while reader.read_exact(&mut chunk).is_ok() {
+        let id = String::from_utf8_lossy(&chunk[0..4]).into_owned();
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        let padded = size as u64 + (size & 1) as u64;
+        if id == "LIST" && size >= 4 {
+            let mut body = Vec::new();
+            (&mut reader).take(padded).read_to_end(&mut body)?;
+            if body.starts_with(b"INFO") {
+                info_tags.extend(parse_info_list(&body[4..]));
+            }
+        } else {
+            reader.seek(SeekFrom::Current(padded as i64))?;
+        }
+        chunks.push((id, size));
+    }
+
+    Ok(WavInfo {
+        spec: source.spec(),
+        file_bytes: metadata.len(),
+        frames: source.frames(),
+        chunks,
+        info_tags,
+        sdrpp: parse_sdrpp_filename(path),
+    })
+}
+
+/// Sub-chunks of a LIST/INFO body as (id, text) pairs, the text cut at its NUL terminator
+fn parse_info_list(mut body: &[u8]) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    while body.len() >= 8 {
+        let id = String::from_utf8_lossy(&body[0..4]).into_owned();
+        let size = u32::from_le_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let text = &body[8..(8 + size).min(body.len())];
+        let text = text.split(|&b| b == 0).next().unwrap_or_default();
+        tags.push((id, String::from_utf8_lossy(text).into_owned()));
+        body = &body[(8 + size + (size & 1)).min(body.len())..];
+    }
+    tags
+}
+
+/// Center frequency and start time from an SDR++ recording name such as
+/// `baseband_433920000Hz_14-23-01_03-01-2026.wav`; None if the name has no `<freq>Hz` field.
+/// WAV LIST/INFO chunks aren't consulted: hound doesn't expose them and SDR++ doesn't write them.
+fn parse_sdrpp_filename(path: &Path) -> Option<(f64, Option<NaiveDateTime>)> {
+    let stem = path.file_stem()?.to_str()?;
+    let fields: Vec<&str> = stem.split('_').collect();
+    let freq_index = fields
+        .iter()
+        .position(|field| field.strip_suffix("Hz").is_some_and(|hz| hz.parse::<f64>().is_ok()))?;
+    let center_frequency: f64 = fields[freq_index].strip_suffix("Hz")?.parse().ok()?;
+
+    // Time of day then date follow the frequency
+    let start_time = match (fields.get(freq_index + 1), fields.get(freq_index + 2)) {
+        (Some(time), Some(date)) => NaiveTime::parse_from_str(time, "%H-%M-%S")
+            .ok()
+            .zip(NaiveDate::parse_from_str(date, "%d-%m-%Y").ok())
+            .map(|(time, date)| date.and_time(time)),
+        _ => None,
+    };
+    Some((center_frequency, start_time))
+}
+
+/// Read every complete frame (one sample per channel) as normalized f32, passing each to `emit`
+fn read_frames(source: WavSource, emit: impl FnMut(&[f32])) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = source.spec();
+    match (spec.sample_format, spec.bits_per_sample, source) {
+        (SampleFormat::Float, 32, WavSource::Hound(reader)) => read_float_samples(reader, emit),
+        (SampleFormat::Int, 8 | 16 | 24 | 32, WavSource::Hound(reader)) => read_int_samples(reader, spec.bits_per_sample, emit),
+        (SampleFormat::Float, 32, WavSource::Raw { reader, .. }) | (SampleFormat::Int, 8 | 16 | 24 | 32, WavSource::Raw { reader, .. }) => {
+            read_raw_samples(reader, spec, emit)
+        }
+        (SampleFormat::Float, bits, _) => Err(format!("Unsupported WAV format: {}-bit float (only 32-bit float is supported)", bits).into()),
+        (SampleFormat::Int, bits, _) => Err(format!("Unsupported WAV format: {}-bit integer (supported: 8, 16, 24, 32)", bits).into()),
+    }
+}
+
+/// Non-finite values (from corrupt or truncated files) are replaced with 0.0 and reported
+fn read_float_samples(mut reader: FileWavReader, mut emit: impl FnMut(&[f32])) -> Result<(), Box<dyn std::error::Error>> {
+    let mut frame = vec![0.0f32; reader.spec().channels as usize];
     let mut iter = reader.samples::<f32>();
+    let mut non_finite = 0usize;
+
+    'frames: loop {
+        for slot in frame.iter_mut() {
+            match iter.next() {
+                Some(value) => {
+                    let value = value?;
+                    *slot = if value.is_finite() {
+                        value
+                    } else {
+                        non_finite += 1;
+                        0.0
+                    };
+                }
+                None => break 'frames,
+            }
+        }
+        emit(&frame);
+    }
 
-    while let (Some(i_result), Some(q_result)) = (iter.next(), iter.next()) {
-        let i = i_result?;
-        let q = q_result?;
-        samples.push(IqSample::new(i, q));
+    if non_finite > 0 {
+        warn!("Replaced {} non-finite (NaN/Inf) sample value(s) with 0", non_finite);
     }
 
-    Ok(samples)
+    Ok(())
 }
 
-fn read_int_samples(mut reader: WavReader<std::io::BufReader<std::fs::File>>, bits: u16) -> Result<Vec<IqSample>, Box<dyn std::error::Error>> {
-    let mut samples = Vec::new();
-    let max_val = (1i32 << (bits - 1)) as f32;
+fn read_int_samples(mut reader: FileWavReader, bits: u16, mut emit: impl FnMut(&[f32])) -> Result<(), Box<dyn std::error::Error>> {
+    let mut frame = vec![0.0f32; reader.spec().channels as usize];
+    // hound sign-extends each sample into i32 by its declared bit depth, including
+    // 24-bit audio stored in 32-bit containers, so full scale is always 2^(bits-1)
+    let max_val = (1i64 << (bits - 1)) as f32;
 
     let mut iter = reader.samples::<i32>();
 
-    while let (Some(i_result), Some(q_result)) = (iter.next(), iter.next()) {
-        let i = i_result? as f32 / max_val;
-        let q = q_result? as f32 / max_val;
-        samples.push(IqSample::new(i, q));
+    'frames: loop {
+        for slot in frame.iter_mut() {
+            match iter.next() {
+                Some(value) => *slot = value? as f32 / max_val,
+                None => break 'frames,
+            }
+        }
+        emit(&frame);
+    }
+
+    Ok(())
+}
+
+/// Decode little-endian PCM or float frames straight from the data chunk until end of file
+/// A trailing partial frame is ignored
+fn read_raw_samples(mut reader: BufReader<File>, spec: WavSpec, mut emit: impl FnMut(&[f32])) -> Result<(), Box<dyn std::error::Error>> {
+    let channels = spec.channels as usize;
+    let sample_bytes = spec.bits_per_sample as usize / 8;
+    let max_val = (1i64 << (spec.bits_per_sample - 1)) as f32;
+    let mut frame = vec![0.0f32; channels];
+    let mut bytes = vec![0u8; channels * sample_bytes];
+    let mut non_finite = 0usize;
+
+    while read_full(&mut reader, &mut bytes)? {
+        for (slot, raw) in frame.iter_mut().zip(bytes.chunks_exact(sample_bytes)) {
+            *slot = match (spec.sample_format, sample_bytes) {
+                (SampleFormat::Float, _) => {
+                    let value = f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]);
+                    if value.is_finite() {
+                        value
+                    } else {
+                        non_finite += 1;
+                        0.0
+                    }
+                }
+                // 8-bit WAV is unsigned
+                (SampleFormat::Int, 1) => (raw[0] as i32 - 128) as f32 / max_val,
+                (SampleFormat::Int, 2) => i16::from_le_bytes([raw[0], raw[1]]) as f32 / max_val,
+                (SampleFormat::Int, 3) => (i32::from_le_bytes([0, raw[0], raw[1], raw[2]]) >> 8) as f32 / max_val,
+                (SampleFormat::Int, _) => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f32 / max_val,
+            };
+        }
+        emit(&frame);
     }
 
-    Ok(samples)
+    if non_finite > 0 {
+        warn!("Replaced {} non-finite (NaN/Inf) sample value(s) with 0", non_finite);
+    }
+
+    Ok(())
+}
+
+/// Fill `buffer` completely, returning false at end of file
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a stereo 24-bit fixture, packed in 3 or 4 bytes per sample
+    fn read_24_bit_fixture(bytes_per_sample: u16) -> (Vec<IqSample>, IqMetadata) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("24bit.wav");
+        let spec = hound::WavSpecEx {
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 48000,
+                bits_per_sample: 24,
+                sample_format: SampleFormat::Int,
+            },
+            bytes_per_sample,
+        };
+        let file = std::io::BufWriter::new(File::create(&path).unwrap());
+        let mut writer = hound::WavWriter::new_with_spec_ex(file, spec).unwrap();
+        for (i, q) in [(1 << 22, -(1 << 23)), ((1 << 23) - 1, 0), (-(1 << 21), 1 << 20)] {
+            writer.write_sample(i).unwrap();
+            writer.write_sample(q).unwrap();
+        }
+        writer.finalize().unwrap();
+        read_iq_wav(&path, &WavOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn reads_24_bit_stereo_at_full_scale() {
+        for bytes_per_sample in [3, 4] {
+            let (samples, metadata) = read_24_bit_fixture(bytes_per_sample);
+            assert_eq!(metadata.sample_rate, 48000);
+            let values: Vec<(f32, f32)> = samples.iter().map(|s| (s.i, s.q)).collect();
+            assert_eq!(values, [(0.5, -1.0), (1.0 - 1.0 / 8388608.0, 0.0), (-0.25, 0.125)], "{}-byte container", bytes_per_sample);
+            assert_eq!(metadata.saturated_samples, 2);
+        }
+    }
+
+    /// A float32 stereo WAV header whose fmt and data chunks declare the given sizes, followed by `frames`
+    fn wav_with_sizes(fmt_size: u32, data_size: u32, frames: &[(f32, f32)]) -> Vec<u8> {
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        bytes.extend(fmt_size.to_le_bytes());
+        // IEEE float, 2 channels, 48 kHz, 384000 bytes/s, 8-byte frames, 32 bits
+        for field in [3u16, 2] {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.extend(48000u32.to_le_bytes());
+        bytes.extend(384000u32.to_le_bytes());
+        for field in [8u16, 32] {
+            bytes.extend(field.to_le_bytes());
+        }
+        bytes.extend(b"data");
+        bytes.extend(data_size.to_le_bytes());
+        for &(i, q) in frames {
+            bytes.extend(i.to_le_bytes());
+            bytes.extend(q.to_le_bytes());
+        }
+        let riff_size = bytes.len() as u32 - 8;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn data_length_past_end_of_file_reads_what_is_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("truncated.wav");
+        std::fs::write(&path, wav_with_sizes(16, 0x7fff_0000, &[(0.5, -0.5), (0.25, 0.125)])).unwrap();
+
+        let (samples, metadata) = read_iq_wav(&path, &WavOptions::default()).unwrap();
+        assert_eq!(metadata.total_samples, Some(2));
+        let values: Vec<(f32, f32)> = samples.iter().map(|s| (s.i, s.q)).collect();
+        assert_eq!(values, [(0.5, -0.5), (0.25, 0.125)]);
+    }
+
+    #[test]
+    fn impossible_fmt_chunk_length_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad_fmt.wav");
+        std::fs::write(&path, wav_with_sizes(0xffff_fff0, 16, &[(0.5, -0.5), (0.25, 0.125)])).unwrap();
+
+        let error = read_iq_wav(&path, &WavOptions::default()).unwrap_err().to_string();
+        assert!(error.contains("fmt chunk"), "{}", error);
+    }
+
+    #[test]
+    fn zero_sample_rate_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("no_rate.wav");
+        let frames = [(0.5, -0.5), (0.25, 0.125)];
+        let mut bytes = wav_with_sizes(16, 16, &frames);
+        // Sample rate and byte rate fields of the fmt chunk
+        bytes[24..32].fill(0);
+        std::fs::write(&path, bytes).unwrap();
+
+        let error = read_iq_wav(&path, &WavOptions::default()).unwrap_err().to_string();
+        assert!(error.contains("sample rate of 0 Hz"), "{}", error);
+    }
+
+    /// Mean phase step between consecutive samples in cycles; the sign gives the side of DC
+    fn mean_frequency(samples: &[IqSample]) -> f32 {
+        let (re, im) = samples.windows(2).fold((0.0, 0.0), |(re, im), pair| {
+            let (a, b) = (pair[0], pair[1]);
+            (re + b.i * a.i + b.q * a.q, im + b.q * a.i - b.i * a.q)
+        });
+        f32::atan2(im, re) / (2.0 * std::f32::consts::PI)
+    }
+
+    #[test]
+    fn conjugate_moves_tone_to_the_mirror_frequency() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = WavSpec { channels: 2, sample_rate: 48000, bits_per_sample: 32, sample_format: SampleFormat::Float };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for n in 0..1024 {
+            let phase = 2.0 * std::f32::consts::PI * 0.125 * n as f32;
+            writer.write_sample(0.5 * phase.cos()).unwrap();
+            writer.write_sample(0.5 * phase.sin()).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (plain, _) = read_iq_wav(&path, &WavOptions::default()).unwrap();
+        let (conjugated, _) = read_iq_wav(&path, &WavOptions { conjugate: true, ..WavOptions::default() }).unwrap();
+        assert!((mean_frequency(&plain) - 0.125).abs() < 1e-3);
+        assert!((mean_frequency(&conjugated) + 0.125).abs() < 1e-3);
+        assert!(plain.iter().zip(&conjugated).all(|(a, b)| a.i == b.i && a.q == -b.q));
+    }
 }
@@ -0,0 +1,12 @@
+//! Automatic transmission detection and slicing for IQ recordings
+//!
+//! The `iq-slicer` binary is a thin command-line front end over these modules; `slicer` holds the
+//! file and stream pipelines, including [`slicer::process_samples`] for samples already in memory.
+
+pub mod input;
+pub mod detector;
+pub mod dsp;
+pub mod events;
+pub mod output;
+pub mod report;
+pub mod slicer;
@@ -1,10 +1,9 @@
 use clap::{Parser, Subcommand, Args as ClapArgs, ValueEnum};
+use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 
-mod input;
-mod detector;
-mod output;
-mod slicer;
+use iq_slicer::{detector, dsp, input, output, slicer};
 
 /// Automatically detect and slice transmissions from IQ recordings
 #[derive(Parser, Debug)]
@@ -21,15 +20,37 @@ enum Command {
     File(FileArgs),
     /// Connect to SDR++ Network Sink for live streaming
     Stream(StreamArgs),
+    /// Print a WAV file's header fields and chunk layout without reading its samples
+    Info(InfoArgs),
+    /// Rewrite a directory of WAV slices in another output format, keeping their names
+    Convert(ConvertArgs),
 }
 
 /// Output WAV sample format
 #[derive(ValueEnum, Clone, Debug)]
 enum OutputFormat {
+    /// 8-bit integer (smallest files)
+    Int8,
     /// 16-bit integer (for URH)
     Int16,
+    /// 32-bit integer (most precision in an integer WAV)
+    Int32,
     /// 32-bit float (for inspectrum)
     Float32,
+    /// Headerless interleaved float32 .cf32 (for GNU Radio)
+    Cf32,
+}
+
+impl OutputFormat {
+    fn slice_writer(&self) -> output::SliceWriter {
+        match self {
+            OutputFormat::Int8 => output::SliceWriter::WavInt8,
+            OutputFormat::Int16 => output::SliceWriter::WavInt16,
+            OutputFormat::Int32 => output::SliceWriter::WavInt32,
+            OutputFormat::Float32 => output::SliceWriter::WavFloat32,
+            OutputFormat::Cf32 => output::SliceWriter::Cf32,
+        }
+    }
 }
 
 /// Input stream sample format
@@ -43,6 +64,132 @@ enum InputFormat {
     Int32,
     /// 32-bit float
     Float32,
+    /// Guess from the first 64 KiB of the stream
+    Auto,
+}
+
+/// Detection algorithm
+#[derive(ValueEnum, Clone, Debug)]
+enum DetectorKind {
+    /// Global auto-threshold with hysteresis
+    Threshold,
+    /// Cell-averaging constant false-alarm rate (adapts to local noise)
+    Cfar,
+    /// Time-frequency segmentation; simultaneous signals on different frequencies become separate slices
+    Spectrogram,
+    /// Spectral flatness; triggers on structured spectra regardless of power level
+    Flatness,
+    /// Power at a single frequency (--target-freq) via the Goertzel algorithm
+    Goertzel,
+}
+
+/// Noise floor estimation method
+#[derive(ValueEnum, Clone, Debug)]
+enum NoiseEstimatorKind {
+    /// 10th percentile of frame powers
+    Percentile,
+    /// Most common power level (histogram mode), robust on busy bands
+    Mode,
+}
+
+/// Byte order of multi-byte stream samples
+#[derive(ValueEnum, Clone, Debug)]
+enum EndianKind {
+    Little,
+    Big,
+}
+
+impl EndianKind {
+    fn endian(&self) -> input::Endian {
+        match self {
+            EndianKind::Little => input::Endian::Little,
+            EndianKind::Big => input::Endian::Big,
+        }
+    }
+}
+
+/// What the stream threshold margin is measured from
+#[derive(ValueEnum, Clone, Debug)]
+enum StreamThresholdKind {
+    /// Running noise floor average
+    Margin,
+    /// A percentile of recent chunk powers (--threshold-percentile over --percentile-window)
+    Percentile,
+}
+
+/// Per-frame power measure used for detection
+#[derive(ValueEnum, Clone, Debug)]
+enum PowerMetricKind {
+    /// Strongest FFT bin (narrowband bursts)
+    Peak,
+    /// Total power across all non-DC bins (wideband and spread-spectrum signals)
+    Total,
+}
+
+/// Stereo channel assignment of I/Q WAVs
+#[derive(ValueEnum, Clone, Debug)]
+enum ChannelOrderKind {
+    /// I left, Q right (SDR++, URH, inspectrum)
+    Iq,
+    /// Q left, I right, for tools that show a mirrored spectrum with the default
+    Qi,
+}
+
+impl ChannelOrderKind {
+    fn order(&self) -> output::ChannelOrder {
+        match self {
+            ChannelOrderKind::Iq => output::ChannelOrder::Iq,
+            ChannelOrderKind::Qi => output::ChannelOrder::Qi,
+        }
+    }
+}
+
+/// Level measure for slice normalization
+#[derive(ValueEnum, Clone, Debug)]
+enum NormalizeKind {
+    /// Largest sample magnitude
+    Peak,
+    /// RMS magnitude
+    Rms,
+}
+
+/// Handling of padded slices that overlap
+#[derive(ValueEnum, Clone, Debug)]
+enum OverlapPolicyKind {
+    /// Keep the shared samples in both slices
+    Allow,
+    /// Join the overlapping slices into one
+    Merge,
+    /// Split the shared samples between them at the middle
+    Trim,
+}
+
+/// Handling of transmissions longer than --max-duration
+#[derive(ValueEnum, Clone, Debug)]
+enum MaxDurationActionKind {
+    /// Discard them
+    Drop,
+    /// Keep them, with a _long filename suffix
+    Keep,
+    /// Cut them into consecutive slices of at most --max-duration
+    Split,
+}
+
+/// Spectrogram image color scale
+#[derive(ValueEnum, Clone, Debug)]
+enum ColormapKind {
+    Viridis,
+    Gray,
+    Hot,
+}
+
+/// Audio demodulation for slices
+#[derive(ValueEnum, Clone, Debug)]
+enum DemodKind {
+    /// Narrowband FM
+    Fm,
+    /// AM envelope
+    Am,
 }
 
 /// Common options for both file and stream modes
@@ -60,6 +207,10 @@ struct CommonArgs {
     #[arg(short = 'M', long)]
     max_duration: Option<u32>,
 
+    /// What to do with transmissions longer than --max-duration (file mode)
+    #[arg(long, value_enum, value_name = "MODE", default_value = "drop")]
+    max_duration_action: MaxDurationActionKind,
+
     /// Maximum gap to merge transmissions in milliseconds
     #[arg(short, long, default_value = "200")]
     gap: u32,
@@ -68,20 +219,309 @@ struct CommonArgs {
     #[arg(short, long, default_value = "100")]
     padding: u32,
 
+    /// Write detection events and the run summary to stdout as newline-delimited JSON
+    #[arg(long)]
+    ndjson: bool,
+
+    /// Trim quiet leading/trailing frames (below the release threshold) from each slice before padding
+    #[arg(long)]
+    trim_tail: bool,
+
     /// Verbose output
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "quiet")]
     verbose: bool,
 
-    /// Output WAV sample format
+    /// Only print warnings and errors
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Overwrite existing slice files (otherwise file mode errors and stream mode picks a new name)
+    #[arg(long)]
+    force: bool,
+
+    /// Output slice format
     #[arg(long, value_enum, default_value_t = OutputFormat::Int16)]
     output_format: OutputFormat,
+
+    /// Gain in dB applied to input samples before detection
+    #[arg(long, value_name = "DB", default_value = "0", allow_negative_numbers = true)]
+    input_gain: f32,
+
+    /// Negate Q on input to undo spectral inversion (swapped sidebands)
+    #[arg(long)]
+    conjugate: bool,
+
+    /// Gain applied to slices before writing, linear ("2") or in dB ("6dB")
+    #[arg(long, value_name = "GAIN", default_value = "1", value_parser = parse_gain)]
+    output_gain: f32,
+
+    /// Tuned center frequency in Hz; makes {freq}, reports and occupancy absolute
+    /// (file mode reads it from SDR++ baseband_<freq>Hz_... names when not given)
+    #[arg(long, value_name = "HZ")]
+    center_freq: Option<f64>,
+
+    /// Detection power per frame: peak bin (narrowband) or total in-band power (wideband)
+    #[arg(long, value_enum, default_value_t = PowerMetricKind::Peak)]
+    power_metric: PowerMetricKind,
+
+    /// Ignore this frequency (Hz from the center) when measuring detection power; repeatable.
+    /// Only the FFT bins are skipped; the saved IQ is unchanged unless --notch-output is given
+    #[arg(long, value_name = "HZ", allow_negative_numbers = true)]
+    notch_freq: Vec<f64>,
+
+    /// Ignore FFT bins fewer than N from DC when measuring detection power (1 = the DC bin only),
+    /// for receivers with a wide DC spike. Bins are those of the 1 ms detection window in file mode
+    /// and of the --chunk-ms chunk (padded to a power of two) when streaming, so N spans a different
+    /// bandwidth in each; it must stay below half the FFT size
+    #[arg(long, value_name = "N", default_value = "1")]
+    dc_guard: usize,
+
+    /// Also remove the --notch-freq frequencies from written slices with a narrow band-stop filter
+    #[arg(long, requires = "notch_freq")]
+    notch_output: bool,
+
+    /// Remove each slice's carrier frequency offset, estimated from its mean phase advance, before writing
+    #[arg(long)]
+    correct_cfo: bool,
+
+    /// Raised-cosine fade applied to the first and last N ms of each slice (0 disables)
+    #[arg(long, value_name = "MS", default_value = "0")]
+    fade_ms: f32,
+
+    /// WAV channel order: iq (I left, as SDR++, URH and inspectrum expect) or qi (Q left)
+    #[arg(long, value_enum, default_value_t = ChannelOrderKind::Iq)]
+    channel_order: ChannelOrderKind,
+
+    /// Write each slice as two mono WAVs, <name>_i.wav and <name>_q.wav, instead of one stereo file
+    #[arg(long)]
+    planar: bool,
+
+    /// Normalize each slice to --normalize-level by peak or RMS magnitude
+    #[arg(long, value_enum)]
+    normalize: Option<NormalizeKind>,
+
+    /// Target level for --normalize in dBFS
+    #[arg(long, value_name = "DBFS", default_value = "-3", allow_negative_numbers = true)]
+    normalize_level: f32,
+
+    /// Slice filename template (.wav or .cf32 is appended). Placeholders: {index}, {index:0N},
+    /// {timestamp}, {start_seconds}, {peak_db}, {crest}, {freq}, {freq_khz} (left out with the
+    /// separator after it when the slice's frequency can't be estimated)
+    /// [default: {prefix}_{index:0N}_{freq_khz}_{timestamp}]
+    #[arg(long, value_name = "TEMPLATE", value_parser = output::NameTemplate::parse, conflicts_with_all = ["prefix", "index_width"])]
+    name_template: Option<output::NameTemplate>,
+
+    /// Filename prefix of the default slice naming
+    #[arg(long, value_name = "TEXT", default_value = output::NameTemplate::DEFAULT_PREFIX, value_parser = parse_prefix)]
+    prefix: String,
+
+    /// Zero-padded digits of the slice index in the default naming
+    #[arg(long, value_name = "N", default_value_t = output::NameTemplate::DEFAULT_INDEX_WIDTH)]
+    index_width: usize,
+
+    /// Continue slice numbering after the highest index already in the output directory
+    #[arg(long = "continue")]
+    continue_numbering: bool,
+
+    /// Write a JSON report with the run summary and per-slice details
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Write a JSON manifest of slices with SHA-256 checksums
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Write a .json sidecar next to each slice with its source sample range and measurements
+    #[arg(long)]
+    sidecar: bool,
+
+    /// Stop after writing this many slices
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_slices: Option<u64>,
+
+    /// Demodulate each slice and write an audio WAV next to it
+    #[arg(long, value_enum)]
+    demod: Option<DemodKind>,
+
+    /// Sample rate for demodulated audio in Hz
+    #[arg(long, value_name = "HZ", default_value = "16000")]
+    audio_rate: u32,
+
+    /// Measure occupied bandwidth this many dB below the spectral peak
+    #[arg(long, value_name = "DB", default_value = "20")]
+    bandwidth_db: f32,
+
+    /// Stop writing when free space on the output volume falls below this many MB (0 disables)
+    #[arg(long, value_name = "MB", default_value = "500")]
+    min_free_space: u64,
 }
 
 #[derive(ClapArgs, Debug)]
 struct FileArgs {
-    /// Input WAV file to process
-    #[arg(value_name = "INPUT")]
-    input_file: PathBuf,
+    /// Input recording(s): IQ WAV, or headerless .cf32/.cs16/.cs8 (optionally .gz) with --rate.
+    /// With several, each gets its own subdirectory of the output directory
+    #[arg(value_name = "INPUT", required = true)]
+    input_files: Vec<PathBuf>,
+
+    /// Number of input files to process concurrently
+    #[arg(long, value_name = "N", default_value = "1")]
+    jobs: usize,
+
+    /// Walk input directories for recordings, mirroring their tree under the output directory
+    #[arg(long)]
+    recursive: bool,
+
+    /// With several inputs, skip any already sliced with the same parameters by an earlier run
+    #[arg(long)]
+    skip_existing: bool,
+
+    /// Sample rate in Hz of headerless raw inputs
+    #[arg(long, short = 'r', value_name = "HZ")]
+    rate: Option<u32>,
+
+    /// Byte order of multi-byte samples in headerless raw inputs (WAV samples are always little-endian)
+    #[arg(long, value_enum, default_value_t = EndianKind::Little)]
+    endian: EndianKind,
+
+    /// Treat the input as real-valued (I channel only) and convert to IQ via Hilbert transform
+    #[arg(long)]
+    mono: bool,
+
+    /// Channel index (0-based) holding I in multi-channel WAVs
+    #[arg(long, value_name = "N", default_value = "0")]
+    i_channel: usize,
+
+    /// Channel index (0-based) holding Q in multi-channel WAVs
+    #[arg(long, value_name = "N", default_value = "1")]
+    q_channel: usize,
+
+    /// Channel order of input WAVs and raw files: iq (I left/first, as SDR++ writes) or qi (Q first, e.g. some GQRX files)
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = ChannelOrderKind::Iq, conflicts_with_all = ["i_channel", "q_channel"])]
+    iq_order: ChannelOrderKind,
+
+    /// Detection algorithm
+    #[arg(long, value_enum, default_value_t = DetectorKind::Threshold)]
+    detector: DetectorKind,
+
+    /// CFAR guard cells on each side of the cell under test (cells are half-window profile frames,
+    /// 0.5ms at 16 kHz and above but longer at lower rates; should span the longest expected burst)
+    #[arg(long, default_value = "1000")]
+    cfar_guard: usize,
+
+    /// CFAR training cells on each side used to estimate local noise
+    #[arg(long, default_value = "2000")]
+    cfar_train: usize,
+
+    /// CFAR probability of false alarm
+    #[arg(long, default_value = "0.001")]
+    cfar_pfa: f32,
+
+    /// Spectrogram detector and --occupancy: dB above each frequency bin's noise floor
+    #[arg(long, value_name = "DB", default_value = "10")]
+    tf_margin: f32,
+
+    /// Flatness detector: spectral flatness (0-1) below which a frame counts as signal
+    #[arg(long, value_name = "FLATNESS", default_value = "0.3")]
+    max_flatness: f32,
+
+    /// Goertzel detector: frequency to watch, in Hz from the center (negative below)
+    #[arg(long, value_name = "HZ", allow_negative_numbers = true)]
+    target_freq: Option<f64>,
+
+    /// Reference IQ WAV to search for with a matched filter (overrides --detector)
+    #[arg(long, value_name = "FILE")]
+    template: Option<PathBuf>,
+
+    /// Normalized correlation (0-1) required for a template match
+    #[arg(long, value_name = "CORR", default_value = "0.5")]
+    match_threshold: f32,
+
+    /// Noise floor estimation method for the auto threshold
+    #[arg(long, value_enum, default_value_t = NoiseEstimatorKind::Percentile)]
+    noise_estimator: NoiseEstimatorKind,
+
+    /// Minimum segment SNR in dB (peak power above noise floor)
+    #[arg(long, value_name = "DB")]
+    min_snr: Option<f32>,
+
+    /// Consecutive frames above/below threshold required to start/end a detection
+    #[arg(long, value_name = "N", default_value = "1")]
+    debounce: usize,
+
+    /// Moving-average length in frames applied to the power profile to bridge fading dips
+    #[arg(long, value_name = "N", default_value = "1")]
+    smooth: usize,
+
+    /// Drop constant carriers (segments with near-constant power)
+    #[arg(long)]
+    skip_carriers: bool,
+
+    /// Power standard deviation in dB below which a segment is treated as a carrier
+    #[arg(long, value_name = "DB", default_value = "0.5")]
+    carrier_std: f32,
+
+    /// Write an Audacity label track of all detected segments
+    #[arg(long, value_name = "FILE")]
+    labels: Option<PathBuf>,
+
+    /// Write a spectrogram PNG of the recording with detections boxed
+    #[arg(long, value_name = "FILE")]
+    spectrogram: Option<PathBuf>,
+
+    /// Copy the recording to this WAV with a cue marker and label at each detection
+    #[arg(long, value_name = "FILE")]
+    annotate: Option<PathBuf>,
+
+    /// Write per-frequency-bin active fraction (spectral occupancy) as CSV
+    #[arg(long, value_name = "FILE")]
+    occupancy: Option<PathBuf>,
+
+    /// FFT size for --occupancy (frequency resolution is rate / size)
+    #[arg(long, value_name = "N", default_value = "1024")]
+    occupancy_fft: usize,
+
+    /// Join kept transmissions less than this many ms apart into one slice [default: --gap].
+    /// Unlike --gap, which bridges pauses before --min-duration drops short fragments, this joins
+    /// whole transmissions, so a larger value combines consecutive bursts without keeping blips
+    #[arg(long, value_name = "MS")]
+    merge_gap: Option<u32>,
+
+    /// What to do where --padding makes neighbouring slices overlap
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "allow")]
+    overlap_policy: OverlapPolicyKind,
+
+    /// Also write every slice back to back into this one file, with Audacity labels alongside (.txt)
+    #[arg(long, value_name = "FILE")]
+    concat: Option<PathBuf>,
+
+    /// Silence between slices in --concat, in milliseconds
+    #[arg(long, value_name = "MS", default_value = "500")]
+    concat_gap: u32,
+
+    /// Spectrogram color scale
+    #[arg(long, value_enum, default_value_t = ColormapKind::Viridis)]
+    colormap: ColormapKind,
+
+    /// Start of the range to process (seconds or HH:MM:SS)
+    #[arg(long, value_name = "TIME", value_parser = parse_time_offset)]
+    start_time: Option<f64>,
+
+    /// End of the range to process (seconds or HH:MM:SS)
+    #[arg(long, value_name = "TIME", value_parser = parse_time_offset)]
+    end_time: Option<f64>,
+
+    /// Write the power profile and detection threshold as CSV for tuning
+    #[arg(long, value_name = "FILE")]
+    dump_profile: Option<PathBuf>,
+
+    /// Detect and report segments without writing slices
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Timestamp base for slice names: now, file-mtime, or an RFC3339 time
+    /// (file-mtime prefers the start time in an SDR++ recording name)
+    #[arg(long, value_name = "BASE", default_value = "file-mtime", value_parser = parse_base_time)]
+    base_time: slicer::BaseTime,
 
     #[command(flatten)]
     common: CommonArgs,
@@ -89,7 +529,7 @@ struct FileArgs {
 
 #[derive(ClapArgs, Debug)]
 struct StreamArgs {
-    /// Host and port to connect to (e.g., localhost:5555)
+    /// Host and port to connect to (e.g., localhost:5555), or "-" to read from stdin
     #[arg(value_name = "HOST:PORT")]
     address: String,
 
@@ -101,59 +541,704 @@ struct StreamArgs {
     #[arg(long, default_value = "15")]
     margin: f32,
 
+    /// Base of the detection threshold: running noise floor, or a percentile of recent chunk powers
+    /// (--margin is added either way, so percentile mode usually wants a few dB)
+    #[arg(long, value_enum, default_value_t = StreamThresholdKind::Margin)]
+    stream_threshold: StreamThresholdKind,
+
+    /// Percentile of recent chunk powers used by --stream-threshold percentile
+    #[arg(long, value_name = "PERCENT", default_value = "95")]
+    threshold_percentile: f32,
+
+    /// Seconds of recent chunk powers used by --stream-threshold percentile
+    #[arg(long, value_name = "SECONDS", default_value = "10")]
+    percentile_window: f64,
+
     /// Input stream sample format
     #[arg(long, value_enum, default_value_t = InputFormat::Float32)]
     input_format: InputFormat,
 
+    /// Byte order of multi-byte input samples
+    #[arg(long, value_enum, default_value_t = EndianKind::Little)]
+    endian: EndianKind,
+
+    /// Send newline-delimited JSON start/end events for each transmission to this TCP address
+    #[arg(long, value_name = "HOST:PORT")]
+    event_sink: Option<String>,
+
+    /// Stop after this much time (seconds or HH:MM:SS), flushing any in-progress transmission
+    #[arg(long, value_name = "TIME", value_parser = parse_time_offset)]
+    duration: Option<f64>,
+
+    /// Detection chunk length in milliseconds (one FFT per chunk)
+    #[arg(long, value_name = "MS", default_value = "10")]
+    chunk_ms: f32,
+
+    /// Noise floor adaptation rate per chunk (higher tracks changes faster)
+    #[arg(long, value_name = "ALPHA", default_value = "0.005")]
+    noise_alpha: f32,
+
+    /// Seconds at the start used only to measure the noise floor (median chunk power) before detecting;
+    /// 0 measures over the first 0.5 s
+    #[arg(long, value_name = "SECONDS", default_value = "0")]
+    warmup_seconds: f64,
+
+    /// Split a transmission into consecutive slices once it runs this long, bounding memory
+    /// when a carrier or open squelch never drops below threshold
+    #[arg(long, value_name = "SECONDS", default_value = "120")]
+    max_tx_seconds: f64,
+
+    /// Socket read timeout in milliseconds (0 to block indefinitely)
+    #[arg(long, value_name = "MS", default_value = "1000")]
+    read_timeout: u64,
+
+    /// After the TCP stream closes or fails, keep reconnecting and resume detection (numbering and
+    /// noise floor carry over; a transmission cut off by the drop is saved first)
+    #[arg(long)]
+    reconnect: bool,
+
+    /// Write slices into per-day YYYY-MM-DD subdirectories
+    #[arg(long)]
+    date_folders: bool,
+
+    /// Also record the complete stream to this WAV file: int16 with --output-format int16, float32
+    /// with any other format (cf32 included, as the recording is always a WAV)
+    #[arg(long, value_name = "FILE")]
+    record_raw: Option<PathBuf>,
+
+    /// Start a new timestamped --record-raw file once the current one reaches this many MB
+    #[arg(long, value_name = "MB", requires = "record_raw")]
+    record_rotate_size: Option<u64>,
+
+    /// Start a new timestamped --record-raw file after this many minutes of recording
+    #[arg(long, value_name = "MINUTES", requires = "record_raw")]
+    record_rotate_minutes: Option<f64>,
+
+    /// Split the stream into N equally spaced channels, each detected separately
+    #[arg(long, value_name = "N", requires = "channel_bw")]
+    channels: Option<usize>,
+
+    /// Channel bandwidth (and spacing) in Hz for --channels
+    #[arg(long, value_name = "HZ", requires = "channels")]
+    channel_bw: Option<u32>,
+
     #[command(flatten)]
     common: CommonArgs,
 }
 
+#[derive(ClapArgs, Debug)]
+struct InfoArgs {
+    /// WAV file to describe
+    #[arg(value_name = "INPUT")]
+    input: PathBuf,
+}
+
+#[derive(ClapArgs, Debug)]
+struct ConvertArgs {
+    /// Directory of WAV slices (date folders included)
+    #[arg(value_name = "DIR")]
+    input_dir: PathBuf,
+
+    /// Directory for the converted slices, mirroring the input layout
+    #[arg(short, long)]
+    output_dir: PathBuf,
+
+    /// Format to convert to
+    #[arg(long, value_enum)]
+    output_format: OutputFormat,
+
+    /// WAV channel order of the converted slices
+    #[arg(long, value_enum, default_value_t = ChannelOrderKind::Iq)]
+    channel_order: ChannelOrderKind,
+
+    /// Overwrite existing files in the output directory
+    #[arg(long)]
+    force: bool,
+}
+
+/// Accept a filename prefix without path separators or template braces
+fn parse_prefix(s: &str) -> Result<String, String> {
+    if s.contains(['/', '\\', '{', '}']) {
+        return Err(format!("invalid prefix '{}': must not contain '/', '\\', '{{' or '}}'", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a gain given as a linear factor ("0.5") or in decibels ("-6dB")
+fn parse_gain(s: &str) -> Result<f32, String> {
+    let trimmed = s.trim();
+    let gain = match trimmed.strip_suffix("dB").or_else(|| trimmed.strip_suffix("db")) {
+        Some(db) => {
+            let db: f32 = db.trim().parse().map_err(|_| format!("invalid gain '{}'", s))?;
+            10f32.powf(db / 20.0)
+        }
+        None => trimmed.parse().map_err(|_| format!("invalid gain '{}'", s))?,
+    };
+    if !(gain.is_finite() && gain > 0.0) {
+        return Err(format!("invalid gain '{}': must be positive", s));
+    }
+    Ok(gain)
+}
+
+/// Parse a time offset given as seconds ("42.5"), "MM:SS" or "HH:MM:SS"
+fn parse_time_offset(s: &str) -> Result<f64, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("invalid time '{}': expected seconds or HH:MM:SS", s));
+    }
+    let mut seconds = 0.0;
+    for part in &parts {
+        let value: f64 = part
+            .parse()
+            .map_err(|_| format!("invalid time '{}': expected seconds or HH:MM:SS", s))?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("invalid time '{}': must be a finite, non-negative number", s));
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// Parse a timestamp base: "now", "file-mtime", or an RFC3339 timestamp
+fn parse_base_time(s: &str) -> Result<slicer::BaseTime, String> {
+    match s {
+        "now" => Ok(slicer::BaseTime::Now),
+        "file-mtime" => Ok(slicer::BaseTime::FileMtime),
+        _ => chrono::DateTime::parse_from_rfc3339(s)
+            .map(|t| slicer::BaseTime::Fixed(t.with_timezone(&chrono::Local)))
+            .map_err(|e| format!("expected now, file-mtime, or an RFC3339 timestamp ({})", e)),
+    }
+}
+
+impl CommonArgs {
+    /// Reject inconsistent or out-of-range options before doing any work
+    fn validate(&self) -> Result<(), String> {
+        if let Some(max) = self.max_duration {
+            if self.min_duration > max {
+                return Err(format!(
+                    "--min-duration ({} ms) must not exceed --max-duration ({} ms)",
+                    self.min_duration, max
+                ));
+            }
+        }
+        if self.planar && matches!(self.output_format, OutputFormat::Cf32) {
+            return Err("--planar writes WAV files and can't be combined with --output-format cf32".to_string());
+        }
+        if !self.input_gain.is_finite() {
+            return Err(format!("--input-gain ({}) must be a finite dB value", self.input_gain));
+        }
+        if let Some(hz) = self.notch_freq.iter().find(|hz| !hz.is_finite()) {
+            return Err(format!("--notch-freq ({}) must be a number", hz));
+        }
+        if self.continue_numbering && !self.name_template().has_parseable_index() {
+            return Err("--continue requires a --name-template that starts with literal text and {index}".to_string());
+        }
+        if self.center_freq.is_some_and(|hz| !hz.is_finite()) {
+            return Err("--center-freq must be a finite frequency".to_string());
+        }
+        if self.fade_ms.is_nan() || self.fade_ms < 0.0 {
+            return Err(format!("--fade-ms ({}) must not be negative", self.fade_ms));
+        }
+        if self.normalize_level.is_nan() || self.normalize_level > 0.0 {
+            return Err(format!("--normalize-level ({} dBFS) must not exceed 0", self.normalize_level));
+        }
+        if self.audio_rate == 0 {
+            return Err("--audio-rate must be positive".to_string());
+        }
+        if self.bandwidth_db.is_nan() || self.bandwidth_db <= 0.0 {
+            return Err(format!("--bandwidth-db ({}) must be positive", self.bandwidth_db));
+        }
+        Ok(())
+    }
+
+    /// The --name-template, or the default naming built from --prefix and --index-width
+    fn name_template(&self) -> output::NameTemplate {
+        self.name_template
+            .clone()
+            .unwrap_or_else(|| output::NameTemplate::standard(&self.prefix, self.index_width))
+    }
+
+    /// Index for the first slice of this run, continuing existing numbering with `--continue`
+    fn first_index(&self) -> std::io::Result<usize> {
+        if !self.continue_numbering {
+            return Ok(1);
+        }
+        let highest = output::highest_slice_index(&self.output_dir, &self.name_template())?;
+        if highest > 0 {
+            log::info!("Continuing slice numbering after {}", highest);
+        }
+        Ok(highest + 1)
+    }
+
+    /// Linear amplitude factor for `--input-gain`
+    fn input_gain_linear(&self) -> f32 {
+        10f32.powf(self.input_gain / 20.0)
+    }
+
+    fn log_level(&self) -> log::LevelFilter {
+        if self.quiet {
+            log::LevelFilter::Warn
+        } else if self.verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
+        }
+    }
+
+    fn slice_config(&self) -> slicer::SliceConfig {
+        slicer::SliceConfig {
+            min_duration_ms: self.min_duration,
+            max_duration_ms: self.max_duration,
+            max_duration_action: match self.max_duration_action {
+                MaxDurationActionKind::Drop => slicer::MaxDurationAction::Drop,
+                MaxDurationActionKind::Keep => slicer::MaxDurationAction::Keep,
+                MaxDurationActionKind::Split => slicer::MaxDurationAction::Split,
+            },
+            gap_ms: self.gap,
+            merge_gap_ms: self.gap,
+            overlap_policy: detector::OverlapPolicy::Allow,
+            padding_ms: self.padding,
+            trim_tail: self.trim_tail,
+            ndjson: self.ndjson,
+            slice_writer: self.output_format.slice_writer(),
+            planar: self.planar,
+            output_gain: self.output_gain,
+            fade_ms: self.fade_ms,
+            channel_order: self.channel_order.order(),
+            normalize: self.normalize.as_ref().map(|kind| match kind {
+                NormalizeKind::Peak => output::Normalization::Peak,
+                NormalizeKind::Rms => output::Normalization::Rms,
+            }),
+            normalize_target_db: self.normalize_level,
+            name_template: self.name_template(),
+            first_index: 1,
+            overwrite: if self.force {
+                output::OverwritePolicy::Overwrite
+            } else {
+                output::OverwritePolicy::Error
+            },
+            report_path: self.report.clone(),
+            manifest_path: self.manifest.clone(),
+            sidecar: self.sidecar,
+            max_slices: self.max_slices.map(|n| n as usize),
+            demod: self.demod.as_ref().map(|kind| match kind {
+                DemodKind::Fm => dsp::Demodulation::Fm,
+                DemodKind::Am => dsp::Demodulation::Am,
+            }),
+            audio_rate: self.audio_rate,
+            bandwidth_drop_db: self.bandwidth_db,
+            min_free_bytes: self.min_free_space.saturating_mul(1024 * 1024),
+            center_frequency: self.center_freq,
+            power_metric: match self.power_metric {
+                PowerMetricKind::Peak => detector::PowerMetric::Peak,
+                PowerMetricKind::Total => detector::PowerMetric::Total,
+            },
+            notch_hz: self.notch_freq.clone(),
+            dc_guard_bins: self.dc_guard,
+            notch_output: self.notch_output,
+            correct_cfo: self.correct_cfo,
+            detector: detector::DetectorMode::Threshold,
+            template_path: None,
+            noise_estimator: detector::NoiseEstimator::Percentile,
+            min_snr_db: None,
+            debounce: 1,
+            smooth_frames: 1,
+            skip_carriers: false,
+            carrier_std_db: 0.5,
+            labels_path: None,
+            spectrogram_path: None,
+            annotate_path: None,
+            occupancy_path: None,
+            occupancy_fft_size: 1024,
+            concat_path: None,
+            concat_gap_ms: 500,
+            occupancy_margin_db: 10.0,
+            colormap: output::Colormap::Viridis,
+            start_seconds: None,
+            end_seconds: None,
+            base_time: slicer::BaseTime::Now,
+            profile_path: None,
+            dry_run: false,
+        }
+    }
+}
+
+impl FileArgs {
+    fn validate(&self) -> Result<(), String> {
+        self.common.validate()?;
+        if let (Some(start), Some(end)) = (self.start_time, self.end_time) {
+            if start >= end {
+                return Err(format!("--start-time ({}s) must be before --end-time ({}s)", start, end));
+            }
+        }
+        if !(self.cfar_pfa > 0.0 && self.cfar_pfa < 1.0) {
+            return Err(format!("--cfar-pfa ({}) must be between 0 and 1", self.cfar_pfa));
+        }
+        if !(self.match_threshold > 0.0 && self.match_threshold <= 1.0) {
+            return Err(format!("--match-threshold ({}) must be in (0, 1]", self.match_threshold));
+        }
+        if self.jobs == 0 {
+            return Err("--jobs must be at least 1".to_string());
+        }
+        if self.rate == Some(0) {
+            return Err("--rate must be positive".to_string());
+        }
+        // --mono reads only the I channel, so Q may be anything
+        if !self.mono && self.i_channel == self.q_channel {
+            return Err(format!("--i-channel and --q-channel must differ (both are {})", self.i_channel));
+        }
+        if self.debounce == 0 {
+            return Err("--debounce must be at least 1".to_string());
+        }
+        if self.smooth == 0 {
+            return Err("--smooth must be at least 1".to_string());
+        }
+        if !(self.max_flatness > 0.0 && self.max_flatness < 1.0) {
+            return Err(format!("--max-flatness ({}) must be between 0 and 1", self.max_flatness));
+        }
+        if matches!(self.detector, DetectorKind::Goertzel) && self.template.is_none() {
+            match self.target_freq {
+                None => return Err("--detector goertzel requires --target-freq".to_string()),
+                Some(hz) if !hz.is_finite() => return Err(format!("--target-freq ({}) must be a number", hz)),
+                Some(_) => {}
+            }
+        }
+        if !(16..=65536).contains(&self.occupancy_fft) {
+            return Err(format!("--occupancy-fft ({}) must be between 16 and 65536", self.occupancy_fft));
+        }
+        if self.tf_margin.is_nan() || self.tf_margin < 0.0 {
+            return Err(format!("--tf-margin ({} dB) must not be negative", self.tf_margin));
+        }
+        if self.carrier_std.is_nan() || self.carrier_std < 0.0 {
+            return Err(format!("--carrier-std ({} dB) must not be negative", self.carrier_std));
+        }
+        Ok(())
+    }
+
+    fn slice_config(&self) -> slicer::SliceConfig {
+        let detector = match (&self.template, &self.detector) {
+            (Some(_), _) => detector::DetectorMode::Matched {
+                threshold: self.match_threshold,
+            },
+            (None, DetectorKind::Threshold) => detector::DetectorMode::Threshold,
+            (None, DetectorKind::Cfar) => detector::DetectorMode::Cfar {
+                guard: self.cfar_guard,
+                train: self.cfar_train,
+                pfa: self.cfar_pfa,
+            },
+            (None, DetectorKind::Spectrogram) => detector::DetectorMode::Spectrogram {
+                margin_db: self.tf_margin,
+            },
+            (None, DetectorKind::Flatness) => detector::DetectorMode::Flatness {
+                max_flatness: self.max_flatness,
+            },
+            (None, DetectorKind::Goertzel) => detector::DetectorMode::Goertzel {
+                target_hz: self.target_freq.unwrap_or(0.0),
+            },
+        };
+        let noise_estimator = match self.noise_estimator {
+            NoiseEstimatorKind::Percentile => detector::NoiseEstimator::Percentile,
+            NoiseEstimatorKind::Mode => detector::NoiseEstimator::Mode,
+        };
+        slicer::SliceConfig {
+            detector,
+            merge_gap_ms: self.merge_gap.unwrap_or(self.common.gap),
+            overlap_policy: match self.overlap_policy {
+                OverlapPolicyKind::Allow => detector::OverlapPolicy::Allow,
+                OverlapPolicyKind::Merge => detector::OverlapPolicy::Merge,
+                OverlapPolicyKind::Trim => detector::OverlapPolicy::Trim,
+            },
+            template_path: self.template.clone(),
+            noise_estimator,
+            min_snr_db: self.min_snr,
+            debounce: self.debounce,
+            smooth_frames: self.smooth,
+            skip_carriers: self.skip_carriers,
+            carrier_std_db: self.carrier_std,
+            labels_path: self.labels.clone(),
+            spectrogram_path: self.spectrogram.clone(),
+            annotate_path: self.annotate.clone(),
+            occupancy_path: self.occupancy.clone(),
+            occupancy_fft_size: self.occupancy_fft,
+            concat_path: self.concat.clone(),
+            concat_gap_ms: self.concat_gap,
+            occupancy_margin_db: self.tf_margin,
+            colormap: match self.colormap {
+                ColormapKind::Viridis => output::Colormap::Viridis,
+                ColormapKind::Gray => output::Colormap::Gray,
+                ColormapKind::Hot => output::Colormap::Hot,
+            },
+            start_seconds: self.start_time,
+            end_seconds: self.end_time,
+            base_time: self.base_time,
+            profile_path: self.dump_profile.clone(),
+            dry_run: self.dry_run,
+            ..self.common.slice_config()
+        }
+    }
+}
+
+/// Print the `info` subcommand's description of a WAV file
+fn print_wav_info(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let info = input::wav::read_wav_info(path)?;
+    let spec = info.spec;
+    let format = match spec.sample_format {
+        hound::SampleFormat::Float => "float",
+        hound::SampleFormat::Int => "integer",
+    };
+    let block_align = spec.channels as u64 * spec.bits_per_sample.div_ceil(8) as u64;
+    println!("File:            {}", path.display());
+    println!("Size:            {} bytes", info.file_bytes);
+    println!("Sample format:   {}-bit {}", spec.bits_per_sample, format);
+    println!("Channels:        {}", spec.channels);
+    println!("Sample rate:     {} Hz", spec.sample_rate);
+    println!("Block align:     {} bytes", block_align);
+    println!("Byte rate:       {} bytes/s", block_align * spec.sample_rate as u64);
+    println!("Frames:          {}", info.frames);
+    println!("Duration:        {:.3}s", info.duration_seconds());
+    println!("Sample data:     {} bytes", info.frames as u64 * block_align);
+    let chunks: Vec<String> = info
+        .chunks
+        .iter()
+        .map(|(id, size)| format!("{} ({} bytes)", id.trim_end(), size))
+        .collect();
+    println!("Chunks:          {}", chunks.join(", "));
+    for (id, text) in &info.info_tags {
+        println!("INFO {}:       {}", id, text);
+    }
+    if let Some((center, start)) = info.sdrpp {
+        println!("SDR++ center:    {:.0} Hz", center);
+        if let Some(start) = start {
+            println!("SDR++ start:     {}", start);
+        }
+    }
+    if spec.channels != 2 {
+        println!(
+            "Note:            IQ needs 2 channels; select them with --i-channel/--q-channel, or use --mono for real samples"
+        );
+    }
+    Ok(())
+}
+
+/// Initialize logging: plain messages for info/debug, prefixed warnings/errors; RUST_LOG overrides
+fn init_logger(level: log::LevelFilter) {
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|buf, record| {
+            // Batch jobs run each file on a thread named after it
+            let thread = std::thread::current();
+            if let Some(name) = thread.name().filter(|name| *name != "main") {
+                write!(buf, "[{}] ", name)?;
+            }
+            match record.level() {
+                log::Level::Error => writeln!(buf, "Error: {}", record.args()),
+                log::Level::Warn => writeln!(buf, "Warning: {}", record.args()),
+                _ => writeln!(buf, "{}", record.args()),
+            }
+        })
+        .init();
+}
+
+impl StreamArgs {
+    /// Highest sample rate accepted; anything above is almost certainly a typo
+    const MAX_RATE: u32 = 1_000_000_000;
+
+    fn validate(&self) -> Result<(), String> {
+        self.common.validate()?;
+        if self.rate < 100 || self.rate > Self::MAX_RATE {
+            return Err(format!("--rate ({} Hz) must be between 100 Hz and 1 GHz", self.rate));
+        }
+        if !(self.chunk_ms > 0.0 && self.chunk_ms <= 1000.0) {
+            return Err(format!("--chunk-ms ({}) must be between 0 and 1000 ms", self.chunk_ms));
+        }
+        let chunk_samples = (self.rate as f64 * self.chunk_ms as f64 / 1000.0) as usize;
+        if chunk_samples < slicer::MIN_CHUNK_SAMPLES {
+            return Err(format!(
+                "--chunk-ms {} at {} Hz gives {} samples per chunk (need at least {})",
+                self.chunk_ms,
+                self.rate,
+                chunk_samples,
+                slicer::MIN_CHUNK_SAMPLES
+            ));
+        }
+        slicer::check_dc_guard(self.common.dc_guard, chunk_samples.next_power_of_two())?;
+        if self.duration == Some(0.0) {
+            return Err("--duration must be positive".to_string());
+        }
+        if !(self.noise_alpha > 0.0 && self.noise_alpha <= 1.0) {
+            return Err(format!("--noise-alpha ({}) must be in (0, 1]", self.noise_alpha));
+        }
+        if self.record_rotate_size == Some(0) {
+            return Err("--record-rotate-size must be positive".to_string());
+        }
+        if self.record_rotate_minutes.is_some_and(|minutes| !(minutes > 0.0 && minutes.is_finite())) {
+            return Err("--record-rotate-minutes must be a positive number".to_string());
+        }
+        if !(self.warmup_seconds >= 0.0 && self.warmup_seconds.is_finite()) {
+            return Err(format!("--warmup-seconds ({}) must be a non-negative number", self.warmup_seconds));
+        }
+        if !(self.max_tx_seconds > 0.0 && self.max_tx_seconds.is_finite()) {
+            return Err(format!("--max-tx-seconds ({}) must be a positive number", self.max_tx_seconds));
+        }
+        if self.margin.is_nan() || self.margin < 0.0 {
+            return Err(format!("--margin ({} dB) must not be negative", self.margin));
+        }
+        if !(0.0..=100.0).contains(&self.threshold_percentile) {
+            return Err(format!("--threshold-percentile ({}) must be between 0 and 100", self.threshold_percentile));
+        }
+        if !(self.percentile_window > 0.0 && self.percentile_window.is_finite()) {
+            return Err(format!("--percentile-window ({}) must be a positive number", self.percentile_window));
+        }
+        if self.channels == Some(0) || self.channel_bw == Some(0) {
+            return Err("--channels and --channel-bw must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn slice_config(&self) -> slicer::SliceConfig {
+        let mut config = self.common.slice_config();
+        // Long-running sessions shouldn't die on a name collision
+        if !self.common.force {
+            config.overwrite = output::OverwritePolicy::Rename;
+        }
+        config
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
+    let level = match &cli.command {
+        Command::File(args) => args.common.log_level(),
+        Command::Stream(args) => args.common.log_level(),
+        Command::Info(_) | Command::Convert(_) => log::LevelFilter::Info,
+    };
+    init_logger(level);
+
     match cli.command {
+        Command::Info(args) => print_wav_info(&args.input)?,
+        Command::Convert(args) => {
+            std::fs::create_dir_all(&args.output_dir)?;
+            output::check_writable(&args.output_dir)?;
+            let overwrite = if args.force {
+                output::OverwritePolicy::Overwrite
+            } else {
+                output::OverwritePolicy::Error
+            };
+            let converted = slicer::convert_slices(
+                &args.input_dir,
+                &args.output_dir,
+                args.output_format.slice_writer(),
+                args.channel_order.order(),
+                overwrite,
+            )?;
+            log::info!("Converted {} slice(s) to {}", converted, args.output_dir.display());
+        }
         Command::File(args) => {
-            std::fs::create_dir_all(&args.common.output_dir)?;
-            let output_float32 = matches!(args.common.output_format, OutputFormat::Float32);
-            if args.common.verbose {
-                println!("Processing file: {}", args.input_file.display());
+            args.validate()?;
+            if !args.dry_run {
+                std::fs::create_dir_all(&args.common.output_dir)?;
+                output::check_writable(&args.common.output_dir)?;
+            }
+            let (i_channel, q_channel) = match args.iq_order {
+                ChannelOrderKind::Iq => (args.i_channel, args.q_channel),
+                ChannelOrderKind::Qi => (1, 0),
+            };
+            let wav_options = input::wav::WavOptions {
+                mono: args.mono,
+                i_channel,
+                q_channel,
+                gain: args.common.input_gain_linear(),
+                conjugate: args.common.conjugate,
+                sample_rate: args.rate,
+                endian: args.endian.endian(),
+            };
+            let mut config = args.slice_config();
+            match args.input_files.as_slice() {
+                [input_file] if !input_file.is_dir() => {
+                    if args.skip_existing {
+                        log::warn!("--skip-existing only applies to several inputs or a directory; processing {}", input_file.display());
+                    }
+                    config.first_index = args.common.first_index()?;
+                    log::debug!("Processing file: {}", input_file.display());
+                    slicer::process_file(input_file, &args.common.output_dir, &config, &wav_options)?;
+                }
+                inputs => {
+                    let jobs = slicer::batch_jobs(inputs, &args.common.output_dir, args.recursive)?;
+                    log::info!("Processing {} files, {} at a time", jobs.len(), args.jobs);
+                    let batch = slicer::process_files(
+                        &jobs,
+                        &config,
+                        &wav_options,
+                        args.jobs,
+                        args.common.continue_numbering,
+                        args.skip_existing,
+                    );
+                    log::info!(
+                        "Batch total: {} processed, {} skipped, {} failed",
+                        batch.processed,
+                        batch.skipped,
+                        batch.failed.len()
+                    );
+                    batch.stats.print_summary();
+                    if let Some(report_path) = &config.report_path {
+                        batch.stats.write_report(report_path)?;
+                    }
+                    if !batch.failed.is_empty() {
+                        return Err(format!("{} of {} files failed", batch.failed.len(), jobs.len()).into());
+                    }
+                }
             }
-            slicer::process_file(
-                &args.input_file,
-                &args.common.output_dir,
-                args.common.min_duration,
-                args.common.max_duration,
-                args.common.gap,
-                args.common.padding,
-                args.common.verbose,
-                output_float32,
-            )?;
         }
         Command::Stream(args) => {
+            args.validate()?;
             std::fs::create_dir_all(&args.common.output_dir)?;
+            output::check_writable(&args.common.output_dir)?;
             let format = match args.input_format {
-                InputFormat::Int8 => input::StreamFormat::Int8,
-                InputFormat::Int16 => input::StreamFormat::Int16,
-                InputFormat::Int32 => input::StreamFormat::Int32,
-                InputFormat::Float32 => input::StreamFormat::Float32,
+                InputFormat::Int8 => Some(input::StreamFormat::Int8),
+                InputFormat::Int16 => Some(input::StreamFormat::Int16),
+                InputFormat::Int32 => Some(input::StreamFormat::Int32),
+                InputFormat::Float32 => Some(input::StreamFormat::Float32),
+                InputFormat::Auto => None,
             };
-            let output_float32 = matches!(args.common.output_format, OutputFormat::Float32);
-            if args.common.verbose {
-                println!("Connecting to stream: {} (input: {:?})", args.address, args.input_format);
-            }
-            slicer::process_stream(
-                &args.address,
-                &args.common.output_dir,
-                args.common.min_duration,
-                args.common.gap,
-                args.common.padding,
-                args.margin,
-                args.rate,
+            let mut config = args.slice_config();
+            config.first_index = args.common.first_index()?;
+            log::debug!("Connecting to stream: {} (input: {:?})", args.address, args.input_format);
+            let stream_config = slicer::StreamConfig {
+                sample_rate: args.rate,
+                threshold_margin: args.margin,
+                threshold_mode: match args.stream_threshold {
+                    StreamThresholdKind::Margin => slicer::StreamThreshold::Margin,
+                    StreamThresholdKind::Percentile => slicer::StreamThreshold::Percentile {
+                        percentile: args.threshold_percentile,
+                        window_seconds: args.percentile_window,
+                    },
+                },
                 format,
-                args.common.verbose,
-                output_float32,
-            )?;
+                input_gain: args.common.input_gain_linear(),
+                conjugate: args.common.conjugate,
+                endian: args.endian.endian(),
+                event_sink: args.event_sink.clone(),
+                time_limit: args.duration.map(Duration::from_secs_f64),
+                chunk_ms: args.chunk_ms,
+                noise_alpha: args.noise_alpha,
+                max_tx_seconds: args.max_tx_seconds,
+                warmup_seconds: args.warmup_seconds,
+                read_timeout: (args.read_timeout > 0).then(|| Duration::from_millis(args.read_timeout)),
+                date_folders: args.date_folders,
+                record_raw: args.record_raw.clone(),
+                record_rotation: output::Rotation {
+                    max_bytes: args.record_rotate_size.map(|mb| mb.saturating_mul(1024 * 1024)),
+                    max_seconds: args.record_rotate_minutes.map(|minutes| minutes * 60.0),
+                },
+                channels: args.channels.zip(args.channel_bw).map(|(count, bandwidth_hz)| slicer::ChannelPlan {
+                    count,
+                    bandwidth_hz,
+                }),
+                reconnect: args.reconnect,
+            };
+            slicer::process_stream(&args.address, &args.common.output_dir, &config, &stream_config)?;
         }
     }
 
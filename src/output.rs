@@ -1,43 +1,320 @@
 use hound::{WavWriter, WavSpec, SampleFormat};
-use std::path::Path;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use chrono::{DateTime, Local, Duration};
+use crate::detector::Segment;
 use crate::input::IqSample;
+use log::info;
+
+/// What to do when an output file already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverwritePolicy {
+    /// Refuse with an error
+    Error,
+    /// Replace the existing file
+    Overwrite,
+    /// Pick a non-colliding name by appending _1, _2, ...
+    Rename,
+}
+
+/// Resolve the path to write to for `path` under the given overwrite policy
+pub fn resolve_output_path(path: &Path, policy: OverwritePolicy) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    resolve_path_with(path, policy, |path| path.exists().then(|| path.to_path_buf()))
+}
+
+/// Like `resolve_output_path` for a planar slice named `path`, which exists when either of its files does
+pub fn resolve_planar_output_path(path: &Path, policy: OverwritePolicy) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    resolve_path_with(path, policy, |path| planar_paths(path).into_iter().find(|p| p.exists()))
+}
+
+/// Resolve `path` under the given overwrite policy, where `existing` returns the file that makes a
+/// candidate path taken (if any)
+pub fn resolve_path_with(
+    path: &Path,
+    policy: OverwritePolicy,
+    existing: impl Fn(&Path) -> Option<PathBuf>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let Some(existing_path) = existing(path) else {
+        return Ok(path.to_path_buf());
+    };
+
+    match policy {
+        OverwritePolicy::Overwrite => Ok(path.to_path_buf()),
+        OverwritePolicy::Error => Err(format!(
+            "Refusing to overwrite existing file {} (use --force to overwrite)",
+            existing_path.display()
+        )
+        .into()),
+        OverwritePolicy::Rename => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            (1..)
+                .map(|n| path.with_file_name(format!("{}_{}{}", stem, n, extension)))
+                .find(|candidate| existing(candidate).is_none())
+                .ok_or_else(|| "no free output filename".into())
+        }
+    }
+}
+
+/// Whether the volume holding `dir` has at least `min_bytes` available
+/// A limit of 0 disables the check without querying the filesystem
+pub fn has_free_space(dir: &Path, min_bytes: u64) -> Result<bool, Box<dyn std::error::Error>> {
+    if min_bytes == 0 {
+        return Ok(true);
+    }
+    Ok(fs2::available_space(dir)? >= min_bytes)
+}
+
+/// Fail early if files can't be created in `dir`, by creating and removing a probe file
+pub fn check_writable(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let probe = dir.join(format!(".iq-slicer-write-test-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("Output directory {} is not writable: {}", dir.display(), e).into())
+}
+
+/// Write via a temporary file renamed into place on success, so readers never see a partial file
+pub(crate) fn write_atomic<P: AsRef<Path>>(
+    path: P,
+    write: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".partial");
+    let temp_path = path.with_file_name(temp_name);
+
+    if let Err(e) = write(&temp_path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Which component goes in the left channel of stereo WAV slices
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChannelOrder {
+    /// I left, Q right
+    #[default]
+    Iq,
+    /// Q left, I right
+    Qi,
+}
+
+impl ChannelOrder {
+    /// Left and right channel values for a sample
+    fn split(self, sample: &IqSample) -> (f32, f32) {
+        match self {
+            ChannelOrder::Iq => (sample.i, sample.q),
+            ChannelOrder::Qi => (sample.q, sample.i),
+        }
+    }
+}
+
+/// Channels of a WAV slice: both components, or one of them for planar output
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WavChannels {
+    Stereo(ChannelOrder),
+    I,
+    Q,
+}
+
+impl WavChannels {
+    fn count(self) -> u16 {
+        match self {
+            WavChannels::Stereo(_) => 2,
+            WavChannels::I | WavChannels::Q => 1,
+        }
+    }
+
+    /// Values of one frame; the second is present for stereo only
+    fn frame(self, sample: &IqSample) -> (f32, Option<f32>) {
+        match self {
+            WavChannels::Stereo(order) => {
+                let (left, right) = order.split(sample);
+                (left, Some(right))
+            }
+            WavChannels::I => (sample.i, None),
+            WavChannels::Q => (sample.q, None),
+        }
+    }
+}
+
+/// The I and Q files of a planar slice named `path`: `<stem>_i.<ext>` and `<stem>_q.<ext>`
+pub fn planar_paths(path: &Path) -> [PathBuf; 2] {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    ["i", "q"].map(|component| path.with_file_name(format!("{}_{}{}", stem, component, extension)))
+}
 
 /// Write IQ samples to a WAV file (stereo int16 PCM, compatible with URH and most tools)
+/// `gain` scales samples before conversion; 1.0 maps full scale to 32000 for some headroom
 pub fn write_iq_wav<P: AsRef<Path>>(
     path: P,
     samples: &[IqSample],
     sample_rate: u32,
+    gain: f32,
+    order: ChannelOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| write_iq_wav_int(path, samples, sample_rate, gain, WavChannels::Stereo(order), IntDepth::Int16))
+}
+
+/// Write IQ samples as a stereo 8-bit WAV (unsigned on disk, as the format requires)
+pub fn write_iq_wav_int8<P: AsRef<Path>>(
+    path: P,
+    samples: &[IqSample],
+    sample_rate: u32,
+    gain: f32,
+    order: ChannelOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| write_iq_wav_int(path, samples, sample_rate, gain, WavChannels::Stereo(order), IntDepth::Int8))
+}
+
+/// Write IQ samples as a stereo 32-bit integer WAV
+pub fn write_iq_wav_int32<P: AsRef<Path>>(
+    path: P,
+    samples: &[IqSample],
+    sample_rate: u32,
+    gain: f32,
+    order: ChannelOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| write_iq_wav_int(path, samples, sample_rate, gain, WavChannels::Stereo(order), IntDepth::Int32))
+}
+
+/// Integer sample depth of a WAV slice
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IntDepth {
+    Int8,
+    Int16,
+    Int32,
+}
+
+impl IntDepth {
+    fn bits(self) -> u16 {
+        match self {
+            IntDepth::Int8 => 8,
+            IntDepth::Int16 => 16,
+            IntDepth::Int32 => 32,
+        }
+    }
+
+    /// Factor from f32 [-1.0, 1.0] to integer; int16 keeps some headroom as it always has
+    fn scale(self) -> f64 {
+        match self {
+            IntDepth::Int8 => 127.0,
+            IntDepth::Int16 => 32000.0,
+            IntDepth::Int32 => 2147483647.0,
+        }
+    }
+
+    /// Smallest and largest representable values
+    fn range(self) -> (f64, f64) {
+        let max = (1i64 << (self.bits() - 1)) as f64;
+        (-max, max - 1.0)
+    }
+
+    /// Convert a normalized value to the nearest integer, clamping at the integer limits
+    fn convert(self, value: f32) -> i32 {
+        let (min, max) = self.range();
+        (value as f64 * self.scale()).round().clamp(min, max) as i32
+    }
+
+    /// Whether a normalized value clips when converted
+    fn clips(self, value: f32) -> bool {
+        let (min, max) = self.range();
+        !(min..=max).contains(&(value as f64 * self.scale()).round())
+    }
+}
+
+fn write_iq_wav_int(
+    path: &Path,
+    samples: &[IqSample],
+    sample_rate: u32,
+    gain: f32,
+    channels: WavChannels,
+    depth: IntDepth,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let spec = WavSpec {
-        channels: 2,
+        channels: channels.count(),
         sample_rate,
-        bits_per_sample: 16,
+        bits_per_sample: depth.bits(),
         sample_format: SampleFormat::Int,
     };
 
     let mut writer = WavWriter::create(path, spec)?;
 
     for sample in samples {
-        // Convert f32 [-1.0, 1.0] to i16, with some headroom
-        let i = (sample.i * 32000.0).clamp(-32768.0, 32767.0) as i16;
-        let q = (sample.q * 32000.0).clamp(-32768.0, 32767.0) as i16;
-        writer.write_sample(i)?;
-        writer.write_sample(q)?;
+        let (first, second) = channels.frame(sample);
+        writer.write_sample(depth.convert(first * gain))?;
+        if let Some(second) = second {
+            writer.write_sample(depth.convert(second * gain))?;
+        }
     }
 
     writer.finalize()?;
     Ok(())
 }
 
+/// Convert f32 [-1.0, 1.0] to i16, with some headroom
+fn to_i16(value: f32) -> i16 {
+    IntDepth::Int16.convert(value) as i16
+}
+
+/// Number of I and Q values that would clip converting to `depth` with `gain`
+fn count_clipped(samples: &[IqSample], gain: f32, depth: IntDepth) -> usize {
+    samples
+        .iter()
+        .map(|s| depth.clips(s.i * gain) as usize + depth.clips(s.q * gain) as usize)
+        .sum()
+}
+
+/// Level measure used to normalize each slice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Largest sample magnitude
+    Peak,
+    /// Root-mean-square magnitude
+    Rms,
+}
+
+/// Linear gain bringing `samples` to `target_db` dBFS by the chosen measure
+/// Capped so the peak magnitude never exceeds full scale; silent slices get unity gain
+pub fn normalization_gain(samples: &[IqSample], mode: Normalization, target_db: f32) -> f32 {
+    let peak = samples.iter().map(|s| s.i.hypot(s.q)).fold(0.0f32, f32::max);
+    if peak <= 0.0 || samples.is_empty() {
+        return 1.0;
+    }
+    let level = match mode {
+        Normalization::Peak => peak,
+        Normalization::Rms => {
+            let mean_power = samples.iter().map(|s| (s.i * s.i + s.q * s.q) as f64).sum::<f64>() / samples.len() as f64;
+            mean_power.sqrt() as f32
+        }
+    };
+    let gain = 10f32.powf(target_db / 20.0) / level;
+    gain.min(1.0 / peak)
+}
+
 /// Write raw IQ samples to a WAV file (stereo float32, compatible with inspectrum and SDR++)
 pub fn write_iq_wav_float32<P: AsRef<Path>>(
     path: P,
     samples: &[IqSample],
     sample_rate: u32,
+    gain: f32,
+    order: ChannelOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| write_iq_wav_f32(path, samples, sample_rate, gain, WavChannels::Stereo(order)))
+}
+
+fn write_iq_wav_f32(
+    path: &Path,
+    samples: &[IqSample],
+    sample_rate: u32,
+    gain: f32,
+    channels: WavChannels,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let spec = WavSpec {
-        channels: 2,
+        channels: channels.count(),
         sample_rate,
         bits_per_sample: 32,
         sample_format: SampleFormat::Float,
@@ -46,29 +323,874 @@ pub fn write_iq_wav_float32<P: AsRef<Path>>(
     let mut writer = WavWriter::create(path, spec)?;
 
     for sample in samples {
-        writer.write_sample(sample.i)?;
-        writer.write_sample(sample.q)?;
+        let (first, second) = channels.frame(sample);
+        writer.write_sample(first * gain)?;
+        if let Some(second) = second {
+            writer.write_sample(second * gain)?;
+        }
     }
 
     writer.finalize()?;
     Ok(())
 }
 
-/// Generate output filename for a slice
-pub fn generate_filename(
-    slice_index: usize,
-    start_sample: usize,
+/// Write demodulated audio as a mono int16 WAV
+pub fn write_audio_wav<P: AsRef<Path>>(
+    path: P,
+    audio: &[f32],
     sample_rate: u32,
-    base_time: DateTime<Local>,
-) -> String {
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut writer = WavWriter::create(path, spec)?;
+        for &sample in audio {
+            writer.write_sample(to_i16(sample))?;
+        }
+        writer.finalize()?;
+        Ok(())
+    })
+}
+
+/// Write IQ samples as headerless interleaved little-endian float32 (.cf32, for GNU Radio)
+pub fn write_iq_raw_cf32<P: AsRef<Path>>(path: P, samples: &[IqSample], gain: f32) -> Result<(), Box<dyn std::error::Error>> {
+    write_atomic(path, |path| {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for sample in samples {
+            file.write_all(&(sample.i * gain).to_le_bytes())?;
+            file.write_all(&(sample.q * gain).to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    })
+}
+
+/// Output file format for slices, dispatching to the matching writer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SliceWriter {
+    /// Stereo int8 WAV
+    WavInt8,
+    /// Stereo int16 WAV
+    WavInt16,
+    /// Stereo int32 WAV
+    WavInt32,
+    /// Stereo float32 WAV
+    WavFloat32,
+    /// Headerless interleaved float32
+    Cf32,
+}
+
+impl SliceWriter {
+    /// Filename extension, including the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            SliceWriter::WavInt8 | SliceWriter::WavInt16 | SliceWriter::WavInt32 | SliceWriter::WavFloat32 => ".wav",
+            SliceWriter::Cf32 => ".cf32",
+        }
+    }
+
+    /// Gain under which samples read back from this format are written with their original values
+    /// Integer WAVs read back relative to full scale (2^(bits-1)) but are written at `IntDepth::scale`,
+    /// so int16's headroom would otherwise compound on every rewrite
+    pub fn unity_gain(self) -> f32 {
+        let depth = match self {
+            SliceWriter::WavInt8 => IntDepth::Int8,
+            SliceWriter::WavInt16 => IntDepth::Int16,
+            SliceWriter::WavInt32 => IntDepth::Int32,
+            SliceWriter::WavFloat32 | SliceWriter::Cf32 => return 1.0,
+        };
+        ((1u64 << (depth.bits() - 1)) as f64 / depth.scale()) as f32
+    }
+
+    /// Write a slice scaled by `gain`, returning how many I/Q values clipped, or None for float
+    /// formats, which can't clip. `order` applies to WAV output; .cf32 is always interleaved I then Q
+    pub fn write(
+        self,
+        path: &Path,
+        samples: &[IqSample],
+        sample_rate: u32,
+        gain: f32,
+        order: ChannelOrder,
+    ) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        match self {
+            SliceWriter::WavInt8 => {
+                write_iq_wav_int8(path, samples, sample_rate, gain, order)?;
+                Ok(Some(count_clipped(samples, gain, IntDepth::Int8)))
+            }
+            SliceWriter::WavInt16 => {
+                write_iq_wav(path, samples, sample_rate, gain, order)?;
+                Ok(Some(count_clipped(samples, gain, IntDepth::Int16)))
+            }
+            SliceWriter::WavInt32 => {
+                write_iq_wav_int32(path, samples, sample_rate, gain, order)?;
+                Ok(Some(count_clipped(samples, gain, IntDepth::Int32)))
+            }
+            SliceWriter::WavFloat32 => write_iq_wav_float32(path, samples, sample_rate, gain, order).map(|_| None),
+            SliceWriter::Cf32 => write_iq_raw_cf32(path, samples, gain).map(|_| None),
+        }
+    }
+
+    /// Write a slice as two mono WAVs named by `planar_paths`, returning how many I/Q values clipped
+    /// (None for float32)
+    pub fn write_planar(
+        self,
+        path: &Path,
+        samples: &[IqSample],
+        sample_rate: u32,
+        gain: f32,
+    ) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+        let depth = match self {
+            SliceWriter::WavInt8 => Some(IntDepth::Int8),
+            SliceWriter::WavInt16 => Some(IntDepth::Int16),
+            SliceWriter::WavInt32 => Some(IntDepth::Int32),
+            SliceWriter::WavFloat32 => None,
+            SliceWriter::Cf32 => return Err("planar output needs a WAV format, not .cf32".into()),
+        };
+        let [i_path, q_path] = planar_paths(path);
+        for (path, channels) in [(i_path, WavChannels::I), (q_path, WavChannels::Q)] {
+            write_atomic(path, |path| match depth {
+                Some(depth) => write_iq_wav_int(path, samples, sample_rate, gain, channels, depth),
+                None => write_iq_wav_f32(path, samples, sample_rate, gain, channels),
+            })?;
+        }
+        Ok(depth.map(|depth| count_clipped(samples, gain, depth)))
+    }
+}
+
+/// Largest WAV data chunk to write, leaving headroom below the format's 4 GiB size field
+const WAV_MAX_DATA_BYTES: u64 = u32::MAX as u64 - 1024 * 1024;
+
+/// Limits at which a continuous recording rolls over into a new file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_seconds: Option<f64>,
+}
+
+impl Rotation {
+    fn enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_seconds.is_some()
+    }
+}
+
+/// Continuous WAV recording of a whole stream, rolled over into timestamped files by size or
+/// time, and always before the WAV size limit. Every file is finalized with a valid header.
+/// Samples are int16 or, with `float32`, float32; the recording is a WAV whatever the slice format.
+pub struct RawRecorder {
+    writer: WavWriter<std::io::BufWriter<std::fs::File>>,
+    spec: WavSpec,
+    float32: bool,
+    /// Requested path; rotated files are named after it
+    base_path: PathBuf,
+    path: PathBuf,
+    /// Frames per file before rolling over
+    max_frames: u64,
+    frames: u64,
+}
+
+impl RawRecorder {
+    pub fn create(
+        path: &Path,
+        sample_rate: u32,
+        float32: bool,
+        rotation: Rotation,
+        overwrite: OverwritePolicy,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: if float32 { 32 } else { 16 },
+            sample_format: if float32 { SampleFormat::Float } else { SampleFormat::Int },
+        };
+        let frame_bytes = 2 * spec.bits_per_sample as u64 / 8;
+        let max_frames = [
+            Some(WAV_MAX_DATA_BYTES),
+            rotation.max_bytes,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|bytes| bytes / frame_bytes)
+        .chain(rotation.max_seconds.map(|seconds| (seconds * sample_rate as f64) as u64))
+        .min()
+        .unwrap_or(u64::MAX)
+        .max(1);
+
+        let base_path = path.to_path_buf();
+        let path = if rotation.enabled() {
+            resolve_output_path(&timestamped_path(path), OverwritePolicy::Rename)?
+        } else {
+            resolve_output_path(path, overwrite)?
+        };
+        Ok(Self {
+            writer: WavWriter::create(&path, spec)?,
+            spec,
+            float32,
+            base_path,
+            path,
+            max_frames,
+            frames: 0,
+        })
+    }
+
+    /// File currently being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, samples: &[IqSample]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut rest = samples;
+        while !rest.is_empty() {
+            if self.frames >= self.max_frames {
+                self.rotate()?;
+            }
+            let room = (self.max_frames - self.frames).min(rest.len() as u64) as usize;
+            let (now, later) = rest.split_at(room);
+            for sample in now {
+                if self.float32 {
+                    self.writer.write_sample(sample.i)?;
+                    self.writer.write_sample(sample.q)?;
+                } else {
+                    self.writer.write_sample(to_i16(sample.i))?;
+                    self.writer.write_sample(to_i16(sample.q))?;
+                }
+            }
+            self.frames += room as u64;
+            rest = later;
+        }
+        Ok(())
+    }
+
+    /// Finalize the current file and continue in a new timestamped one
+    fn rotate(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = resolve_output_path(&timestamped_path(&self.base_path), OverwritePolicy::Rename)?;
+        let writer = WavWriter::create(&path, self.spec)?;
+        std::mem::replace(&mut self.writer, writer).finalize()?;
+        info!("Recording continues in {}", path.display());
+        self.path = path;
+        self.frames = 0;
+        Ok(())
+    }
+
+    /// Write the final WAV header
+    pub fn finalize(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.finalize()?;
+        Ok(())
+    }
+}
+
+/// `path` with the current local time appended to its stem, e.g. `raw_2026-01-03_14-23-01.wav`
+fn timestamped_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let time = Local::now().format("%Y-%m-%d_%H-%M-%S");
+    path.with_file_name(format!("{}_{}{}", stem, time, extension))
+}
+
+/// A placeholder or literal piece of a filename template
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    /// Slice number, zero-padded to `width`
+    Index { width: usize },
+    Timestamp,
+    StartSeconds,
+    PeakDb,
+    Crest,
+    Freq,
+    /// Frequency in whole kHz with `_` thousands separators; left out when unknown
+    FreqKhz,
+}
+
+/// Filename template with `{placeholder}` fields, parsed and validated up front
+/// Supported: {index}, {index:0N}, {timestamp}, {start_seconds}, {peak_db}, {crest}, {freq}, {freq_khz}
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl NameTemplate {
+    /// Prefix of the default naming
+    pub const DEFAULT_PREFIX: &'static str = "slice";
+    /// Index digits of the default naming; wide enough that long sessions still sort by name
+    pub const DEFAULT_INDEX_WIDTH: usize = 4;
+
+    /// Default naming `{prefix}_{index:0N}_{freq_khz}_{timestamp}`: slice_0001_146_520kHz_2026-01-03_14-23-01,
+    /// or slice_0001_2026-01-03_14-23-01 when the slice has no frequency estimate
+    pub fn standard(prefix: &str, index_width: usize) -> Self {
+        let mut parts = Vec::new();
+        if !prefix.is_empty() {
+            parts.push(TemplatePart::Literal(format!("{}_", prefix)));
+        }
+        parts.extend([
+            TemplatePart::Index { width: index_width },
+            TemplatePart::Literal("_".to_string()),
+            TemplatePart::FreqKhz,
+            TemplatePart::Literal("_".to_string()),
+            TemplatePart::Timestamp,
+        ]);
+        Self { parts }
+    }
+
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(TemplatePart::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed '{{' in name template '{}'", template))?
+                + open;
+            let field = &rest[open + 1..close];
+            let (name, spec) = match field.split_once(':') {
+                Some((name, spec)) => (name, Some(spec)),
+                None => (field, None),
+            };
+
+            let part = match (name, spec) {
+                ("index", None) => TemplatePart::Index { width: 0 },
+                ("index", Some(spec)) if spec.starts_with('0') && spec.len() > 1 => {
+                    let width = spec[1..]
+                        .parse()
+                        .map_err(|_| format!("invalid index width '{{{}}}' in name template", field))?;
+                    TemplatePart::Index { width }
+                }
+                ("timestamp", None) => TemplatePart::Timestamp,
+                ("start_seconds", None) => TemplatePart::StartSeconds,
+                ("peak_db", None) => TemplatePart::PeakDb,
+                ("crest", None) => TemplatePart::Crest,
+                ("freq", None) => TemplatePart::Freq,
+                ("freq_khz", None) => TemplatePart::FreqKhz,
+                _ => return Err(format!("unknown placeholder '{{{}}}' in name template", field)),
+            };
+            parts.push(part);
+            rest = &rest[close + 1..];
+        }
+
+        if rest.contains('}') {
+            return Err(format!("unmatched '}}' in name template '{}'", template));
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// This template with `_{freq}Hz` appended unless it already includes `{freq}` or `{freq_khz}`
+    pub fn with_freq(&self) -> Self {
+        let mut template = self.clone();
+        if !template.parts.iter().any(|part| matches!(part, TemplatePart::Freq | TemplatePart::FreqKhz)) {
+            template.parts.push(TemplatePart::Literal("_".to_string()));
+            template.parts.push(TemplatePart::Freq);
+            template.parts.push(TemplatePart::Literal("Hz".to_string()));
+        }
+        template
+    }
+
+    /// Recover the slice index from a filename generated by this template
+    /// Only templates whose `{index}` is preceded by literal text alone can be parsed
+    pub fn parse_index(&self, filename: &str) -> Option<usize> {
+        let mut prefix = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => prefix.push_str(text),
+                TemplatePart::Index { .. } => {
+                    let rest = filename.strip_prefix(prefix.as_str())?;
+                    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                    return rest[..digits].parse().ok();
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Label for slice `index`: the `{index}` field as the filename formats it, after the literal text just
+    /// before it (slice_0001 for the default naming). Used by label tracks and cue markers.
+    pub fn index_label(&self, index: usize) -> String {
+        let mut prefix = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => prefix.push_str(text),
+                TemplatePart::Index { width } => {
+                    // Separators left over from a placeholder before the prefix don't belong in a label
+                    let prefix = prefix.trim_start_matches(['_', '-', '.', ' ']);
+                    return format!("{}{:0width$}", prefix, index, width = *width);
+                }
+                _ => prefix.clear(),
+            }
+        }
+        index.to_string()
+    }
+
+    /// Whether `parse_index` can recover indices from this template's filenames
+    pub fn has_parseable_index(&self) -> bool {
+        self.parts
+            .iter()
+            .find(|part| !matches!(part, TemplatePart::Literal(_)))
+            .is_some_and(|part| matches!(part, TemplatePart::Index { .. }))
+    }
+}
+
+/// Highest slice index among existing files in `dir` and its immediate subdirectories (date folders)
+/// Returns 0 when the directory is missing or holds no matching slices
+pub fn highest_slice_index(dir: &Path, template: &NameTemplate) -> std::io::Result<usize> {
+    let mut highest = 0;
+    if !dir.is_dir() {
+        return Ok(highest);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            for nested in std::fs::read_dir(&path)? {
+                let name = nested?.file_name();
+                if let Some(index) = name.to_str().and_then(|n| template.parse_index(n)) {
+                    highest = highest.max(index);
+                }
+            }
+        } else if let Some(index) = entry.file_name().to_str().and_then(|n| template.parse_index(n)) {
+            highest = highest.max(index);
+        }
+    }
+    Ok(highest)
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self::standard(Self::DEFAULT_PREFIX, Self::DEFAULT_INDEX_WIDTH)
+    }
+}
+
+impl std::fmt::Display for NameTemplate {
+    /// The template in `parse` syntax
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => f.write_str(text)?,
+                TemplatePart::Index { width: 0 } => f.write_str("{index}")?,
+                TemplatePart::Index { width } => write!(f, "{{index:0{}}}", width)?,
+                TemplatePart::Timestamp => f.write_str("{timestamp}")?,
+                TemplatePart::StartSeconds => f.write_str("{start_seconds}")?,
+                TemplatePart::PeakDb => f.write_str("{peak_db}")?,
+                TemplatePart::Crest => f.write_str("{crest}")?,
+                TemplatePart::Freq => f.write_str("{freq}")?,
+                TemplatePart::FreqKhz => f.write_str("{freq_khz}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per-slice values available to the filename template
+#[derive(Debug, Clone)]
+pub struct SliceName {
+    pub index: usize,
+    pub start_sample: usize,
+    pub sample_rate: u32,
+    pub base_time: DateTime<Local>,
+    pub peak_power_db: Option<f32>,
+    /// Peak minus mean power in dB, when measured
+    pub crest_db: Option<f32>,
+    /// Estimated frequency in Hz, when known
+    pub frequency_hz: Option<f64>,
+}
+
+/// Generate output filename for a slice, ending in `extension`
+pub fn generate_filename(template: &NameTemplate, slice: &SliceName, extension: &str) -> String {
     // Calculate timestamp offset from base time
-    let offset_seconds = start_sample as f64 / sample_rate as f64;
+    let offset_seconds = slice.start_sample as f64 / slice.sample_rate as f64;
     let offset_duration = Duration::milliseconds((offset_seconds * 1000.0) as i64);
-    let slice_time = base_time + offset_duration;
+    let slice_time = slice.base_time + offset_duration;
+
+    let mut name = String::new();
+    // An unknown {freq_khz} is left out along with the separator that follows it
+    let mut skip_separator = false;
+    for part in &template.parts {
+        match part {
+            TemplatePart::Literal(text) if skip_separator => {
+                name.push_str(text.strip_prefix(['_', '-', '.']).unwrap_or(text));
+            }
+            TemplatePart::Literal(text) => name.push_str(text),
+            TemplatePart::Index { width } => name.push_str(&format!("{:0width$}", slice.index, width = *width)),
+            TemplatePart::Timestamp => name.push_str(&slice_time.format("%Y-%m-%d_%H-%M-%S").to_string()),
+            TemplatePart::StartSeconds => name.push_str(&format!("{:.3}", offset_seconds)),
+            TemplatePart::PeakDb => match slice.peak_power_db {
+                Some(db) => name.push_str(&format!("{:.1}", db)),
+                None => name.push_str("unknown"),
+            },
+            TemplatePart::Crest => match slice.crest_db {
+                Some(db) => name.push_str(&format!("{:.1}", db)),
+                None => name.push_str("unknown"),
+            },
+            TemplatePart::Freq => match slice.frequency_hz {
+                Some(hz) => name.push_str(&format!("{:.0}", hz)),
+                None => name.push_str("unknown"),
+            },
+            TemplatePart::FreqKhz => {
+                if let Some(hz) = slice.frequency_hz {
+                    name.push_str(&format_khz(hz));
+                }
+            }
+        }
+        skip_separator = *part == TemplatePart::FreqKhz && slice.frequency_hz.is_none();
+    }
+    name.push_str(extension);
+    name
+}
+
+/// Frequency rounded to whole kHz with `_` between digit groups, e.g. 146_520kHz
+fn format_khz(hz: f64) -> String {
+    let khz = (hz / 1000.0).round() as i64;
+    let digits = khz.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}kHz", if khz < 0 { "-" } else { "" }, grouped)
+}
+
+/// Write an Audacity label track (tab-separated start/end seconds and label) for detected segments
+/// `labels` holds one name per segment (see `NameTemplate::index_label`), followed by its peak power
+pub fn write_audacity_labels<P: AsRef<Path>>(
+    path: P,
+    segments: &[Segment],
+    sample_rate: u32,
+    labels: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    for (segment, label) in segments.iter().zip(labels) {
+        writeln!(
+            file,
+            "{:.6}\t{:.6}\t{} ({:.1} dB)",
+            segment.start_sample as f64 / sample_rate as f64,
+            segment.end_sample as f64 / sample_rate as f64,
+            label,
+            segment.peak_power_db
+        )?;
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Copy a whole recording to a WAV with a `cue ` point and `labl` label at each segment start
+/// Uses the sample format of `writer`, falling back to float32 WAV for .cf32; `labels` holds one name per
+/// segment (see `NameTemplate::index_label`), followed in each marker by the segment's peak power
+pub fn write_annotated_wav(
+    path: &Path,
+    samples: &[IqSample],
+    sample_rate: u32,
+    segments: &[Segment],
+    labels: &[String],
+    writer: SliceWriter,
+    order: ChannelOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let writer = if writer == SliceWriter::Cf32 { SliceWriter::WavFloat32 } else { writer };
+    writer.write(path, samples, sample_rate, 1.0, order)?;
 
-    format!(
-        "slice_{:03}_{}.wav",
-        slice_index,
-        slice_time.format("%Y-%m-%d_%H-%M-%S")
-    )
+    let mut cue = Vec::with_capacity(4 + segments.len() * 24);
+    cue.extend_from_slice(&(segments.len() as u32).to_le_bytes());
+    let mut adtl = b"adtl".to_vec();
+    for (i, (segment, name)) in segments.iter().zip(labels).enumerate() {
+        let id = i as u32 + 1;
+        cue.extend_from_slice(&id.to_le_bytes());
+        cue.extend_from_slice(&(segment.start_sample as u32).to_le_bytes());
+        cue.extend_from_slice(b"data");
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&(segment.start_sample as u32).to_le_bytes());
+
+        let mut label = id.to_le_bytes().to_vec();
+        label.extend_from_slice(format!("{} ({:.1} dB)", name, segment.peak_power_db).as_bytes());
+        label.push(0);
+        push_riff_chunk(&mut adtl, b"labl", &label);
+    }
+    let mut chunks = Vec::new();
+    push_riff_chunk(&mut chunks, b"cue ", &cue);
+    push_riff_chunk(&mut chunks, b"LIST", &adtl);
+
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let riff_size = file.seek(SeekFrom::End(0))? - 8 + chunks.len() as u64;
+    let riff_size = u32::try_from(riff_size).map_err(|_| format!("{} is too large for cue markers", path.display()))?;
+    file.write_all(&chunks)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_size.to_le_bytes())?;
+    Ok(())
+}
+
+/// Append a RIFF chunk (id, little-endian size, body, pad byte to even length) to `out`
+fn push_riff_chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Write the detector's power profile as CSV for offline tuning
+/// `thresholds` holds the per-frame detection threshold; `sample_offset` positions frames within the recording
+pub fn write_profile_csv<P: AsRef<Path>>(
+    path: P,
+    power_profile: &[f32],
+    thresholds: &[f32],
+    hop_size: usize,
+    sample_rate: u32,
+    sample_offset: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    writeln!(file, "frame_index,time_seconds,power_db,threshold_db")?;
+    for (i, (power, threshold)) in power_profile.iter().zip(thresholds).enumerate() {
+        writeln!(
+            file,
+            "{},{:.6},{:.3},{:.3}",
+            i,
+            (sample_offset + i * hop_size) as f64 / sample_rate as f64,
+            power,
+            threshold
+        )?;
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Write per-bin spectral occupancy as CSV, frequencies offset by `center_frequency`
+/// (0 gives frequencies relative to the tuned center)
+pub fn write_occupancy_csv<P: AsRef<Path>>(
+    path: P,
+    bin_fractions: &[f32],
+    sample_rate: u32,
+    center_frequency: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let bin_hz = sample_rate as f64 / bin_fractions.len() as f64;
+    let half = (bin_fractions.len() / 2) as f64;
+
+    writeln!(file, "frequency_hz,active_fraction")?;
+    for (bin, fraction) in bin_fractions.iter().enumerate() {
+        writeln!(file, "{:.1},{:.4}", center_frequency + (bin as f64 - half) * bin_hz, fraction)?;
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Color scale for spectrogram images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Colormap {
+    Viridis,
+    Gray,
+    Hot,
+}
+
+impl Colormap {
+    /// Map a normalized level (0-1) to RGB
+    fn color(self, level: f32) -> [u8; 3] {
+        let stops: &[[f32; 3]] = match self {
+            Colormap::Viridis => &[
+                [68.0, 1.0, 84.0],
+                [59.0, 82.0, 139.0],
+                [33.0, 145.0, 140.0],
+                [94.0, 201.0, 98.0],
+                [253.0, 231.0, 37.0],
+            ],
+            Colormap::Gray => &[[0.0, 0.0, 0.0], [255.0, 255.0, 255.0]],
+            Colormap::Hot => &[
+                [0.0, 0.0, 0.0],
+                [230.0, 0.0, 0.0],
+                [255.0, 210.0, 0.0],
+                [255.0, 255.0, 255.0],
+            ],
+        };
+        let position = level.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let t = position - index as f32;
+        let (a, b) = (stops[index], stops[index + 1]);
+        [0, 1, 2].map(|c| (a[c] + (b[c] - a[c]) * t).round() as u8)
+    }
+}
+
+/// Spectrogram image size limits; long recordings are averaged down to at most this many columns
+const SPECTROGRAM_MAX_WIDTH: usize = 2000;
+const SPECTROGRAM_FFT_SIZE: usize = 512;
+/// FFT frames averaged per image column, spread evenly across the column's time span
+const SPECTROGRAM_FRAMES_PER_COLUMN: usize = 8;
+
+/// Write a waterfall PNG (time left to right, frequency increasing upwards) with detected segments boxed
+/// Segments with a known band are boxed around it; others span the full height
+pub fn write_spectrogram_png<P: AsRef<Path>>(
+    path: P,
+    samples: &[IqSample],
+    sample_rate: u32,
+    segments: &[Segment],
+    colormap: Colormap,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fft_size = SPECTROGRAM_FFT_SIZE;
+    if samples.len() < fft_size {
+        return Err("recording too short for a spectrogram".into());
+    }
+
+    let width = (samples.len() / fft_size).clamp(1, SPECTROGRAM_MAX_WIDTH);
+    let height = fft_size;
+    let column_span = samples.len() as f64 / width as f64;
+
+    let window = crate::detector::blackman_window(fft_size);
+    let fft = rustfft::FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut buffer = vec![rustfft::num_complex::Complex::new(0.0f32, 0.0); fft_size];
+    let half = fft_size / 2;
+
+    // Column-major dB values, bins ordered -fs/2..+fs/2
+    let mut levels = vec![0.0f32; width * height];
+    for column in 0..width {
+        let span_start = (column as f64 * column_span) as usize;
+        let span_len = (column_span as usize).max(fft_size);
+        let frames = SPECTROGRAM_FRAMES_PER_COLUMN.min(span_len / fft_size).max(1);
+        let mut power = vec![0.0f32; fft_size];
+        for f in 0..frames {
+            let start = (span_start + f * span_len / frames).min(samples.len() - fft_size);
+            for ((slot, s), w) in buffer.iter_mut().zip(&samples[start..start + fft_size]).zip(&window) {
+                *slot = rustfft::num_complex::Complex::new(s.i * w, s.q * w);
+            }
+            fft.process(&mut buffer);
+            for (p, c) in power.iter_mut().zip(&buffer) {
+                *p += c.norm_sqr();
+            }
+        }
+        let norm = (frames * fft_size * fft_size) as f32;
+        for bin in 0..fft_size {
+            levels[column * height + bin] = 10.0 * (power[(bin + half) % fft_size] / norm).max(1e-30).log10();
+        }
+    }
+
+    // Scale from just below the typical (noise) level to the strongest cell
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let low = sorted[sorted.len() / 2] - 3.0;
+    let high = sorted[sorted.len() - 1].max(low + 1.0);
+
+    let mut image = image::RgbImage::new(width as u32, height as u32);
+    for column in 0..width {
+        for bin in 0..height {
+            let level = (levels[column * height + bin] - low) / (high - low);
+            let y = (height - 1 - bin) as u32;
+            image.put_pixel(column as u32, y, image::Rgb(colormap.color(level)));
+        }
+    }
+
+    // Detection boxes
+    let outline = image::Rgb([255u8, 255, 255]);
+    let bin_hz = sample_rate as f64 / fft_size as f64;
+    for segment in segments {
+        let x0 = ((segment.start_sample as f64 / column_span) as usize).min(width - 1);
+        let x1 = ((segment.end_sample as f64 / column_span) as usize).clamp(x0, width - 1);
+        let (bin_low, bin_high) = match segment.band {
+            Some(band) => {
+                let to_bin = |hz: f64| ((hz / bin_hz + half as f64).round().max(0.0) as usize).min(height - 1);
+                (
+                    to_bin(band.center_hz - band.bandwidth_hz / 2.0),
+                    to_bin(band.center_hz + band.bandwidth_hz / 2.0),
+                )
+            }
+            None => (0, height - 1),
+        };
+        let (y0, y1) = ((height - 1 - bin_high) as u32, (height - 1 - bin_low) as u32);
+        for x in x0 as u32..=x1 as u32 {
+            image.put_pixel(x, y0, outline);
+            image.put_pixel(x, y1, outline);
+        }
+        for y in y0..=y1 {
+            image.put_pixel(x0 as u32, y, outline);
+            image.put_pixel(x1 as u32, y, outline);
+        }
+    }
+
+    image.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::raw::read_iq_raw;
+    use crate::input::wav::{read_iq_wav, WavOptions};
+
+    const VALUES: [(f32, f32); 4] = [(0.5, -0.25), (0.9, -0.9), (0.001, 0.0), (-1.0, 0.75)];
+
+    #[test]
+    fn each_output_format_round_trips_within_one_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let samples: Vec<IqSample> = VALUES.iter().map(|&(i, q)| IqSample::new(i, q)).collect();
+        let options = WavOptions { sample_rate: Some(48000), ..WavOptions::default() };
+
+        for (writer, depth) in [
+            (SliceWriter::WavInt8, Some(IntDepth::Int8)),
+            (SliceWriter::WavInt16, Some(IntDepth::Int16)),
+            (SliceWriter::WavInt32, Some(IntDepth::Int32)),
+            (SliceWriter::WavFloat32, None),
+            (SliceWriter::Cf32, None),
+        ] {
+            let path = dir.path().join(format!("{:?}{}", writer, writer.extension()));
+            let clipped = writer.write(&path, &samples, 48000, 1.0, ChannelOrder::Iq).unwrap();
+            let (read, metadata) = match writer {
+                SliceWriter::Cf32 => read_iq_raw(&path, &options).unwrap(),
+                _ => read_iq_wav(&path, &options).unwrap(),
+            };
+            assert_eq!(metadata.sample_rate, 48000);
+            assert_eq!(clipped, depth.map(|_| 0), "{:?}", writer);
+
+            // Integer formats read back relative to their full scale, so int16's headroom shows
+            let (factor, step) = match depth {
+                Some(depth) => {
+                    let full_scale = (1u64 << (depth.bits() - 1)) as f64;
+                    ((depth.scale() / full_scale) as f32, (1.0 / full_scale) as f32)
+                }
+                None => (1.0, 0.0),
+            };
+            for (sample, &(i, q)) in read.iter().zip(&VALUES) {
+                assert!((sample.i - i * factor).abs() <= step, "{:?}: I {} read back as {}", writer, i, sample.i);
+                assert!((sample.q - q * factor).abs() <= step, "{:?}: Q {} read back as {}", writer, q, sample.q);
+            }
+            assert_eq!(read.len(), samples.len());
+        }
+    }
+
+    #[test]
+    fn four_digit_indices_sort_past_a_thousand() {
+        let template = NameTemplate::default();
+        let name = |index| {
+            generate_filename(
+                &template,
+                &SliceName {
+                    index,
+                    start_sample: 0,
+                    sample_rate: 48000,
+                    base_time: Local::now(),
+                    peak_power_db: None,
+                    crest_db: None,
+                    frequency_hz: None,
+                },
+                ".wav",
+            )
+        };
+        let (before, after) = (name(999), name(1000));
+        assert!(before.starts_with("slice_0999_") && after.starts_with("slice_1000_"), "{} {}", before, after);
+        assert!(before < after);
+        assert_eq!(template.index_label(999), "slice_0999");
+        assert_eq!(NameTemplate::parse("{timestamp}_rx{index:03}").unwrap().index_label(7), "rx007");
+    }
 }
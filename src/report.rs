@@ -0,0 +1,316 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+use log::{info, warn};
+use crate::output::write_atomic;
+
+/// Per-slice entry in the JSON report
+#[derive(Debug, Clone, Serialize)]
+pub struct SliceRecord {
+    pub filename: String,
+    /// I and Q files of a --planar slice, which `filename` names together
+    pub planar_filenames: Option<Vec<String>>,
+    /// Offset of the slice start within the recording (file mode)
+    pub start_seconds: Option<f64>,
+    /// Sample range of the slice within the source recording, or since the stream started (stream mode),
+    /// at the slice's own sample rate
+    pub start_sample: usize,
+    pub end_sample: usize,
+    pub duration_ms: f32,
+    pub peak_power_db: f32,
+    /// Peak minus mean power over the slice (dB); low for steady carriers, high for bursty signals
+    pub crest_db: Option<f32>,
+    /// Center of the occupied band, offset from the tuned frequency
+    pub center_hz: Option<f64>,
+    /// Absolute center of the occupied band, when the tuned frequency is known
+    pub frequency_hz: Option<f64>,
+    /// Occupied bandwidth at the configured level below the peak
+    pub bandwidth_hz: Option<f64>,
+    /// Rough symbol rate estimate for digital signals (Hz)
+    pub symbol_rate_hz: Option<f64>,
+    /// Carrier frequency offset removed by --correct-cfo (Hz)
+    pub cfo_hz: Option<f64>,
+    /// Gain applied by --normalize, so the original level can be recovered
+    pub normalize_gain_db: Option<f32>,
+}
+
+/// Metadata written next to a slice with --sidecar, so the slice can be traced back to its source alone
+#[derive(Debug, Serialize)]
+pub struct SliceSidecar<'a> {
+    pub sample_rate: u32,
+    /// Wall-clock time of the slice's first sample (RFC3339)
+    pub start_time: String,
+    #[serde(flatten)]
+    pub record: &'a SliceRecord,
+}
+
+impl SliceSidecar<'_> {
+    /// Write the sidecar for the slice at `slice_path`, as the same name ending in .json
+    pub fn write(&self, slice_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(slice_path.with_extension("json"))?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// How much of the analyzed input held transmissions, before padding (file mode)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Activity {
+    pub signal_seconds: f64,
+    pub analyzed_seconds: f64,
+}
+
+/// Aggregate statistics for a run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub total_slices: usize,
+    pub total_captured_seconds: f64,
+    pub mean_duration_seconds: Option<f64>,
+    pub median_duration_seconds: Option<f64>,
+    pub min_duration_seconds: Option<f64>,
+    pub max_duration_seconds: Option<f64>,
+    pub mean_peak_power_db: Option<f32>,
+    /// Wall-clock time spent monitoring (stream mode)
+    pub monitored_seconds: Option<f64>,
+    /// Estimated stream time lost to dropouts (stream mode)
+    pub dropout_seconds: Option<f64>,
+    /// Share of written I/Q values that clipped at the integer limits; None for float output
+    pub clipped_percent: Option<f64>,
+    /// Share of input samples at ADC full scale
+    pub input_saturated_percent: Option<f64>,
+    /// Detected transmission time before padding, and the input time it was found in (file mode)
+    pub signal_seconds: Option<f64>,
+    pub analyzed_seconds: Option<f64>,
+    /// Signal time as a share of the analyzed time
+    pub duty_cycle_percent: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    summary: RunSummary,
+    slices: &'a [SliceRecord],
+}
+
+/// Seconds as `1h02m03.4s`, `4m12.0s` or `12.3s`
+fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor() as u64;
+    let rest = seconds - minutes as f64 * 60.0;
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => format!("{:.1}s", rest),
+        (0, m) => format!("{}m{:04.1}s", m, rest),
+        (h, m) => format!("{}h{:02}m{:04.1}s", h, m, rest),
+    }
+}
+
+/// Input saturation above this share of samples triggers a warning
+const SATURATION_WARN_PERCENT: f64 = 0.01;
+
+/// Collects per-slice results during a run for the end-of-run summary and JSON report
+#[derive(Debug, Default)]
+pub struct RunStats {
+    slices: Vec<SliceRecord>,
+    /// Wall-clock time spent monitoring (stream mode)
+    pub monitored_seconds: Option<f64>,
+    /// Estimated stream time lost to dropouts (stream mode)
+    pub dropout_seconds: Option<f64>,
+    /// I/Q values written in integer formats, and how many of them clipped
+    output_values: u64,
+    clipped_values: u64,
+    /// Input samples read, and how many of them were at full scale
+    input_samples: u64,
+    saturated_samples: u64,
+    /// Transmission time within the analyzed input (file mode)
+    activity: Option<Activity>,
+}
+
+impl RunStats {
+    pub fn slice_count(&self) -> usize {
+        self.slices.len()
+    }
+
+    pub fn record(&mut self, slice: SliceRecord) {
+        self.slices.push(slice);
+    }
+
+    /// Count `samples` input samples read, `saturated` of which hit full scale
+    pub fn record_input(&mut self, samples: usize, saturated: usize) {
+        self.input_samples += samples as u64;
+        self.saturated_samples += saturated as u64;
+    }
+
+    /// Add one input's transmission and analyzed time
+    pub fn record_activity(&mut self, activity: Activity) {
+        let total = self.activity.get_or_insert_with(Activity::default);
+        total.signal_seconds += activity.signal_seconds;
+        total.analyzed_seconds += activity.analyzed_seconds;
+    }
+
+    /// Count `samples` IQ samples written, `clipped` of whose I/Q values clipped
+    /// Float slices (`clipped` None) can't clip and are left out of the clipped share
+    pub fn record_clipping(&mut self, samples: usize, clipped: Option<usize>) {
+        if let Some(clipped) = clipped {
+            self.output_values += 2 * samples as u64;
+            self.clipped_values += clipped as u64;
+        }
+    }
+
+    /// Add another run's slices and counters to this one, e.g. to total a batch of files
+    pub fn merge(&mut self, other: RunStats) {
+        self.slices.extend(other.slices);
+        self.output_values += other.output_values;
+        self.clipped_values += other.clipped_values;
+        self.input_samples += other.input_samples;
+        self.saturated_samples += other.saturated_samples;
+        if let Some(activity) = other.activity {
+            self.record_activity(activity);
+        }
+    }
+
+    pub fn summary(&self) -> RunSummary {
+        let mut durations: Vec<f64> = self.slices.iter().map(|s| s.duration_ms as f64 / 1000.0).collect();
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let total: f64 = durations.iter().sum();
+        let count = durations.len();
+        let median = match count {
+            0 => None,
+            n if n % 2 == 1 => Some(durations[n / 2]),
+            n => Some((durations[n / 2 - 1] + durations[n / 2]) / 2.0),
+        };
+
+        RunSummary {
+            total_slices: count,
+            total_captured_seconds: total,
+            mean_duration_seconds: (count > 0).then(|| total / count as f64),
+            median_duration_seconds: median,
+            min_duration_seconds: durations.first().copied(),
+            max_duration_seconds: durations.last().copied(),
+            mean_peak_power_db: (count > 0)
+                .then(|| self.slices.iter().map(|s| s.peak_power_db).sum::<f32>() / count as f32),
+            monitored_seconds: self.monitored_seconds,
+            dropout_seconds: self.dropout_seconds,
+            clipped_percent: (self.output_values > 0)
+                .then(|| self.clipped_values as f64 / self.output_values as f64 * 100.0),
+            input_saturated_percent: (self.input_samples > 0)
+                .then(|| self.saturated_samples as f64 / self.input_samples as f64 * 100.0),
+            signal_seconds: self.activity.map(|a| a.signal_seconds),
+            analyzed_seconds: self.activity.map(|a| a.analyzed_seconds),
+            duty_cycle_percent: self
+                .activity
+                .filter(|a| a.analyzed_seconds > 0.0)
+                .map(|a| a.signal_seconds / a.analyzed_seconds * 100.0),
+        }
+    }
+
+    /// Print a formatted summary block
+    pub fn print_summary(&self) {
+        let summary = self.summary();
+        info!("Summary:");
+        info!("  Slices:          {}", summary.total_slices);
+        info!("  Captured:        {:.2}s", summary.total_captured_seconds);
+        if let (Some(mean), Some(median), Some(min), Some(max)) = (
+            summary.mean_duration_seconds,
+            summary.median_duration_seconds,
+            summary.min_duration_seconds,
+            summary.max_duration_seconds,
+        ) {
+            info!(
+                "  Duration:        mean {:.2}s, median {:.2}s, min {:.2}s, max {:.2}s",
+                mean, median, min, max
+            );
+        }
+        if let (Some(signal), Some(analyzed), Some(duty)) =
+            (summary.signal_seconds, summary.analyzed_seconds, summary.duty_cycle_percent)
+        {
+            info!("  Signal time:     {} of {} ({:.1}% duty)", format_duration(signal), format_duration(analyzed), duty);
+        }
+        if let Some(peak) = summary.mean_peak_power_db {
+            info!("  Mean peak power: {:.1} dB", peak);
+        }
+        if let Some(monitored) = summary.monitored_seconds {
+            info!("  Monitored:       {:.1}s", monitored);
+        }
+        if let Some(dropout) = summary.dropout_seconds {
+            info!("  Dropouts:        ~{:.1}s of samples estimated missing", dropout);
+        }
+        if let Some(saturated) = summary.input_saturated_percent {
+            info!("  Input saturated: {:.3}% of samples", saturated);
+            if saturated > SATURATION_WARN_PERCENT {
+                warn!("Input is saturating; consider reducing the SDR gain");
+            }
+        }
+        if let Some(clipped) = summary.clipped_percent {
+            info!("  Clipped:         {:.3}% of output values", clipped);
+            if clipped > 0.1 {
+                warn!("Output is clipping; consider a lower --output-gain");
+            }
+        }
+    }
+
+    /// Write the summary and per-slice records as JSON
+    pub fn write_report<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let report = Report {
+            summary: self.summary(),
+            slices: &self.slices,
+        };
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, &report)?;
+        Ok(())
+    }
+}
+
+/// One written slice in the run manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub filename: String,
+    /// SHA-256 of the finalized file, hex encoded
+    pub sha256: String,
+    pub samples: usize,
+    pub duration_ms: f32,
+    /// Slice start time (RFC3339)
+    pub timestamp: String,
+}
+
+/// Tamper-evident index of all slices written in a run
+#[derive(Debug, Default, Serialize)]
+pub struct Manifest {
+    slices: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn add(&mut self, entry: ManifestEntry) {
+        self.slices.push(entry);
+    }
+
+    /// Write the manifest via a temporary file renamed into place, so it is never seen half-written
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        write_atomic(path, |path| {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            serde_json::to_writer_pretty(&mut file, self)?;
+            file.flush()?;
+            Ok(())
+        })
+    }
+}
+
+/// SHA-256 of a byte string as lowercase hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SHA-256 of a file's contents as lowercase hex
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String, Box<dyn std::error::Error>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
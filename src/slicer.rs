@@ -1,287 +1,3277 @@
-use std::path::Path;
-use chrono::Local;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use log::{debug, info, warn};
 
-use crate::input::wav::read_iq_wav;
-use crate::input::stream::{IqStreamReader, StreamFormat};
-use crate::input::IqSample;
-use crate::detector::{auto_threshold, detect_segments, add_padding, calculate_peak_power_db, blackman_window};
+use crate::input::wav::{read_iq_wav, NotIq, WavOptions};
+use crate::input::raw::is_gzip;
+use crate::input::stream::{Endian, IqStreamReader, ReadTimeout, StreamFormat};
+use crate::input::{is_iq_file, read_iq_file, IqMetadata, IqSample};
+use crate::dsp::{
+    apply_fade, demodulate, estimate_cfo, estimate_spectrum, estimate_symbol_rate, extract_band, notch_filter,
+    remove_frequency_offset, resample, Channelizer, Demodulation,
+};
+use crate::events::{print_event, Event, EventSink};
+use crate::report::{sha256_file, sha256_hex, Activity, Manifest, ManifestEntry, RunStats, SliceRecord, SliceSidecar};
+use crate::detector::{
+    auto_threshold, detect_segments, detect_segments_cfar, detect_segments_matched, detect_segments_spectrogram, matched_filter_profile,
+    detect_segments_flatness, spectral_flatness_profile, spectral_occupancy,
+    add_padding, covered_samples, merge_segments, resolve_overlaps, split_segments, trim_segment_silence, calculate_power_db_padded,
+    calculate_power_profile, cfar_thresholds, goertzel_power_profile, blackman_window, segment_crest_db, segment_power_std_db, smooth_profile, DetectorMode,
+    NoiseEstimator, OverlapPolicy, PowerMetric, Segment,
+};
 use rustfft::FftPlanner;
-use crate::output::{write_iq_wav, write_iq_wav_float32, generate_filename};
+use crate::output::{
+    write_annotated_wav, write_audacity_labels, write_audio_wav, write_spectrogram_png, Colormap, write_profile_csv, write_occupancy_csv, generate_filename, has_free_space,
+    normalization_gain, planar_paths, resolve_output_path, resolve_path_with,
+    ChannelOrder, NameTemplate, Normalization, OverwritePolicy, RawRecorder, Rotation, SliceName, SliceWriter,
+};
+
+/// Reference time that slice timestamps are offset from
+#[derive(Debug, Clone, Copy)]
+pub enum BaseTime {
+    /// Time processing started
+    Now,
+    /// Recording start derived from the input file's modification time minus its duration
+    FileMtime,
+    /// Explicit timestamp
+    Fixed(DateTime<Local>),
+}
+
+impl BaseTime {
+    /// Resolve to the wall-clock time of the recording's first sample
+    /// A start time recorded in the file itself (e.g. an SDR++ filename) takes precedence over its mtime;
+    /// samples without an input file fall back to the current time
+    fn resolve(
+        &self,
+        input_path: Option<&Path>,
+        metadata: &IqMetadata,
+        duration_seconds: f64,
+    ) -> Result<DateTime<Local>, Box<dyn std::error::Error>> {
+        let recorded_start = metadata.start_time.and_then(|time| time.and_local_timezone(Local).earliest());
+        Ok(match (self, recorded_start, input_path) {
+            (BaseTime::Now, _, _) => Local::now(),
+            (BaseTime::FileMtime, Some(start), _) => start,
+            (BaseTime::FileMtime, None, Some(input_path)) => {
+                // mtime marks when the recorder finished writing, i.e. the end of the recording
+                let modified: DateTime<Local> = std::fs::metadata(input_path)?.modified()?.into();
+                modified - chrono::Duration::milliseconds((duration_seconds * 1000.0) as i64)
+            }
+            (BaseTime::FileMtime, None, None) => Local::now(),
+            (BaseTime::Fixed(time), _, _) => *time,
+        })
+    }
+}
+
+/// Detection and slicing parameters shared by file and stream modes
+#[derive(Debug, Clone)]
+pub struct SliceConfig {
+    pub min_duration_ms: u32,
+    pub max_duration_ms: Option<u32>,
+    /// What happens to segments longer than `max_duration_ms` (file mode only)
+    pub max_duration_action: MaxDurationAction,
+    /// Longest pause bridged while detecting, before short fragments are dropped
+    pub gap_ms: u32,
+    /// Longest pause between kept segments for them to be joined into one slice (file mode)
+    pub merge_gap_ms: u32,
+    pub padding_ms: u32,
+    /// What happens where padding makes neighbouring segments overlap (file mode only); applied after
+    /// `max_duration_action`, so merged slices may exceed `max_duration_ms`
+    pub overlap_policy: OverlapPolicy,
+    /// Trim leading/trailing frames below the release threshold before padding
+    pub trim_tail: bool,
+    /// Write detection events and the run summary to stdout as newline-delimited JSON
+    pub ndjson: bool,
+    pub slice_writer: SliceWriter,
+    /// Write each slice as separate mono I and Q WAVs (`<name>_i.wav`, `<name>_q.wav`)
+    pub planar: bool,
+    /// Linear gain applied to slice samples before writing
+    pub output_gain: f32,
+    /// Tuned center frequency in Hz from --center-freq, for absolute frequencies in names and reports
+    /// (file mode falls back to the input's own metadata)
+    pub center_frequency: Option<f64>,
+    /// Raised-cosine fade at each slice edge in milliseconds (0 disables)
+    pub fade_ms: f32,
+    /// Left/right channel assignment of WAV slices
+    pub channel_order: ChannelOrder,
+    /// Scale each slice to `normalize_target_db` dBFS by this measure before writing
+    pub normalize: Option<Normalization>,
+    pub normalize_target_db: f32,
+    pub name_template: NameTemplate,
+    /// Index of the first slice written (numbering continues from here)
+    pub first_index: usize,
+    /// Handling of existing files with the same slice name
+    pub overwrite: OverwritePolicy,
+    /// JSON report with run summary and per-slice details
+    pub report_path: Option<PathBuf>,
+    /// JSON manifest of written slices with SHA-256 checksums
+    pub manifest_path: Option<PathBuf>,
+    /// Write a .json sidecar with each slice's source sample range and measurements
+    pub sidecar: bool,
+    /// Stop after this many slices have been written
+    pub max_slices: Option<usize>,
+    /// Demodulate each slice to an audio WAV written next to it
+    pub demod: Option<Demodulation>,
+    /// Target sample rate for demodulated audio
+    pub audio_rate: u32,
+    /// Occupied bandwidth is measured this many dB below the spectral peak
+    pub bandwidth_drop_db: f32,
+    /// Stop writing when free space on the output volume drops below this (0 disables)
+    pub min_free_bytes: u64,
+    /// Reduction of each frame's spectrum to the power value detection runs on
+    pub power_metric: PowerMetric,
+    /// Frequencies (Hz from the tuned center) whose FFT bins are ignored by the detection power
+    pub notch_hz: Vec<f64>,
+    /// FFT bins fewer than this from DC are ignored by the detection power (1 = the DC bin only)
+    pub dc_guard_bins: usize,
+    /// Also remove the notched frequencies from written slices with a band-stop filter
+    pub notch_output: bool,
+    /// Remove each slice's own carrier frequency offset, estimated from its mean phase advance
+    pub correct_cfo: bool,
+    /// Detection algorithm (file mode only)
+    pub detector: DetectorMode,
+    /// Reference waveform for matched-filter detection (file mode only)
+    pub template_path: Option<PathBuf>,
+    /// Noise floor estimation method for the auto threshold (file mode only)
+    pub noise_estimator: NoiseEstimator,
+    /// Minimum peak power above noise floor in dB (file mode only)
+    pub min_snr_db: Option<f32>,
+    /// Consecutive frames required to start or end a detection (file mode only)
+    pub debounce: usize,
+    /// Moving-average length in frames applied to the power profile before detection (file mode only)
+    pub smooth_frames: usize,
+    /// Drop segments whose power barely varies (constant carriers)
+    pub skip_carriers: bool,
+    /// Power profile standard deviation (dB) below which a segment counts as a carrier
+    pub carrier_std_db: f32,
+    /// Audacity label track covering all detected segments (file mode only)
+    pub labels_path: Option<PathBuf>,
+    /// Spectrogram PNG of the whole recording with detections boxed (file mode only)
+    pub spectrogram_path: Option<PathBuf>,
+    /// Copy of the recording with cue markers at each detected segment (file mode only)
+    pub annotate_path: Option<PathBuf>,
+    pub colormap: Colormap,
+    /// Only process the file from this offset in seconds (file mode only)
+    pub start_seconds: Option<f64>,
+    /// Only process the file up to this offset in seconds (file mode only)
+    pub end_seconds: Option<f64>,
+    /// Reference time for slice timestamps (file mode only; streams always use now)
+    pub base_time: BaseTime,
+    /// Per-bin spectral occupancy CSV (file mode only)
+    pub occupancy_path: Option<PathBuf>,
+    /// FFT size (frequency resolution) for the occupancy report
+    pub occupancy_fft_size: usize,
+    /// All slices back to back in one file, with a label file next to it (file mode only)
+    pub concat_path: Option<PathBuf>,
+    /// Silence inserted between slices in the concatenation, in milliseconds
+    pub concat_gap_ms: u32,
+    /// dB above each bin's noise floor for a cell to count as occupied
+    pub occupancy_margin_db: f32,
+    /// Write the power profile and threshold as CSV (file mode only)
+    pub profile_path: Option<PathBuf>,
+    /// Detect and report without writing slices (file mode only)
+    pub dry_run: bool,
+}
+
+impl Default for SliceConfig {
+    /// The command-line defaults, with the free-space check off and slices timestamped from now
+    fn default() -> Self {
+        Self {
+            min_duration_ms: 500,
+            max_duration_ms: None,
+            max_duration_action: MaxDurationAction::Drop,
+            gap_ms: 200,
+            merge_gap_ms: 200,
+            padding_ms: 100,
+            overlap_policy: OverlapPolicy::Allow,
+            trim_tail: false,
+            ndjson: false,
+            slice_writer: SliceWriter::WavInt16,
+            planar: false,
+            output_gain: 1.0,
+            center_frequency: None,
+            fade_ms: 0.0,
+            channel_order: ChannelOrder::Iq,
+            normalize: None,
+            normalize_target_db: -3.0,
+            name_template: NameTemplate::default(),
+            first_index: 1,
+            overwrite: OverwritePolicy::Error,
+            report_path: None,
+            manifest_path: None,
+            sidecar: false,
+            max_slices: None,
+            demod: None,
+            audio_rate: 16000,
+            bandwidth_drop_db: 20.0,
+            min_free_bytes: 0,
+            power_metric: PowerMetric::Peak,
+            notch_hz: Vec::new(),
+            dc_guard_bins: 1,
+            notch_output: false,
+            correct_cfo: false,
+            detector: DetectorMode::Threshold,
+            template_path: None,
+            noise_estimator: NoiseEstimator::Percentile,
+            min_snr_db: None,
+            debounce: 1,
+            smooth_frames: 1,
+            skip_carriers: false,
+            carrier_std_db: 0.5,
+            labels_path: None,
+            spectrogram_path: None,
+            annotate_path: None,
+            colormap: Colormap::Viridis,
+            start_seconds: None,
+            end_seconds: None,
+            base_time: BaseTime::Now,
+            occupancy_path: None,
+            occupancy_fft_size: 1024,
+            concat_path: None,
+            concat_gap_ms: 500,
+            occupancy_margin_db: 10.0,
+            profile_path: None,
+            dry_run: false,
+        }
+    }
+}
+
+/// Auto thresholds closer than this to the noise floor trigger a warning (dB)
+const MIN_THRESHOLD_SPAN_DB: f32 = 3.0;
+
+/// A single detection covering more than this fraction of the input triggers a warning
+const WHOLE_FILE_COVERAGE: f64 = 0.9;
+
+/// Smallest stream chunk (or file detection window) that still gives a meaningful FFT peak power
+pub const MIN_CHUNK_SAMPLES: usize = 16;
+
+/// Check that `--dc-guard` leaves bins to measure in an `fft_size`-point detection FFT
+pub fn check_dc_guard(dc_guard_bins: usize, fft_size: usize) -> Result<(), String> {
+    if dc_guard_bins >= fft_size / 2 {
+        return Err(format!(
+            "--dc-guard {} leaves no bins of the {}-point detection FFT (must be below {})",
+            dc_guard_bins,
+            fft_size,
+            fft_size / 2
+        ));
+    }
+    Ok(())
+}
+
+/// Stream-specific input and detection parameters
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    pub sample_rate: u32,
+    /// Threshold margin above the running noise floor (or percentile level) in dB
+    pub threshold_margin: f32,
+    /// What the margin is added to
+    pub threshold_mode: StreamThreshold,
+    /// Sample format, or None to guess it from the start of the stream
+    pub format: Option<StreamFormat>,
+    /// Linear amplitude factor applied to decoded samples
+    pub input_gain: f32,
+    /// Negate Q of decoded samples to undo spectral inversion
+    pub conjugate: bool,
+    /// Byte order of multi-byte samples
+    pub endian: Endian,
+    /// Detection chunk length in milliseconds
+    pub chunk_ms: f32,
+    /// Noise floor adaptation rate per chunk (0-1, higher tracks faster)
+    pub noise_alpha: f32,
+    /// Seconds at the start of the stream used only to estimate the noise floor (0 = a short fixed buffer)
+    pub warmup_seconds: f64,
+    /// Longest single transmission kept in memory before it is flushed as a slice
+    pub max_tx_seconds: f64,
+    /// Send newline-delimited JSON detection events to this HOST:PORT
+    pub event_sink: Option<String>,
+    /// Stop cleanly after this much wall-clock time
+    pub time_limit: Option<Duration>,
+    /// Socket read timeout; on expiry the shutdown flag is checked and the read retried
+    pub read_timeout: Option<Duration>,
+    /// Route slices into YYYY-MM-DD subdirectories by slice start time
+    pub date_folders: bool,
+    /// Continuous WAV recording of every received sample alongside the slices
+    pub record_raw: Option<PathBuf>,
+    /// When the continuous recording rolls over into a new file
+    pub record_rotation: Rotation,
+    /// Split the stream into fixed channels, each with its own detector
+    pub channels: Option<ChannelPlan>,
+    /// Keep reconnecting after the TCP stream closes or fails, carrying detector state and numbering over
+    pub reconnect: bool,
+}
+
+impl Default for StreamConfig {
+    /// The command-line defaults: float32 little-endian samples at 48 kHz, 10 ms chunks, +15 dB margin
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            threshold_margin: 15.0,
+            threshold_mode: StreamThreshold::Margin,
+            format: Some(StreamFormat::Float32),
+            input_gain: 1.0,
+            conjugate: false,
+            endian: Endian::Little,
+            chunk_ms: 10.0,
+            noise_alpha: 0.005,
+            warmup_seconds: 0.0,
+            max_tx_seconds: 120.0,
+            event_sink: None,
+            time_limit: None,
+            read_timeout: Some(Duration::from_millis(1000)),
+            date_folders: false,
+            record_raw: None,
+            record_rotation: Rotation::default(),
+            channels: None,
+            reconnect: false,
+        }
+    }
+}
+
+/// Handling of segments longer than --max-duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxDurationAction {
+    /// Discard them
+    Drop,
+    /// Write them with a `_long` filename suffix
+    Keep,
+    /// Write them as consecutive slices of at most the maximum duration
+    Split,
+}
+
+/// How the stream detector sets its detection threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamThreshold {
+    /// Running (EMA) noise floor plus the margin
+    Margin,
+    /// A percentile of recent quiet chunk powers plus the margin, for bands whose noise is itself bursty
+    Percentile { percentile: f32, window_seconds: f64 },
+}
+
+/// Equally spaced channels centered on the tuned frequency
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelPlan {
+    pub count: usize,
+    pub bandwidth_hz: u32,
+}
 
 /// Process an IQ WAV file and output sliced IQ segments
-#[allow(clippy::too_many_arguments)]
 pub fn process_file(
     input_path: &Path,
     output_dir: &Path,
-    min_duration_ms: u32,
-    max_duration_ms: Option<u32>,
-    gap_ms: u32,
-    padding_ms: u32,
-    verbose: bool,
-    float32_output: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
+    config: &SliceConfig,
+    wav_options: &WavOptions,
+) -> Result<RunStats, Box<dyn std::error::Error>> {
+    process_file_with(input_path, output_dir, config, wav_options, |_| {})
+}
+
+/// Like `process_file`, calling `on_slice` for each slice once it has been extracted and written
+/// (or only extracted, on a dry run)
+pub fn process_file_with(
+    input_path: &Path,
+    output_dir: &Path,
+    config: &SliceConfig,
+    wav_options: &WavOptions,
+    on_slice: impl FnMut(&SlicedSegment),
+) -> Result<RunStats, Box<dyn std::error::Error>> {
     // Read IQ file
-    if verbose {
-        println!("Reading IQ file...");
+    debug!("Reading IQ file...");
+    // A zero-byte file has no header to parse; treat it like a header-only WAV
+    // (pipes and FIFOs always report zero bytes, so they are read regardless)
+    let input_metadata = std::fs::metadata(input_path)?;
+    if input_metadata.is_file() && input_metadata.len() == 0 {
+        info!("No samples in file");
+        return Ok(RunStats::default());
     }
-    let (samples, metadata) = read_iq_wav(input_path)?;
+    let (all_samples, metadata) = read_iq_file(input_path, wav_options)?;
 
-    if verbose {
-        println!(
-            "Loaded {} samples at {} Hz ({:.2}s)",
-            samples.len(),
-            metadata.sample_rate,
-            samples.len() as f32 / metadata.sample_rate as f32
-        );
-    }
+    // Pin the timestamp base while the file is at hand, e.g. for its mtime
+    let duration_seconds = all_samples.len() as f64 / metadata.sample_rate as f64;
+    let config = SliceConfig {
+        base_time: BaseTime::Fixed(config.base_time.resolve(Some(input_path), &metadata, duration_seconds)?),
+        ..config.clone()
+    };
+    process_samples(&all_samples, metadata, output_dir, &config, on_slice)
+}
 
-    // Calculate detection parameters in samples
-    let window_size = (metadata.sample_rate as usize / 1000).max(1); // 1ms windows for better burst detection
-    let min_duration_samples = (min_duration_ms as f32 / 1000.0 * metadata.sample_rate as f32) as usize;
-    let gap_samples = (gap_ms as f32 / 1000.0 * metadata.sample_rate as f32) as usize;
-    let padding_samples = (padding_ms as f32 / 1000.0 * metadata.sample_rate as f32) as usize;
+/// Run detection and slicing on samples already in memory, as `process_file_with` does after reading
+/// its input. `--base-time file-mtime` uses the recorded start time, if any, or else the current time.
+pub fn process_samples(
+    all_samples: &[IqSample],
+    mut metadata: IqMetadata,
+    output_dir: &Path,
+    config: &SliceConfig,
+    mut on_slice: impl FnMut(&SlicedSegment),
+) -> Result<RunStats, Box<dyn std::error::Error>> {
+    let SliceConfig {
+        slice_writer,
+        base_time,
+        dry_run,
+        ..
+    } = *config;
 
-    // Auto-detect threshold
-    let analysis = auto_threshold(&samples, window_size);
-    if verbose {
-        println!(
-            "Auto-detected: noise_floor={:.1} dB, p95={:.1} dB, threshold={:.1} dB",
-            analysis.noise_floor, analysis.p95, analysis.threshold
-        );
+    if config.center_frequency.is_some() {
+        metadata.center_frequency = config.center_frequency;
+    }
+    if all_samples.is_empty() {
+        info!("No samples in file");
+        return Ok(RunStats::default());
     }
-    let threshold = analysis.threshold;
 
-    // Detect segments
-    if verbose {
-        println!("Detecting transmissions...");
-    }
-    let segments = detect_segments(
-        &samples,
-        window_size,
-        threshold,
-        min_duration_samples,
-        gap_samples,
+    debug!(
+        "Loaded {} samples at {} Hz ({:.2}s)",
+        all_samples.len(),
+        metadata.sample_rate,
+        all_samples.len() as f32 / metadata.sample_rate as f32
+    );
+    debug!(
+        "Input saturation: {:.3}% of samples at full scale",
+        metadata.saturated_samples as f64 / all_samples.len().max(1) as f64 * 100.0
     );
 
-    // Add padding
-    let segments = add_padding(segments, padding_samples, samples.len());
+    let (mut slices, activity) = slice_to_memory(all_samples, &metadata, config)?;
+    let segments: Vec<Segment> = slices.iter().map(|slice| slice.segment.clone()).collect();
+    let labels = slice_labels(config, segments.len());
 
-    // Filter by max duration if specified
-    let segments: Vec<_> = if let Some(max_ms) = max_duration_ms {
-        let max_samples = (max_ms as f32 / 1000.0 * metadata.sample_rate as f32) as usize;
-        segments
-            .into_iter()
-            .filter(|s| s.duration_samples() <= max_samples)
-            .collect()
-    } else {
-        segments
-    };
+    if let Some(labels_path) = &config.labels_path {
+        write_audacity_labels(labels_path, &segments, metadata.sample_rate, &labels)?;
+        debug!("Wrote labels: {}", labels_path.display());
+    }
 
-    if verbose {
-        println!("Found {} transmission(s)", segments.len());
+    if let Some(spectrogram_path) = &config.spectrogram_path {
+        write_spectrogram_png(spectrogram_path, all_samples, metadata.sample_rate, &segments, config.colormap)?;
+        debug!("Wrote spectrogram: {}", spectrogram_path.display());
     }
 
-    if segments.is_empty() {
-        println!("No transmissions detected");
-        return Ok(());
+    if let Some(annotate_path) = &config.annotate_path {
+        let annotate_path = resolve_output_path(annotate_path, config.overwrite)?;
+        write_annotated_wav(
+            &annotate_path,
+            all_samples,
+            metadata.sample_rate,
+            &segments,
+            &labels,
+            config.slice_writer,
+            config.channel_order,
+        )?;
+        debug!("Wrote annotated copy: {}", annotate_path.display());
     }
 
-    // Process each segment
-    let base_time = Local::now();
-    for (i, segment) in segments.iter().enumerate() {
-        if verbose {
-            println!(
-                "  Slice {}: {:.2}s - {:.2}s ({:.2}s duration)",
-                i + 1,
+    if let Some(occupancy_path) = &config.occupancy_path {
+        match spectral_occupancy(all_samples, config.occupancy_fft_size, config.occupancy_margin_db) {
+            Some(occupancy) => {
+                write_occupancy_csv(
+                    occupancy_path,
+                    &occupancy.bin_fractions,
+                    metadata.sample_rate,
+                    metadata.center_frequency.unwrap_or(0.0),
+                )?;
+                info!("Band duty cycle: {:.1}%", occupancy.duty_cycle * 100.0);
+                debug!("Wrote occupancy: {}", occupancy_path.display());
+            }
+            None => warn!("Recording too short for --occupancy with FFT size {}", config.occupancy_fft_size),
+        }
+    }
+
+    let mut stats = RunStats::default();
+    stats.record_input(all_samples.len(), metadata.saturated_samples);
+    stats.record_activity(activity);
+
+    if slices.is_empty() {
+        info!("No transmissions detected");
+        finish_run(&stats, config)?;
+        return Ok(stats);
+    }
+
+    if let Some(max_slices) = config.max_slices {
+        if slices.len() > max_slices {
+            info!(
+                "Slice limit reached: writing {} of {} detected transmission(s)",
+                max_slices,
+                slices.len()
+            );
+            slices.truncate(max_slices);
+        }
+    }
+
+    let base_time = base_time.resolve(None, &metadata, all_samples.len() as f64 / metadata.sample_rate as f64)?;
+    debug!("Timestamp base: {}", base_time.format("%Y-%m-%d %H:%M:%S"));
+
+    // Time-frequency slices are named by their band's center frequency
+    let name_template = match config.detector {
+        DetectorMode::Spectrogram { .. } => config.name_template.with_freq(),
+        _ => config.name_template.clone(),
+    };
+    // Over-long slices kept by --max-duration-action are tagged
+    let slice_filename = |i: usize, slice: &SlicedSegment| {
+        let segment = &slice.segment;
+        let long = config.max_duration_action == MaxDurationAction::Keep
+            && config
+                .max_duration_ms
+                .is_some_and(|ms| segment.duration_samples() > max_duration_samples(ms, metadata.sample_rate));
+        let extension = format!("{}{}", if long { "_long" } else { "" }, slice_writer.extension());
+        generate_filename(
+            &name_template,
+            &SliceName {
+                index: config.first_index + i,
+                start_sample: segment.start_sample,
+                sample_rate: metadata.sample_rate,
+                base_time,
+                peak_power_db: Some(slice.peak_power_db),
+                crest_db: slice.segment.crest_db,
+                frequency_hz: segment
+                    .band
+                    .map(|band| band.center_hz)
+                    .or(slice.located_center_hz(metadata.sample_rate))
+                    .map(|offset| offset + metadata.center_frequency.unwrap_or(0.0)),
+            },
+            &extension,
+        )
+    };
+
+    if dry_run {
+        for (i, slice) in slices.iter().enumerate() {
+            let segment = &slice.segment;
+            info!(
+                "  Slice {}: {:.2}s - {:.2}s ({:.2}s duration, peak {:.1} dB)",
+                config.first_index + i,
                 segment.start_sample as f32 / metadata.sample_rate as f32,
                 segment.end_sample as f32 / metadata.sample_rate as f32,
-                segment.duration_ms(metadata.sample_rate) / 1000.0
+                segment.duration_ms(metadata.sample_rate) / 1000.0,
+                segment.peak_power_db
             );
+            stats.record(slice_record(slice, &metadata, slice_filename(i, slice)));
+            on_slice(slice);
         }
+        info!("Dry run: {} slice(s) not written", slices.len());
+        finish_run(&stats, config)?;
+        return Ok(stats);
+    }
 
-        // Extract segment samples
-        let segment_samples = &samples[segment.start_sample..segment.end_sample];
+    // Resolved before any slice is written, so a refused overwrite doesn't leave a partial run behind
+    let concat_path = config.concat_path.as_deref().map(|path| resolve_concat_path(path, config.overwrite)).transpose()?;
 
-        // Generate output filename and write
-        let filename = generate_filename(i + 1, segment.start_sample, metadata.sample_rate, base_time);
-        let output_path = output_dir.join(&filename);
+    // Process each segment
+    let mut manifest = Manifest::default();
+    let mut events = EventSink::new(None, config.ndjson);
 
-        if float32_output {
-            write_iq_wav_float32(&output_path, segment_samples, metadata.sample_rate)?;
-        } else {
-            write_iq_wav(&output_path, segment_samples, metadata.sample_rate)?;
+    for (i, slice) in slices.iter().enumerate() {
+        let segment = &slice.segment;
+        debug!(
+            "  Slice {}: {:.2}s - {:.2}s ({:.2}s duration)",
+            config.first_index + i,
+            segment.start_sample as f32 / metadata.sample_rate as f32,
+            segment.end_sample as f32 / metadata.sample_rate as f32,
+            segment.duration_ms(metadata.sample_rate) / 1000.0
+        );
+
+        if !has_free_space(output_dir, config.min_free_bytes)? {
+            warn!("{}", low_space_message(output_dir, config.min_free_bytes));
+            break;
         }
 
-        if verbose {
-            println!("    Wrote: {}", filename);
+        let center_hz = segment.band.map_or(0.0, |band| band.center_hz);
+        let segment_samples = &*output_samples(config, slice, metadata.sample_rate, center_hz);
+
+        let filename = slice_filename(i, slice);
+        let output_path = resolve_slice_path(config, &output_dir.join(&filename), &HashSet::new())?;
+
+        let normalize_gain = slice_normalize_gain(config, segment_samples);
+        let clipped = write_slice_file(
+            config,
+            &output_path,
+            segment_samples,
+            metadata.sample_rate,
+            config.output_gain * normalize_gain.unwrap_or(1.0),
+        )?;
+        stats.record_clipping(segment_samples.len(), clipped);
+        write_demod_audio(config, &output_path, segment_samples, metadata.sample_rate)?;
+
+        debug!("    Wrote: {}", filename);
+        let start_seconds = segment.start_sample as f64 / metadata.sample_rate as f64;
+        let start_time = base_time + chrono::Duration::milliseconds((start_seconds * 1000.0) as i64);
+        let files = slice_files(config, &output_path, &filename);
+        if config.manifest_path.is_some() {
+            for (path, name) in &files {
+                manifest.add(ManifestEntry {
+                    filename: name.clone(),
+                    sha256: sha256_file(path)?,
+                    samples: segment_samples.len(),
+                    duration_ms: segment.duration_ms(metadata.sample_rate),
+                    timestamp: start_time.to_rfc3339(),
+                });
+            }
+        }
+        // File-mode events carry recording time rather than processing time
+        if let Some(sink) = events.as_mut() {
+            let duration_ms = segment.duration_ms(metadata.sample_rate);
+            sink.send(&Event::Start {
+                time: start_time.to_rfc3339(),
+                peak_db: slice.peak_power_db,
+                channel_hz: None,
+            });
+            sink.send(&Event::End {
+                time: (start_time + chrono::Duration::milliseconds(duration_ms as i64)).to_rfc3339(),
+                filename: Some(&filename),
+                duration_ms,
+                peak_db: slice.peak_power_db,
+                channel_hz: None,
+            });
+        }
+        let record = SliceRecord {
+            planar_filenames: config.planar.then(|| files.into_iter().map(|(_, name)| name).collect()),
+            normalize_gain_db: normalize_gain.map(|gain| 20.0 * gain.log10()),
+            ..slice_record(slice, &metadata, filename)
+        };
+        if config.sidecar {
+            SliceSidecar {
+                sample_rate: metadata.sample_rate,
+                start_time: start_time.to_rfc3339(),
+                record: &record,
+            }
+            .write(&output_path)?;
         }
+        stats.record(record);
+        on_slice(slice);
     }
 
-    println!(
+    info!(
         "Saved {} slice(s) to {}",
-        segments.len(),
+        stats.slice_count(),
         output_dir.display()
     );
+    if let Some(concat_path) = &concat_path {
+        write_concat(concat_path, &slices[..stats.slice_count()], metadata.sample_rate, config)?;
+    }
+    if let Some(manifest_path) = &config.manifest_path {
+        manifest.write(manifest_path)?;
+        debug!("Wrote manifest: {}", manifest_path.display());
+    }
+    finish_run(&stats, config)?;
+    Ok(stats)
+}
+
+/// Report entry for a file-mode slice written (or, on a dry run, to be written) as `filename`
+fn slice_record(slice: &SlicedSegment, metadata: &IqMetadata, filename: String) -> SliceRecord {
+    let segment = &slice.segment;
+    SliceRecord {
+        filename,
+        planar_filenames: None,
+        start_seconds: Some(segment.start_sample as f64 / metadata.sample_rate as f64),
+        start_sample: segment.start_sample,
+        end_sample: segment.end_sample,
+        duration_ms: segment.duration_ms(metadata.sample_rate),
+        peak_power_db: slice.peak_power_db,
+        crest_db: segment.crest_db,
+        center_hz: slice.center_hz,
+        frequency_hz: metadata.center_frequency.zip(slice.center_hz).map(|(tuned, offset)| tuned + offset),
+        bandwidth_hz: slice.bandwidth_hz,
+        symbol_rate_hz: slice.symbol_rate_hz,
+        cfo_hz: slice.cfo_hz,
+        normalize_gain_db: None,
+    }
+}
 
+/// One input of a batch run and the directory its slices go to
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    pub input: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+/// Outcome of a batch run
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    /// Combined stats of every file that was processed
+    pub stats: RunStats,
+    pub processed: usize,
+    /// Inputs skipped as not IQ or already sliced
+    pub skipped: usize,
+    pub failed: Vec<PathBuf>,
+}
+
+/// Expand the input arguments into batch jobs, each writing to its own subdirectory of `output_dir`
+/// Files get a subdirectory named after their stem. With `recursive`, directories are walked for
+/// recordings and their tree is mirrored under `output_dir`; otherwise a directory is an error.
+pub fn batch_jobs(inputs: &[PathBuf], output_dir: &Path, recursive: bool) -> Result<Vec<BatchJob>, Box<dyn std::error::Error>> {
+    let mut jobs = Vec::new();
+    let mut seen = std::collections::HashMap::new();
+    let mut add = |input: PathBuf, relative: &Path| {
+        let relative = if is_gzip(relative) { relative.with_extension("") } else { relative.to_path_buf() };
+        let relative = relative.with_extension("");
+        let count = seen.entry(relative.clone()).or_insert(0);
+        *count += 1;
+        let output_dir = match *count {
+            1 => output_dir.join(&relative),
+            n => output_dir.join(format!("{}_{}", relative.display(), n)),
+        };
+        jobs.push(BatchJob { input, output_dir });
+    };
+
+    for input in inputs {
+        if !input.is_dir() {
+            let name = input.file_name().map_or_else(|| PathBuf::from("input"), PathBuf::from);
+            add(input.clone(), &name);
+        } else if recursive {
+            let mut found = Vec::new();
+            find_iq_files(input, &mut found)?;
+            found.sort();
+            debug!("Found {} recording(s) under {}", found.len(), input.display());
+            for file in found {
+                let relative = file.strip_prefix(input).unwrap_or(&file).to_path_buf();
+                add(file, &relative);
+            }
+        } else {
+            return Err(format!("{} is a directory (use --recursive to process the files in it)", input.display()).into());
+        }
+    }
+    Ok(jobs)
+}
+
+/// Collect every recording (WAV or raw IQ) below `dir`, not following symlinked directories
+fn find_iq_files(dir: &Path, found: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_iq_files(&path, found)?;
+        } else if path.is_file() && is_iq_file(&path) {
+            found.push(path);
+        }
+    }
     Ok(())
 }
 
-/// Process live IQ stream and output sliced IQ segments
-#[allow(clippy::too_many_arguments)]
-pub fn process_stream(
-    addr: &str,
+/// Rewrite every I/Q WAV below `input_dir` into the same place under `output_dir` with `writer`,
+/// keeping names apart from the extension. Other WAVs (e.g. demodulated audio) are skipped.
+/// Sample values are kept as they are, so converting to the same format reproduces them exactly.
+/// Returns how many files were converted.
+pub fn convert_slices(
+    input_dir: &Path,
     output_dir: &Path,
-    min_duration_ms: u32,
-    gap_ms: u32,
-    padding_ms: u32,
-    threshold_margin: f32,
-    sample_rate: u32,
-    format: StreamFormat,
-    verbose: bool,
-    float32_output: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut reader = IqStreamReader::connect(addr, format)?;
-
-    if verbose {
-        println!("Connected to stream at {}", addr);
-        println!("Sample rate: {} Hz", sample_rate);
-        println!("Using FFT peak detection for wideband monitoring");
-        println!("Threshold margin: +{:.0} dB above noise floor", threshold_margin);
+    writer: SliceWriter,
+    order: ChannelOrder,
+    overwrite: OverwritePolicy,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if !input_dir.is_dir() {
+        return Err(format!("{} is not a directory", input_dir.display()).into());
     }
+    let mut found = Vec::new();
+    find_iq_files(input_dir, &mut found)?;
+    found.retain(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav")));
+    found.sort();
 
-    // Streaming state machine
-    let chunk_size = sample_rate as usize / 100; // 10ms chunks
-    let min_duration_samples = (min_duration_ms as f32 / 1000.0 * sample_rate as f32) as usize;
-    let gap_samples = (gap_ms as f32 / 1000.0 * sample_rate as f32) as usize;
-    let padding_samples = (padding_ms as f32 / 1000.0 * sample_rate as f32) as usize;
+    let mut converted = 0;
+    for file in found {
+        let (samples, metadata) = match read_iq_wav(&file, &WavOptions::default()) {
+            Ok(read) => read,
+            Err(e) if e.is::<NotIq>() => {
+                debug!("Skipping {}: {}", file.display(), e);
+                continue;
+            }
+            Err(e) => return Err(format!("{}: {}", file.display(), e).into()),
+        };
+        let relative = file.strip_prefix(input_dir).unwrap_or(&file);
+        let output_path = output_dir.join(relative).with_extension(writer.extension().trim_start_matches('.'));
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let output_path = resolve_output_path(&output_path, overwrite)?;
+        writer.write(&output_path, &samples, metadata.sample_rate, writer.unity_gain(), order)?;
+        debug!("Converted {} -> {}", file.display(), output_path.display());
+        converted += 1;
+    }
+    Ok(converted)
+}
 
-    // Ring buffer for padding (stores recent samples before transmission)
-    let mut pre_buffer: Vec<IqSample> = Vec::with_capacity(padding_samples);
+/// Marker written to a batch output subdirectory once its input has been processed
+/// Holds a hash of the slicing parameters, so changing them makes `--skip-existing` process the input again
+const DONE_MARKER: &str = ".done";
 
-    // Buffer for current transmission
-    let mut tx_buffer: Vec<IqSample> = Vec::new();
+/// Hash of the parameters that decide which slices an input yields and what they contain, for `.done` markers
+/// Fields are listed explicitly so report paths, logging options and type layouts don't change the hash
+fn parameter_hash(config: &SliceConfig, wav_options: &WavOptions) -> String {
+    fn optional<T: std::fmt::Display>(value: Option<T>) -> String {
+        value.map_or_else(|| "none".to_string(), |value| value.to_string())
+    }
 
-    // FFT planner and Blackman window for peak detection
-    let mut fft_planner = FftPlanner::new();
-    let window = blackman_window(chunk_size);
+    let detector = match config.detector {
+        DetectorMode::Threshold => "threshold".to_string(),
+        DetectorMode::Cfar { guard, train, pfa } => format!("cfar guard={} train={} pfa={}", guard, train, pfa),
+        DetectorMode::Matched { threshold } => format!("matched threshold={}", threshold),
+        DetectorMode::Spectrogram { margin_db } => format!("spectrogram margin={}", margin_db),
+        DetectorMode::Flatness { max_flatness } => format!("flatness max={}", max_flatness),
+        DetectorMode::Goertzel { target_hz } => format!("goertzel target={}", target_hz),
+    };
+    let base_time = match config.base_time {
+        BaseTime::Now => "now".to_string(),
+        BaseTime::FileMtime => "file-mtime".to_string(),
+        BaseTime::Fixed(time) => time.to_rfc3339(),
+    };
+    let power_metric = match config.power_metric {
+        PowerMetric::Peak => "peak",
+        PowerMetric::Total => "total",
+    };
+    let noise_estimator = match config.noise_estimator {
+        NoiseEstimator::Percentile => "percentile",
+        NoiseEstimator::Mode => "mode",
+    };
+    let max_duration_action = match config.max_duration_action {
+        MaxDurationAction::Drop => "drop",
+        MaxDurationAction::Keep => "keep",
+        MaxDurationAction::Split => "split",
+    };
+    let overlap_policy = match config.overlap_policy {
+        OverlapPolicy::Allow => "allow",
+        OverlapPolicy::Merge => "merge",
+        OverlapPolicy::Trim => "trim",
+    };
+    let output_format = match config.slice_writer {
+        SliceWriter::WavInt8 => "int8",
+        SliceWriter::WavInt16 => "int16",
+        SliceWriter::WavInt32 => "int32",
+        SliceWriter::WavFloat32 => "float32",
+        SliceWriter::Cf32 => "cf32",
+    };
+    let channel_order = match config.channel_order {
+        ChannelOrder::Iq => "iq",
+        ChannelOrder::Qi => "qi",
+    };
+    let normalize = config.normalize.map(|normalize| match normalize {
+        Normalization::Peak => "peak",
+        Normalization::Rms => "rms",
+    });
+    let demod = config.demod.map(|demod| match demod {
+        Demodulation::Fm => "fm",
+        Demodulation::Am => "am",
+    });
+    let endian = match wav_options.endian {
+        Endian::Little => "little",
+        Endian::Big => "big",
+    };
+    let notch_hz: Vec<String> = config.notch_hz.iter().map(|hz| hz.to_string()).collect();
 
-    // Noise floor estimation (running average of FFT peak power)
-    let mut noise_floor_db: f32 = -60.0;
-    let noise_alpha = 0.005; // Slower adaptation for FFT peak
+    let fields = [
+        ("detector", detector),
+        ("template", optional(config.template_path.as_ref().map(|path| path.display()))),
+        ("power_metric", power_metric.to_string()),
+        ("noise_estimator", noise_estimator.to_string()),
+        ("notch_hz", notch_hz.join(",")),
+        ("dc_guard_bins", config.dc_guard_bins.to_string()),
+        ("min_snr_db", optional(config.min_snr_db)),
+        ("debounce", config.debounce.to_string()),
+        ("smooth_frames", config.smooth_frames.to_string()),
+        ("skip_carriers", config.skip_carriers.to_string()),
+        ("carrier_std_db", config.carrier_std_db.to_string()),
+        ("min_duration_ms", config.min_duration_ms.to_string()),
+        ("max_duration_ms", optional(config.max_duration_ms)),
+        ("max_duration_action", max_duration_action.to_string()),
+        ("gap_ms", config.gap_ms.to_string()),
+        ("merge_gap_ms", config.merge_gap_ms.to_string()),
+        ("padding_ms", config.padding_ms.to_string()),
+        ("overlap_policy", overlap_policy.to_string()),
+        ("trim_tail", config.trim_tail.to_string()),
+        ("start_seconds", optional(config.start_seconds)),
+        ("end_seconds", optional(config.end_seconds)),
+        ("max_slices", optional(config.max_slices)),
+        ("output_format", output_format.to_string()),
+        ("planar", config.planar.to_string()),
+        ("channel_order", channel_order.to_string()),
+        ("output_gain", config.output_gain.to_string()),
+        ("fade_ms", config.fade_ms.to_string()),
+        ("normalize", optional(normalize)),
+        ("normalize_target_db", config.normalize_target_db.to_string()),
+        ("notch_output", config.notch_output.to_string()),
+        ("correct_cfo", config.correct_cfo.to_string()),
+        ("demod", optional(demod)),
+        ("audio_rate", config.audio_rate.to_string()),
+        ("name_template", config.name_template.to_string()),
+        ("first_index", config.first_index.to_string()),
+        ("center_frequency", optional(config.center_frequency)),
+        ("base_time", base_time),
+        ("mono", wav_options.mono.to_string()),
+        ("i_channel", wav_options.i_channel.to_string()),
+        ("q_channel", wav_options.q_channel.to_string()),
+        ("input_gain", wav_options.gain.to_string()),
+        ("conjugate", wav_options.conjugate.to_string()),
+        ("sample_rate", optional(wav_options.sample_rate)),
+        ("endian", endian.to_string()),
+    ];
+    let text: String = fields.iter().map(|(name, value)| format!("{}={}\n", name, value)).collect();
+    sha256_hex(text.as_bytes())
+}
 
-    // State machine
-    let mut in_transmission = false;
-    let mut silence_counter = 0;
-    let mut slice_counter = 0;
+/// Stats of a processed batch file, None if it was skipped, or why it failed
+type FileOutcome = Result<Option<RunStats>, String>;
 
-    println!("Listening for transmissions... (Ctrl+C to stop)");
+/// Process a batch of IQ recordings, up to `workers` at a time
+/// Per-file failures and panics are logged without stopping the other files. Single-channel files
+/// read without `--mono` are skipped with a note. With `skip_existing`, inputs already completed
+/// with the same parameters (per their `.done` marker) are skipped too.
+pub fn process_files(
+    jobs: &[BatchJob],
+    config: &SliceConfig,
+    wav_options: &WavOptions,
+    workers: usize,
+    continue_numbering: bool,
+    skip_existing: bool,
+) -> BatchSummary {
+    let parameters = parameter_hash(config, wav_options);
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, FileOutcome)>> = Mutex::new(Vec::new());
 
-    let mut debug_counter = 0;
+    std::thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+                let marker = job.output_dir.join(DONE_MARKER);
+                match std::fs::read_to_string(&marker) {
+                    Ok(done) if skip_existing && done.trim() == parameters => {
+                        info!("Skipping {}: already sliced into {}", job.input.display(), job.output_dir.display());
+                        results.lock().unwrap_or_else(|e| e.into_inner()).push((index, Ok(None)));
+                        continue;
+                    }
+                    Ok(_) => {
+                        if skip_existing {
+                            info!("{} was sliced with different parameters; processing again", job.input.display());
+                        }
+                        // Only a run that finishes may leave a marker behind
+                        let _ = std::fs::remove_file(&marker);
+                    }
+                    Err(_) => {}
+                }
+                let name = job.input.file_name().map_or_else(|| job.input.display().to_string(), |n| n.to_string_lossy().into_owned());
+                // A named thread per file labels its log lines and contains any panic
+                let result = std::thread::Builder::new()
+                    .name(name)
+                    .spawn_scoped(scope, || match process_batch_file(job, config, wav_options, continue_numbering) {
+                        Ok(stats) => Ok(Some(stats)),
+                        Err(e) if e.is::<NotIq>() => {
+                            info!("Skipping {}: {}", job.input.display(), e);
+                            Ok(None)
+                        }
+                        Err(e) => Err(e.to_string()),
+                    })
+                    .map_err(|e| e.to_string())
+                    .and_then(|handle| handle.join().unwrap_or_else(|panic| Err(panic_message(panic.as_ref()))));
+                if !matches!(result, Ok(Some(_))) {
+                    // Don't leave empty directories behind for inputs that produced nothing
+                    let _ = std::fs::remove_dir(&job.output_dir);
+                } else if !config.dry_run {
+                    if let Err(e) = std::fs::write(&marker, format!("{}\n", parameters)) {
+                        warn!("Could not write {}: {}", marker.display(), e);
+                    }
+                }
+                results.lock().unwrap_or_else(|e| e.into_inner()).push((index, result));
+            });
+        }
+    });
 
-    loop {
-        let chunk = match reader.read_chunk(chunk_size)? {
-            Some(c) => c,
-            None => {
-                println!("Stream closed");
-                break;
+    let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    results.sort_by_key(|(index, _)| *index);
+    let mut summary = BatchSummary::default();
+    for (index, result) in results {
+        match result {
+            Ok(Some(stats)) => {
+                summary.stats.merge(stats);
+                summary.processed += 1;
             }
-        };
+            Ok(None) => summary.skipped += 1,
+            Err(e) => {
+                log::error!("{}: {}", jobs[index].input.display(), e);
+                summary.failed.push(jobs[index].input.clone());
+            }
+        }
+    }
+    summary
+}
 
-        // Use FFT peak power detection with Blackman window for wideband monitoring
-        let power_db = calculate_peak_power_db(&chunk, &window, &mut fft_planner);
+/// Run `process_file` for one batch input, keeping its side outputs (labels, report, ...) in its own directory
+fn process_batch_file(
+    job: &BatchJob,
+    config: &SliceConfig,
+    wav_options: &WavOptions,
+    continue_numbering: bool,
+) -> Result<RunStats, Box<dyn std::error::Error>> {
+    let dir = job.output_dir.as_path();
+    let mut config = config.clone();
+    let relocate = |path: &mut Option<PathBuf>| {
+        if let Some(name) = path.as_ref().and_then(|p| p.file_name()) {
+            *path = Some(dir.join(name));
+        }
+    };
+    relocate(&mut config.report_path);
+    relocate(&mut config.manifest_path);
+    relocate(&mut config.labels_path);
+    relocate(&mut config.spectrogram_path);
+    relocate(&mut config.annotate_path);
+    relocate(&mut config.occupancy_path);
+    relocate(&mut config.profile_path);
+    relocate(&mut config.concat_path);
 
-        // Debug: print power level every ~1 second
-        debug_counter += 1;
-        if verbose && debug_counter % 100 == 0 {
-            let threshold = noise_floor_db + threshold_margin;
-            println!("[debug] peak_power: {:.1} dB, noise_floor: {:.1} dB, threshold: {:.1} dB",
-                     power_db, noise_floor_db, threshold);
+    if !config.dry_run {
+        std::fs::create_dir_all(dir)?;
+    }
+    if continue_numbering && dir.is_dir() {
+        config.first_index = crate::output::highest_slice_index(dir, &config.name_template)? + 1;
+    }
+    debug!("Processing file: {}", job.input.display());
+    process_file(&job.input, dir, &config, wav_options)
+}
+
+/// Text of a caught panic payload
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_string());
+    format!("processing panicked: {}", message)
+}
+
+/// A detected segment with its samples, ready to write or hand to the caller
+#[derive(Debug, Clone)]
+pub struct SlicedSegment {
+    /// Position within the recording, including padding
+    pub segment: Segment,
+    /// Segment samples, shifted to baseband and band-limited when the detector found a band
+    pub samples: Vec<IqSample>,
+    pub peak_power_db: f32,
+    /// Center of the occupied band, offset from the tuned frequency
+    pub center_hz: Option<f64>,
+    /// Occupied bandwidth at the configured level below the peak
+    pub bandwidth_hz: Option<f64>,
+    /// Estimated symbol rate for digital signals
+    pub symbol_rate_hz: Option<f64>,
+    /// Carrier offset to remove before writing (`--correct-cfo`); `samples` still contain it
+    pub cfo_hz: Option<f64>,
+}
+
+impl SlicedSegment {
+    /// `center_hz` when the occupied band is narrower than the sample rate; a band spanning
+    /// everything doesn't locate the signal, so it shouldn't name the slice
+    fn located_center_hz(&self, sample_rate: u32) -> Option<f64> {
+        self.center_hz
+            .filter(|_| self.bandwidth_hz.is_some_and(|bandwidth| bandwidth < sample_rate as f64))
+    }
+}
+
+/// Run the file detection pipeline over in-memory samples and return the slices without writing them
+/// Applies the same time range, detection, filtering and padding as `process_file`
+pub fn slice_to_memory(
+    all_samples: &[IqSample],
+    metadata: &IqMetadata,
+    config: &SliceConfig,
+) -> Result<(Vec<SlicedSegment>, Activity), Box<dyn std::error::Error>> {
+    let (segments, activity) = detect_file_segments(all_samples, metadata.sample_rate, config)?;
+    let slices = segments
+        .into_iter()
+        .map(|segment| extract_slice(all_samples, metadata.sample_rate, segment, config))
+        .collect();
+    Ok((slices, activity))
+}
+
+/// Copy a segment's samples, shifting and band-limiting time-frequency detections, and measure its spectrum
+fn extract_slice(all_samples: &[IqSample], sample_rate: u32, segment: Segment, config: &SliceConfig) -> SlicedSegment {
+    let segment_samples = &all_samples[segment.start_sample..segment.end_sample];
+    let samples = match segment.band {
+        Some(band) => {
+            debug!("    Band: {:.0} Hz at {:+.0} Hz", band.bandwidth_hz, band.center_hz);
+            extract_band(segment_samples, sample_rate, band.center_hz, band.bandwidth_hz)
         }
+        None => segment_samples.to_vec(),
+    };
 
-        // Update noise floor estimate when not in transmission
-        if !in_transmission {
-            noise_floor_db = noise_floor_db * (1.0 - noise_alpha) + power_db * noise_alpha;
+    let band_offset_hz = segment.band.map_or(0.0, |band| band.center_hz);
+    let spectrum = estimate_spectrum(&samples, sample_rate, config.bandwidth_drop_db).map(|mut s| {
+        s.center_hz += band_offset_hz;
+        s
+    });
+    if let Some(spectrum) = spectrum {
+        debug!(
+            "    Bandwidth: {:.0} Hz at {:+.0} Hz",
+            spectrum.bandwidth_hz, spectrum.center_hz
+        );
+    }
+
+    let symbol_rate_hz = estimate_symbol_rate(&samples, sample_rate);
+    if let Some(rate) = symbol_rate_hz {
+        debug!("    Symbol rate: ~{:.0} Bd (estimate)", rate);
+    }
+
+    let cfo_hz = config.correct_cfo.then(|| estimate_cfo(&samples, sample_rate)).flatten();
+    if let Some(cfo) = cfo_hz {
+        debug!("    Carrier offset: {:+.1} Hz", cfo);
+    }
+
+    SlicedSegment {
+        cfo_hz,
+        peak_power_db: segment.peak_power_db,
+        center_hz: spectrum.map(|s| s.center_hz),
+        bandwidth_hz: spectrum.map(|s| s.bandwidth_hz),
+        symbol_rate_hz,
+        segment,
+        samples,
+    }
+}
+
+/// --max-duration in samples
+fn max_duration_samples(max_ms: u32, sample_rate: u32) -> usize {
+    (max_ms as f32 / 1000.0 * sample_rate as f32) as usize
+}
+
+/// Detect, filter and pad segments within the configured time range
+/// Returned segments are positioned absolutely within `all_samples`
+fn detect_file_segments(
+    all_samples: &[IqSample],
+    sample_rate: u32,
+    config: &SliceConfig,
+) -> Result<(Vec<Segment>, Activity), Box<dyn std::error::Error>> {
+    let SliceConfig {
+        min_duration_ms,
+        max_duration_ms,
+        max_duration_action,
+        gap_ms,
+        merge_gap_ms,
+        padding_ms,
+        overlap_policy,
+        detector,
+        noise_estimator,
+        min_snr_db,
+        debounce,
+        smooth_frames,
+        skip_carriers,
+        carrier_std_db,
+        start_seconds,
+        end_seconds,
+        ..
+    } = *config;
+    if sample_rate == 0 {
+        return Err("sample rate must be positive".into());
+    }
+
+    // Restrict to the requested time range, clamped to file bounds
+    // Segments are detected relative to the range and shifted back to absolute positions before output
+    let seconds_to_sample = |t: f64| ((t.max(0.0) * sample_rate as f64) as usize).min(all_samples.len());
+    let range_start = start_seconds.map(seconds_to_sample).unwrap_or(0);
+    let range_end = end_seconds.map(seconds_to_sample).unwrap_or(all_samples.len());
+    if range_start >= range_end {
+        return Err(format!(
+            "Empty time range: {:.2}s - {:.2}s (file is {:.2}s long)",
+            range_start as f64 / sample_rate as f64,
+            range_end as f64 / sample_rate as f64,
+            all_samples.len() as f64 / sample_rate as f64
+        )
+        .into());
+    }
+    let samples = &all_samples[range_start..range_end];
+    if start_seconds.is_some() || end_seconds.is_some() {
+        debug!(
+            "Processing range {:.2}s - {:.2}s",
+            range_start as f64 / sample_rate as f64,
+            range_end as f64 / sample_rate as f64
+        );
+    }
+
+    // Calculate detection parameters in samples
+    // 1ms windows for better burst detection, widened at low sample rates to keep a usable FFT
+    let window_size = (sample_rate as usize / 1000).max(MIN_CHUNK_SAMPLES);
+    let min_duration_samples = (min_duration_ms as f32 / 1000.0 * sample_rate as f32) as usize;
+    let gap_samples = (gap_ms as f32 / 1000.0 * sample_rate as f32) as usize;
+    let merge_gap_samples = (merge_gap_ms as f32 / 1000.0 * sample_rate as f32) as usize;
+    let padding_samples = (padding_ms as f32 / 1000.0 * sample_rate as f32) as usize;
+
+    // Compute power profile once for threshold analysis and detection
+    // Threshold and detection run on the smoothed profile; the carrier check keeps the raw fading
+    let raw_profile = match detector {
+        DetectorMode::Goertzel { target_hz } => {
+            if target_hz.abs() >= sample_rate as f64 / 2.0 {
+                return Err(format!(
+                    "--target-freq ({} Hz) must be within ±{} Hz of the center for this {} Hz recording",
+                    target_hz,
+                    sample_rate / 2,
+                    sample_rate
+                )
+                .into());
+            }
+            debug!("Using Goertzel detector at {:+.0} Hz", target_hz);
+            goertzel_power_profile(samples, window_size, target_hz, sample_rate)
         }
+        _ => {
+            check_dc_guard(config.dc_guard_bins, window_size)?;
+            let notches: Vec<f64> = config.notch_hz.iter().map(|hz| hz / sample_rate as f64).collect();
+            calculate_power_profile(samples, window_size, config.power_metric, &notches, config.dc_guard_bins)
+        }
+    };
+    let power_profile = smooth_profile(&raw_profile, smooth_frames);
+    if smooth_frames > 1 {
+        debug!("Smoothing power profile over {} frames", smooth_frames);
+    }
 
-        let threshold = noise_floor_db + threshold_margin;
-        let threshold_off = threshold - 3.0;
+    // Auto-detect threshold
+    let analysis = auto_threshold(&power_profile, noise_estimator);
+    debug!(
+        "Auto-detected: noise_floor={:.1} dB, p95={:.1} dB, threshold={:.1} dB",
+        analysis.noise_floor, analysis.p95, analysis.threshold
+    );
+    debug!(
+        "Power profile: min={:.1} dB, max={:.1} dB, mean={:.1} dB, std={:.1} dB",
+        analysis.min, analysis.max, analysis.mean, analysis.std_dev
+    );
+    let threshold = analysis.threshold;
+    if matches!(detector, DetectorMode::Threshold | DetectorMode::Goertzel { .. })
+        && threshold <= analysis.noise_floor + MIN_THRESHOLD_SPAN_DB {
+        warn!(
+            "Detection threshold ({:.1} dB) is within {:.0} dB of the noise floor ({:.1} dB); the recording may have no \
+             clear bursts. Try --noise-estimator mode, --min-snr or --detector cfar",
+            threshold, MIN_THRESHOLD_SPAN_DB, analysis.noise_floor
+        );
+    }
 
-        if !in_transmission {
-            // Update pre-buffer (ring buffer behavior)
-            pre_buffer.extend(chunk.iter().cloned());
-            if pre_buffer.len() > padding_samples {
-                pre_buffer.drain(0..(pre_buffer.len() - padding_samples));
+    if let Some(profile_path) = &config.profile_path {
+        let thresholds = match detector {
+            DetectorMode::Threshold
+            | DetectorMode::Goertzel { .. }
+            | DetectorMode::Matched { .. }
+            | DetectorMode::Spectrogram { .. }
+            | DetectorMode::Flatness { .. } => {
+                vec![threshold; power_profile.len()]
             }
+            DetectorMode::Cfar { guard, train, pfa } => cfar_thresholds(&power_profile, guard, train, pfa),
+        };
+        let hop_size = window_size / 2; // Must match calculate_power_profile
+        write_profile_csv(profile_path, &power_profile, &thresholds, hop_size, sample_rate, range_start)?;
+        debug!("Wrote power profile: {}", profile_path.display());
+    }
 
-            if power_db > threshold {
-                // Start of transmission
-                in_transmission = true;
-                silence_counter = 0;
-                tx_buffer.clear();
+    // Detect segments
+    debug!("Detecting transmissions...");
+    let segments = match detector {
+        DetectorMode::Threshold | DetectorMode::Goertzel { .. } => detect_segments(
+            &power_profile,
+            samples.len(),
+            window_size,
+            threshold,
+            min_duration_samples,
+            gap_samples,
+            debounce,
+        ),
+        DetectorMode::Cfar { guard, train, pfa } => {
+            debug!("Using CFAR detector (guard={}, train={}, pfa={:e})", guard, train, pfa);
+            detect_segments_cfar(
+                &power_profile,
+                samples.len(),
+                window_size,
+                guard,
+                train,
+                pfa,
+                min_duration_samples,
+                gap_samples,
+            )
+        }
+        DetectorMode::Matched { threshold: match_threshold } => {
+            let template_path = config
+                .template_path
+                .as_deref()
+                .ok_or("matched-filter detection requires a template")?;
+            let (template, template_metadata) = read_iq_wav(template_path, &WavOptions::default())?;
+            let template = resample(&template, template_metadata.sample_rate, sample_rate);
+            debug!(
+                "Using matched filter: {} ({} samples, threshold {:.2})",
+                template_path.display(),
+                template.len(),
+                match_threshold
+            );
 
-                // Add pre-buffer (padding before transmission)
-                tx_buffer.extend(pre_buffer.iter().cloned());
-                tx_buffer.extend(chunk);
+            let correlation = matched_filter_profile(samples, &template, window_size / 2);
+            detect_segments_matched(
+                &correlation,
+                &power_profile,
+                samples.len(),
+                window_size,
+                template.len(),
+                match_threshold,
+                min_duration_samples,
+                gap_samples,
+            )
+        }
+        DetectorMode::Spectrogram { margin_db } => {
+            debug!("Using time-frequency segmentation (margin {:.1} dB)", margin_db);
+            detect_segments_spectrogram(samples, sample_rate, margin_db, min_duration_samples, gap_samples)
+        }
+        DetectorMode::Flatness { max_flatness } => {
+            debug!("Using spectral flatness detector (max flatness {:.2})", max_flatness);
+            let flatness = spectral_flatness_profile(samples, window_size);
+            detect_segments_flatness(
+                &flatness,
+                &power_profile,
+                samples.len(),
+                window_size,
+                max_flatness,
+                min_duration_samples,
+                gap_samples,
+            )
+        }
+    };
+
+    // Signal time counts what the detector found, before filtering and padding
+    let activity = Activity {
+        signal_seconds: covered_samples(&segments) as f64 / sample_rate as f64,
+        analyzed_seconds: samples.len() as f64 / sample_rate as f64,
+    };
+
+    // One detection spanning nearly the whole range usually means the threshold sits in the noise
+    if let Some(widest) = segments.iter().map(|s| s.duration_samples()).max() {
+        if widest as f64 > WHOLE_FILE_COVERAGE * samples.len() as f64 {
+            warn!(
+                "A single detection covers {:.0}% of the input; the threshold is likely too low. \
+                 Try --noise-estimator mode, --min-snr or --detector cfar",
+                widest as f64 / samples.len() as f64 * 100.0
+            );
+        }
+    }
 
-                if verbose {
-                    println!("Transmission detected (peak: {:.1} dB, threshold: {:.1} dB)", power_db, threshold);
+    // Drop weak detections before padding so padding doesn't influence the decision
+    let segments: Vec<_> = if let Some(min_snr) = min_snr_db {
+        segments
+            .into_iter()
+            .filter(|s| {
+                let snr = s.peak_power_db - analysis.noise_floor;
+                if snr < min_snr {
+                    debug!(
+                        "  Discarded {:.2}s - {:.2}s (SNR {:.1} dB < {:.1} dB)",
+                        (s.start_sample + range_start) as f32 / sample_rate as f32,
+                        (s.end_sample + range_start) as f32 / sample_rate as f32,
+                        snr,
+                        min_snr
+                    );
                 }
+                snr >= min_snr
+            })
+            .collect()
+    } else {
+        segments
+    };
+
+    // Constant-power segments are likely carriers or birdies rather than bursts
+    let segments: Vec<_> = segments
+        .into_iter()
+        .filter(|s| {
+            let std_db = segment_power_std_db(s, &raw_profile, window_size);
+            if std_db >= carrier_std_db {
+                return true;
             }
-        } else {
-            // Currently recording
-            tx_buffer.extend(chunk);
+            debug!(
+                "  {} constant carrier {:.2}s - {:.2}s (power std dev {:.2} dB)",
+                if skip_carriers { "Skipped" } else { "Possible" },
+                (s.start_sample + range_start) as f32 / sample_rate as f32,
+                (s.end_sample + range_start) as f32 / sample_rate as f32,
+                std_db
+            );
+            !skip_carriers
+        })
+        .collect();
+
+    // Join neighbouring transmissions into one slice; time-frequency segments on different bands stay apart
+    let segments = match detector {
+        DetectorMode::Spectrogram { .. } => segments,
+        _ => merge_segments(segments, merge_gap_samples),
+    };
+
+    let segments: Vec<_> = segments
+        .into_iter()
+        .map(|mut s| {
+            s.crest_db = segment_crest_db(&s, &raw_profile, window_size);
+            s
+        })
+        .collect();
 
-            if power_db < threshold_off {
-                silence_counter += chunk_size;
+    // Drop quiet edges (e.g. bridged gaps) so padding alone sets the margin
+    let segments = if config.trim_tail {
+        trim_segment_silence(segments, &power_profile, window_size, threshold - 3.0)
+    } else {
+        segments
+    };
 
-                if silence_counter >= gap_samples {
-                    // End of transmission
-                    in_transmission = false;
+    // Add padding, clamped to the recording
+    let segments = add_padding(segments, padding_samples, samples.len());
 
-                    // Check minimum duration (excluding padding)
-                    let actual_duration = tx_buffer.len().saturating_sub(padding_samples);
+    // Drop, keep or split segments over the max duration, if specified
+    let segments: Vec<_> = match max_duration_ms.map(|ms| max_duration_samples(ms, sample_rate)) {
+        Some(max_samples) => match max_duration_action {
+            MaxDurationAction::Drop => segments
+                .into_iter()
+                .filter(|s| s.duration_samples() <= max_samples)
+                .collect(),
+            MaxDurationAction::Keep => segments,
+            MaxDurationAction::Split => split_segments(segments, max_samples),
+        },
+        None => segments,
+    };
 
-                    if actual_duration >= min_duration_samples {
-                        slice_counter += 1;
+    // Keep padded neighbours from sharing samples if asked; time-frequency segments on different bands
+    // may legitimately overlap in time. This comes after the max-duration handling so merging two
+    // overlapping slices can't get both dropped as one over-long slice.
+    let segments = match detector {
+        DetectorMode::Spectrogram { .. } => segments,
+        _ => resolve_overlaps(segments, overlap_policy),
+    };
 
-                        let filename = generate_filename(slice_counter, 0, sample_rate, Local::now());
-                        let output_path = output_dir.join(&filename);
+    // Shift to absolute positions within the recording
+    let segments: Vec<_> = segments
+        .into_iter()
+        .map(|mut s| {
+            s.start_sample += range_start;
+            s.end_sample += range_start;
+            s
+        })
+        .collect();
 
-                        if float32_output {
-                            write_iq_wav_float32(&output_path, &tx_buffer, sample_rate)?;
-                        } else {
-                            write_iq_wav(&output_path, &tx_buffer, sample_rate)?;
-                        }
+    debug!("Found {} transmission(s)", segments.len());
 
-                        let duration_ms = tx_buffer.len() as f32 / sample_rate as f32 * 1000.0;
-                        println!("Saved: {} ({:.1}ms)", filename, duration_ms);
-                    } else if verbose {
-                        println!("Discarded short transmission ({:.1}ms)", actual_duration as f32 / sample_rate as f32 * 1000.0);
-                    }
+    Ok((segments, activity))
+}
 
-                    tx_buffer.clear();
-                }
-            } else {
-                // Reset silence counter if signal comes back
-                silence_counter = 0;
-            }
-        }
-    }
+/// Label track and cue marker names for `count` slices, numbered and formatted like their filenames
+fn slice_labels(config: &SliceConfig, count: usize) -> Vec<String> {
+    (0..count).map(|i| config.name_template.index_label(config.first_index + i)).collect()
+}
 
-    // Handle any remaining transmission
-    if in_transmission && tx_buffer.len() >= min_duration_samples {
-        slice_counter += 1;
-        let filename = generate_filename(slice_counter, 0, sample_rate, Local::now());
-        let output_path = output_dir.join(&filename);
+/// Resolve where the `--concat` recording goes; it is taken when either it or its `.txt` labels exist
+fn resolve_concat_path(path: &Path, overwrite: OverwritePolicy) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    resolve_path_with(path, overwrite, |path| {
+        [path.to_path_buf(), path.with_extension("txt")].into_iter().find(|p| p.exists())
+    })
+}
 
-        if float32_output {
-            write_iq_wav_float32(&output_path, &tx_buffer, sample_rate)?;
-        } else {
-            write_iq_wav(&output_path, &tx_buffer, sample_rate)?;
+/// Write slices back to back into one file, `concat_gap_ms` of silence apart, plus Audacity labels
+/// (`<file>.txt`) marking where each slice sits in it
+fn write_concat(path: &Path, slices: &[SlicedSegment], sample_rate: u32, config: &SliceConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let gap = vec![IqSample::new(0.0, 0.0); (config.concat_gap_ms as f64 * sample_rate as f64 / 1000.0) as usize];
+    let mut samples = Vec::new();
+    let mut placed = Vec::with_capacity(slices.len());
+    for (i, slice) in slices.iter().enumerate() {
+        if i > 0 {
+            samples.extend_from_slice(&gap);
         }
-        println!("Saved final: {} ({:.1}ms)", filename, tx_buffer.len() as f32 / sample_rate as f32 * 1000.0);
+        let start_sample = samples.len();
+        let center_hz = slice.segment.band.map_or(0.0, |band| band.center_hz);
+        samples.extend_from_slice(&output_samples(config, slice, sample_rate, center_hz));
+        placed.push(Segment {
+            start_sample,
+            end_sample: samples.len(),
+            ..slice.segment.clone()
+        });
     }
 
-    println!("Total slices saved: {}", slice_counter);
+    config.slice_writer.write(path, &samples, sample_rate, config.output_gain, config.channel_order)?;
+    let labels_path = path.with_extension("txt");
+    write_audacity_labels(&labels_path, &placed, sample_rate, &slice_labels(config, placed.len()))?;
+    info!(
+        "Wrote {} slice(s) into {} ({:.1}s), labels in {}",
+        slices.len(),
+        path.display(),
+        samples.len() as f64 / sample_rate as f64,
+        labels_path.display()
+    );
     Ok(())
 }
+
+/// Print the run summary and write the JSON report if requested
+fn finish_run(stats: &RunStats, config: &SliceConfig) -> Result<(), Box<dyn std::error::Error>> {
+    stats.print_summary();
+    if config.ndjson {
+        print_event(&Event::Summary {
+            time: Event::now(),
+            summary: stats.summary(),
+        });
+    }
+    if let Some(report_path) = &config.report_path {
+        stats.write_report(report_path)?;
+        debug!("Wrote report: {}", report_path.display());
+    }
+
+    Ok(())
+}
+
+/// Process live IQ stream and output sliced IQ segments
+pub fn process_stream(
+    addr: &str,
+    output_dir: &Path,
+    config: &SliceConfig,
+    stream_config: &StreamConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    process_stream_with(addr, output_dir, config, stream_config, |_| {})
+}
+
+/// Like `process_stream`, calling `on_slice` once each ended transmission has been written
+/// Segment positions count samples since the start of the stream (or channel, when channelized)
+pub fn process_stream_with(
+    addr: &str,
+    output_dir: &Path,
+    config: &SliceConfig,
+    stream_config: &StreamConfig,
+    mut on_slice: impl FnMut(&SlicedSegment),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let StreamConfig {
+        sample_rate,
+        threshold_margin,
+        format,
+        input_gain,
+        conjugate,
+        endian,
+        read_timeout,
+        chunk_ms,
+        noise_alpha,
+        ..
+    } = *stream_config;
+
+    // "-" reads from stdin so the slicer can sit at the end of a shell pipeline
+    let initial_format = format.unwrap_or(StreamFormat::Float32);
+    let reader = if addr == "-" {
+        IqStreamReader::stdin(initial_format, endian)
+    } else {
+        IqStreamReader::connect(addr, initial_format, endian, read_timeout)?
+    };
+    let mut reader = reader.with_gain(input_gain).with_conjugate(conjugate);
+    if format.is_none() {
+        match reader.detect_format()? {
+            Some(guess) => info!("Input format detected as {} (use --input-format to override)", guess),
+            None => warn!("Could not detect input format, assuming {}", initial_format),
+        }
+    }
+
+    let shutdown = shutdown_flag()?;
+
+    if addr == "-" {
+        debug!("Reading stream from stdin");
+    } else {
+        debug!("Connected to stream at {}", addr);
+    }
+
+    let mut raw_recorder = match &stream_config.record_raw {
+        Some(path) => {
+            let float32 = config.slice_writer != SliceWriter::WavInt16;
+            let recorder = RawRecorder::create(path, sample_rate, float32, stream_config.record_rotation, config.overwrite)?;
+            info!("Recording full stream to {}", recorder.path().display());
+            Some(recorder)
+        }
+        None => None,
+    };
+
+    debug!("Sample rate: {} Hz", sample_rate);
+    debug!("Using FFT peak detection for wideband monitoring");
+    match stream_config.threshold_mode {
+        StreamThreshold::Margin => debug!("Threshold margin: +{:.0} dB above noise floor", threshold_margin),
+        StreamThreshold::Percentile { percentile, window_seconds } => debug!(
+            "Threshold margin: +{:.0} dB above the {}th percentile of the last {}s",
+            threshold_margin, percentile, window_seconds
+        ),
+    }
+
+    let chunk_size = (sample_rate as f64 * chunk_ms as f64 / 1000.0) as usize;
+    debug!(
+        "Chunk size: {} samples ({} ms, {}-point FFT), noise alpha {}",
+        chunk_size,
+        chunk_ms,
+        chunk_size.next_power_of_two(),
+        noise_alpha
+    );
+
+    // Either one wideband detector, or one per channel fed by the channelizer
+    let mut channelizer = match stream_config.channels {
+        Some(plan) => {
+            let channelizer = Channelizer::new(sample_rate, plan.count, plan.bandwidth_hz)?;
+            info!(
+                "Channelizing into {} x {} Hz channels ({} Hz per channel)",
+                plan.count,
+                plan.bandwidth_hz,
+                channelizer.output_rate()
+            );
+            Some(channelizer)
+        }
+        None => None,
+    };
+    let mut detectors: Vec<StreamDetector> = match &channelizer {
+        Some(channelizer) => channelizer
+            .offsets_hz()
+            .iter()
+            .map(|&offset| StreamDetector::new(config, channelizer.output_rate(), stream_config, Some(offset)))
+            .collect(),
+        None => vec![StreamDetector::new(config, sample_rate, stream_config, None)],
+    };
+    if let Some(channelizer) = &channelizer {
+        let channel_chunk = (channelizer.output_rate() as f64 * chunk_ms as f64 / 1000.0) as usize;
+        if channel_chunk < MIN_CHUNK_SAMPLES {
+            return Err(format!(
+                "--chunk-ms {} gives only {} samples per channel chunk (need at least {})",
+                chunk_ms, channel_chunk, MIN_CHUNK_SAMPLES
+            )
+            .into());
+        }
+        check_dc_guard(config.dc_guard_bins, channel_chunk.next_power_of_two())?;
+    } else {
+        check_dc_guard(config.dc_guard_bins, chunk_size.next_power_of_two())?;
+    }
+
+    let mut fft_planner = FftPlanner::new();
+    let mut output = StreamOutput::new(config, output_dir, stream_config.date_folders);
+    let session_start = Instant::now();
+    let mut dropouts = DropoutMonitor::new(sample_rate);
+    let mut events = EventSink::new(stream_config.event_sink.as_deref(), config.ndjson);
+
+    info!("Listening for transmissions... (Ctrl+C to stop)");
+    let reconnect = stream_config.reconnect && addr != "-";
+
+    'stream: loop {
+        if shutdown.load(Ordering::SeqCst) {
+            info!("Shutting down");
+            break;
+        }
+        if stream_config.time_limit.is_some_and(|limit| session_start.elapsed() >= limit) {
+            info!("Duration limit reached, stopping");
+            break;
+        }
+
+        let chunk = match reader.read_chunk(chunk_size) {
+            Ok(Some(c)) => c,
+            Ok(None) if !reconnect => {
+                info!("Stream closed");
+                break;
+            }
+            Err(e) if e.is::<ReadTimeout>() => {
+                debug!("No data within read timeout, waiting...");
+                continue;
+            }
+            Err(e) if !reconnect => return Err(e),
+            lost => {
+                match lost {
+                    Err(e) => warn!("Stream error: {}", e),
+                    _ => warn!("Stream closed"),
+                }
+                // Save transmissions cut off by the disconnect now rather than splice them onto later samples
+                let disconnected = Instant::now();
+                if !flush_detectors(&mut detectors, &mut output, config, output_dir)? {
+                    break;
+                }
+                output.collect_written(false, &mut events, &mut on_slice)?;
+                let Some(resumed) = reconnect_stream(addr, reader.format(), stream_config, &shutdown, session_start)
+                else {
+                    break;
+                };
+                reader = resumed;
+                for detector in &mut detectors {
+                    detector.resume_after(disconnected.elapsed());
+                }
+                continue;
+            }
+        };
+
+        if let Some(recorder) = raw_recorder.as_mut() {
+            recorder.write(&chunk)?;
+        }
+        output.record_chunk(chunk.len(), sample_rate);
+        output.stats.record_input(chunk.len(), chunk.iter().filter(|s| s.is_saturated()).count());
+        if let Some(missing) = dropouts.record(chunk.len()) {
+            warn!("Possible stream dropout: ~{:.0} ms of samples missing", missing * 1000.0);
+        }
+
+        let chunks = match channelizer.as_mut() {
+            Some(channelizer) => channelizer.process(&chunk),
+            None => vec![chunk],
+        };
+
+        for (detector, chunk) in detectors.iter_mut().zip(chunks) {
+            let transmission = match detector.process(chunk, &mut fft_planner) {
+                None => continue,
+                Some(DetectorEvent::Started { power_db }) => {
+                    if let Some(sink) = events.as_mut() {
+                        sink.send(&Event::Start {
+                            time: Event::now(),
+                            peak_db: power_db,
+                            channel_hz: detector.channel_hz,
+                        });
+                    }
+                    continue;
+                }
+                Some(DetectorEvent::Discarded { duration_ms, peak_power_db }) => {
+                    if let Some(sink) = events.as_mut() {
+                        sink.send(&Event::End {
+                            time: Event::now(),
+                            filename: None,
+                            duration_ms,
+                            peak_db: peak_power_db,
+                            channel_hz: detector.channel_hz,
+                        });
+                    }
+                    continue;
+                }
+                Some(DetectorEvent::Ended(transmission)) => transmission,
+            };
+            if !has_free_space(output_dir, config.min_free_bytes)? {
+                warn!("{}", low_space_message(output_dir, config.min_free_bytes));
+                break 'stream;
+            }
+            let slice = transmission.into_slice(detector.sample_rate, config);
+            output.save(slice, detector.sample_rate, detector.channel_hz)?;
+
+            if config.max_slices.is_some_and(|max| output.slice_counter >= max) {
+                info!("Slice limit reached, stopping");
+                break 'stream;
+            }
+        }
+        output.collect_written(false, &mut events, &mut on_slice)?;
+    }
+
+    // Handle any remaining transmissions
+    flush_detectors(&mut detectors, &mut output, config, output_dir)?;
+    output.collect_written(true, &mut events, &mut on_slice)?;
+
+    if let Some(recorder) = raw_recorder {
+        recorder.finalize()?;
+    }
+
+    info!("Total slices saved: {}", output.slice_counter);
+    output.stats.monitored_seconds = Some(session_start.elapsed().as_secs_f64());
+    output.stats.dropout_seconds = Some(dropouts.total_seconds);
+    finish_run(&output.stats, config)
+}
+
+/// Flag set by Ctrl+C, cleared for each new stream run
+/// The handler can only be installed once per process, so every run in it shares one flag
+fn shutdown_flag() -> Result<Arc<AtomicBool>, Box<dyn std::error::Error>> {
+    static SHUTDOWN: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+    let mut installed = SHUTDOWN.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(shutdown) = installed.as_ref() {
+        shutdown.store(false, Ordering::SeqCst);
+        return Ok(shutdown.clone());
+    }
+    // First Ctrl+C requests a clean shutdown (flushing any in-progress transmission), second exits immediately
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        ctrlc::set_handler(move || {
+            if shutdown.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        })?;
+    }
+    *installed = Some(shutdown.clone());
+    Ok(shutdown)
+}
+
+/// End every detector's transmission in progress and queue it for writing
+/// Returns false when the slice limit or low disk space means nothing more should be written
+fn flush_detectors(
+    detectors: &mut [StreamDetector],
+    output: &mut StreamOutput,
+    config: &SliceConfig,
+    output_dir: &Path,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    for detector in detectors {
+        let Some(transmission) = detector.flush() else {
+            continue;
+        };
+        if config.max_slices.is_some_and(|max| output.slice_counter >= max) {
+            return Ok(false);
+        }
+        if !has_free_space(output_dir, config.min_free_bytes)? {
+            warn!("{}", low_space_message(output_dir, config.min_free_bytes));
+            return Ok(false);
+        }
+        let slice = transmission.into_slice(detector.sample_rate, config);
+        output.save(slice, detector.sample_rate, detector.channel_hz)?;
+    }
+    Ok(true)
+}
+
+/// Time between attempts to reconnect a lost stream
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reconnect to a lost TCP stream in the format it was sending, retrying until it is back
+/// Returns None if shutdown is requested or the duration limit passes first
+fn reconnect_stream(
+    addr: &str,
+    format: StreamFormat,
+    stream_config: &StreamConfig,
+    shutdown: &AtomicBool,
+    session_start: Instant,
+) -> Option<IqStreamReader> {
+    loop {
+        let waited = Instant::now();
+        while waited.elapsed() < RECONNECT_INTERVAL {
+            if shutdown.load(Ordering::SeqCst) {
+                info!("Shutting down");
+                return None;
+            }
+            if stream_config.time_limit.is_some_and(|limit| session_start.elapsed() >= limit) {
+                info!("Duration limit reached, stopping");
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        match IqStreamReader::connect(addr, format, stream_config.endian, stream_config.read_timeout) {
+            Ok(reader) => {
+                info!("Reconnected to {}", addr);
+                return Some(
+                    reader
+                        .with_gain(stream_config.input_gain)
+                        .with_conjugate(stream_config.conjugate),
+                );
+            }
+            Err(e) => debug!("Reconnect to {} failed: {}", addr, e),
+        }
+    }
+}
+
+/// Wall-clock lag beyond the sample clock that counts as a dropout
+const DROPOUT_THRESHOLD_SECONDS: f64 = 0.25;
+
+/// Estimates samples lost upstream by comparing stream time (samples / rate) with wall-clock time
+/// The stream carries no sequence numbers, so a sender that stalls and later catches up from a
+/// buffer is indistinguishable from one that drops samples; reports are estimates
+struct DropoutMonitor {
+    sample_rate: u32,
+    /// Arrival time of the first chunk
+    start: Option<Instant>,
+    /// Samples received after the first chunk
+    samples: u64,
+    /// Lag already reported as dropouts
+    reported_lag: f64,
+    total_seconds: f64,
+}
+
+impl DropoutMonitor {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            start: None,
+            samples: 0,
+            reported_lag: 0.0,
+            total_seconds: 0.0,
+        }
+    }
+
+    /// Account for a received chunk, returning the estimated missing duration if a dropout is detected
+    fn record(&mut self, samples: usize) -> Option<f64> {
+        let Some(start) = self.start else {
+            self.start = Some(Instant::now());
+            return None;
+        };
+        self.samples += samples as u64;
+
+        // Wall-clock running ahead of the sample clock means samples never arrived
+        let lag = start.elapsed().as_secs_f64() - self.samples as f64 / self.sample_rate as f64;
+        let missing = lag - self.reported_lag;
+        if missing > DROPOUT_THRESHOLD_SECONDS {
+            self.reported_lag = lag;
+            self.total_seconds += missing;
+            return Some(missing);
+        }
+        // A sender delivering faster than real time pulls the lag down; track it so later gaps measure from there
+        self.reported_lag = self.reported_lag.min(lag.max(0.0));
+        None
+    }
+}
+
+/// State change reported by a `StreamDetector` for one chunk
+enum DetectorEvent {
+    /// A transmission crossed the threshold
+    Started { power_db: f32 },
+    /// A transmission ended and meets the minimum duration
+    Ended(Transmission),
+    /// A transmission ended but was too short to keep
+    Discarded { duration_ms: f32, peak_power_db: f32 },
+}
+
+/// A finished stream transmission, including padding
+struct Transmission {
+    /// Position of the first sample since the start of the (channel) stream
+    start_sample: usize,
+    samples: Vec<IqSample>,
+    peak_power_db: f32,
+    crest_db: Option<f32>,
+}
+
+impl Transmission {
+    /// Measure the occupied spectrum and package the transmission as a slice
+    fn into_slice(self, sample_rate: u32, config: &SliceConfig) -> SlicedSegment {
+        let spectrum = estimate_spectrum(&self.samples, sample_rate, config.bandwidth_drop_db);
+        if let Some(spectrum) = spectrum {
+            debug!(
+                "Bandwidth: {:.0} Hz at {:+.0} Hz",
+                spectrum.bandwidth_hz, spectrum.center_hz
+            );
+        }
+        let symbol_rate_hz = estimate_symbol_rate(&self.samples, sample_rate);
+        if let Some(rate) = symbol_rate_hz {
+            debug!("Symbol rate: ~{:.0} Bd (estimate)", rate);
+        }
+        let cfo_hz = config.correct_cfo.then(|| estimate_cfo(&self.samples, sample_rate)).flatten();
+        if let Some(cfo) = cfo_hz {
+            debug!("Carrier offset: {:+.1} Hz", cfo);
+        }
+        SlicedSegment {
+            symbol_rate_hz,
+            cfo_hz,
+            segment: Segment {
+                start_sample: self.start_sample,
+                end_sample: self.start_sample + self.samples.len(),
+                peak_power_db: self.peak_power_db,
+                crest_db: self.crest_db,
+                band: None,
+            },
+            samples: self.samples,
+            peak_power_db: self.peak_power_db,
+            center_hz: spectrum.map(|s| s.center_hz),
+            bandwidth_hz: spectrum.map(|s| s.bandwidth_hz),
+        }
+    }
+}
+
+/// Stream start used to seed the noise floor when no --warmup-seconds is given (seconds)
+/// A median over this many chunks is not thrown off by a transmission already on air at startup.
+const INITIAL_FLOOR_SECONDS: f64 = 0.5;
+
+/// Streaming detection state machine for one (wideband or channelized) IQ stream
+struct StreamDetector {
+    sample_rate: u32,
+    /// Channel center relative to the tuned frequency, when channelized
+    channel_hz: Option<f64>,
+    threshold_margin: f32,
+    threshold_mode: StreamThreshold,
+    /// Recent chunk powers outside transmissions, for the percentile threshold
+    recent_powers: VecDeque<f32>,
+    /// Chunks kept in `recent_powers` (0 in margin mode)
+    recent_capacity: usize,
+    power_metric: PowerMetric,
+    /// `--notch-freq` frequencies relative to this stream's center, as fractions of its sample rate
+    notches: Vec<f64>,
+    /// FFT bins around DC left out of the detection power
+    dc_guard_bins: usize,
+    /// Noise floor adaptation rate per chunk
+    noise_alpha: f32,
+    /// Samples still to observe before detection starts; only the noise floor is updated meanwhile
+    warmup_samples: usize,
+    /// Finite chunk powers seen during warmup; their median seeds the noise floor
+    warmup_powers: Vec<f32>,
+    /// Chunks between periodic debug power reports (~1 second)
+    debug_interval: usize,
+    min_duration_samples: usize,
+    gap_samples: usize,
+    padding_samples: usize,
+    /// Cut the closing silence down to `padding_samples` when a transmission ends
+    trim_tail: bool,
+    /// Buffered transmission length at which the buffer is flushed as a slice
+    max_tx_samples: usize,
+    /// Blackman window matching the current chunk length
+    window: Vec<f32>,
+    /// Running average of FFT power; None until measured from the stream
+    noise_floor_db: Option<f32>,
+    /// Ring buffer for padding (stores recent samples before transmission)
+    pre_buffer: Vec<IqSample>,
+    /// Buffer for current transmission
+    tx_buffer: Vec<IqSample>,
+    in_transmission: bool,
+    /// The current transmission was already split by `max_tx_samples`
+    tx_continued: bool,
+    tx_peak_db: f32,
+    /// Sum and count of linear chunk powers in the current transmission, for its crest factor
+    tx_power_sum: f64,
+    tx_power_chunks: usize,
+    /// Linear power and count of the chunks since the signal last dropped below the release threshold;
+    /// folded into the crest sums if it comes back, left out if the transmission ends there
+    quiet_power_sum: f64,
+    quiet_power_chunks: usize,
+    /// Stream position of the first buffered transmission sample
+    tx_start: usize,
+    /// Samples fed so far
+    position: usize,
+    silence_counter: usize,
+    debug_counter: usize,
+}
+
+impl StreamDetector {
+    fn new(config: &SliceConfig, sample_rate: u32, stream_config: &StreamConfig, channel_hz: Option<f64>) -> Self {
+        let ms_to_samples = |ms: u32| (ms as f32 / 1000.0 * sample_rate as f32) as usize;
+        let padding_samples = ms_to_samples(config.padding_ms);
+        Self {
+            sample_rate,
+            channel_hz,
+            threshold_margin: stream_config.threshold_margin,
+            threshold_mode: stream_config.threshold_mode,
+            recent_powers: VecDeque::new(),
+            recent_capacity: match stream_config.threshold_mode {
+                StreamThreshold::Margin => 0,
+                StreamThreshold::Percentile { window_seconds, .. } => {
+                    ((window_seconds * 1000.0 / stream_config.chunk_ms as f64) as usize).max(1)
+                }
+            },
+            power_metric: config.power_metric,
+            notches: config
+                .notch_hz
+                .iter()
+                .map(|hz| (hz - channel_hz.unwrap_or(0.0)) / sample_rate as f64)
+                .collect(),
+            dc_guard_bins: config.dc_guard_bins,
+            noise_alpha: stream_config.noise_alpha,
+            warmup_samples: if stream_config.warmup_seconds > 0.0 {
+                (stream_config.warmup_seconds * sample_rate as f64) as usize
+            } else {
+                (INITIAL_FLOOR_SECONDS * sample_rate as f64) as usize
+            },
+            warmup_powers: Vec::new(),
+            debug_interval: ((1000.0 / stream_config.chunk_ms).round() as usize).max(1),
+            min_duration_samples: ms_to_samples(config.min_duration_ms),
+            gap_samples: ms_to_samples(config.gap_ms),
+            padding_samples,
+            trim_tail: config.trim_tail,
+            max_tx_samples: (stream_config.max_tx_seconds * sample_rate as f64) as usize,
+            window: Vec::new(),
+            noise_floor_db: None,
+            pre_buffer: Vec::with_capacity(padding_samples),
+            tx_buffer: Vec::new(),
+            in_transmission: false,
+            tx_continued: false,
+            tx_peak_db: f32::NEG_INFINITY,
+            tx_power_sum: 0.0,
+            tx_power_chunks: 0,
+            quiet_power_sum: 0.0,
+            quiet_power_chunks: 0,
+            tx_start: 0,
+            position: 0,
+            silence_counter: 0,
+            debug_counter: 0,
+        }
+    }
+
+    /// Detection threshold in dB given the current noise floor estimate
+    fn threshold_db(&self, noise_floor_db: f32) -> f32 {
+        let StreamThreshold::Percentile { percentile, .. } = self.threshold_mode else {
+            return noise_floor_db + self.threshold_margin;
+        };
+        if self.recent_powers.is_empty() {
+            return noise_floor_db + self.threshold_margin;
+        }
+        let mut recent: Vec<f32> = self.recent_powers.iter().copied().collect();
+        recent.sort_by(f32::total_cmp);
+        let rank = ((recent.len() - 1) as f32 * percentile / 100.0).round() as usize;
+        recent[rank.min(recent.len() - 1)] + self.threshold_margin
+    }
+
+    /// Add the power of a chunk outside transmissions to the percentile history
+    /// Called after the start decision, so a chunk never raises its own threshold
+    fn push_recent_power(&mut self, power_db: f32) {
+        if self.recent_capacity == 0 || !power_db.is_finite() {
+            return;
+        }
+        if self.recent_powers.len() == self.recent_capacity {
+            self.recent_powers.pop_front();
+        }
+        self.recent_powers.push_back(power_db);
+    }
+
+    /// Keep the last `padding_samples` samples before a transmission (ring buffer behavior)
+    fn push_pre_buffer(&mut self, chunk: &[IqSample]) {
+        self.pre_buffer.extend_from_slice(chunk);
+        if self.pre_buffer.len() > self.padding_samples {
+            self.pre_buffer.drain(0..(self.pre_buffer.len() - self.padding_samples));
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.channel_hz {
+            Some(hz) => format!("[{:+.0} Hz] ", hz),
+            None => String::new(),
+        }
+    }
+
+    /// Feed one chunk, returning an event when a transmission starts or ends
+    fn process(&mut self, chunk: Vec<IqSample>, fft_planner: &mut FftPlanner<f32>) -> Option<DetectorEvent> {
+        // The final chunk before end of stream may be short, and channelized chunks vary by a sample
+        let chunk_len = chunk.len();
+        if chunk_len == 0 {
+            return None;
+        }
+        if self.window.len() != chunk_len {
+            self.window = blackman_window(chunk_len);
+        }
+        self.position += chunk_len;
+
+        // Use FFT peak power detection with Blackman window for wideband monitoring
+        let power_db = calculate_power_db_padded(
+            &chunk,
+            &self.window,
+            chunk_len.next_power_of_two(),
+            self.power_metric,
+            &self.notches,
+            self.dc_guard_bins,
+            fft_planner,
+        );
+
+        // Debug: print power level every ~1 second
+        self.debug_counter += 1;
+        if let Some(floor) = self.noise_floor_db.filter(|_| self.debug_counter.is_multiple_of(self.debug_interval)) {
+            let threshold = self.threshold_db(floor);
+            debug!("{}[debug] peak_power: {:.1} dB, noise_floor: {:.1} dB, threshold: {:.1} dB",
+                     self.label(), power_db, floor, threshold);
+        }
+
+        // During warmup only collect chunk powers; their median seeds the noise floor
+        // The pre-buffer still fills, so a transmission right after warmup gets its leading padding
+        if self.warmup_samples > 0 {
+            self.push_pre_buffer(&chunk);
+            self.push_recent_power(power_db);
+            if power_db.is_finite() {
+                self.warmup_powers.push(power_db);
+            }
+            self.warmup_samples = self.warmup_samples.saturating_sub(chunk_len);
+            if self.warmup_samples == 0 && !self.warmup_powers.is_empty() {
+                self.warmup_powers.sort_by(f32::total_cmp);
+                let floor = self.warmup_powers[self.warmup_powers.len() / 2];
+                info!("{}Warmup complete, measured noise floor {:.1} dB", self.label(), floor);
+                self.noise_floor_db = Some(floor);
+                self.warmup_powers = Vec::new();
+            }
+            return None;
+        }
+
+        // If warmup saw no power at all (digital silence), start from the first chunk with any
+        let mut noise_floor_db = match self.noise_floor_db {
+            Some(floor) => floor,
+            None if power_db.is_finite() => {
+                debug!("{}Initial noise floor {:.1} dB from first chunk", self.label(), power_db);
+                self.noise_floor_db = Some(power_db);
+                self.push_pre_buffer(&chunk);
+                self.push_recent_power(power_db);
+                return None;
+            }
+            None => return None,
+        };
+
+        // Update noise floor estimate when not in transmission
+        if !self.in_transmission {
+            noise_floor_db = noise_floor_db * (1.0 - self.noise_alpha) + power_db * self.noise_alpha;
+            self.noise_floor_db = Some(noise_floor_db);
+        }
+
+        let threshold = self.threshold_db(noise_floor_db);
+        let threshold_off = threshold - 3.0;
+
+        if !self.in_transmission {
+            if power_db > threshold {
+                // Start of transmission
+                self.in_transmission = true;
+                self.tx_continued = false;
+                self.silence_counter = 0;
+                self.tx_buffer.clear();
+                self.tx_peak_db = power_db;
+                self.tx_power_sum = 0.0;
+                self.tx_power_chunks = 0;
+                self.quiet_power_sum = 0.0;
+                self.quiet_power_chunks = 0;
+                self.add_tx_power(power_db);
+
+                // The pre-buffer holds the padding before this chunk, which joins the transmission once
+                self.tx_buffer.append(&mut self.pre_buffer);
+                self.tx_buffer.extend(chunk);
+                self.tx_start = self.position - self.tx_buffer.len();
+
+                debug!("{}Transmission detected (peak: {:.1} dB, threshold: {:.1} dB)", self.label(), power_db, threshold);
+                return Some(DetectorEvent::Started { power_db });
+            }
+            self.push_pre_buffer(&chunk);
+            self.push_recent_power(power_db);
+            return None;
+        }
+
+        // Currently recording
+        self.tx_buffer.extend(chunk);
+        self.tx_peak_db = self.tx_peak_db.max(power_db);
+
+        if power_db >= threshold_off {
+            // Reset silence counter if signal comes back; the quiet stretch was part of the transmission
+            self.silence_counter = 0;
+            self.tx_power_sum += std::mem::take(&mut self.quiet_power_sum);
+            self.tx_power_chunks += std::mem::take(&mut self.quiet_power_chunks);
+            self.add_tx_power(power_db);
+
+            // A stuck signal would grow the buffer without bound; flush it and keep recording
+            if self.tx_buffer.len() >= self.max_tx_samples {
+                if !self.tx_continued {
+                    warn!(
+                        "{}Transmission exceeded --max-tx-seconds, saving it in consecutive slices",
+                        self.label()
+                    );
+                }
+                self.tx_continued = true;
+                let transmission = Transmission {
+                    start_sample: self.tx_start,
+                    samples: std::mem::take(&mut self.tx_buffer),
+                    peak_power_db: self.tx_peak_db,
+                    crest_db: self.tx_crest_db(),
+                };
+                self.tx_start = self.position;
+                self.tx_peak_db = f32::NEG_INFINITY;
+                self.tx_power_sum = 0.0;
+                self.tx_power_chunks = 0;
+                return Some(DetectorEvent::Ended(transmission));
+            }
+            return None;
+        }
+
+        self.silence_counter += chunk_len;
+        if power_db.is_finite() {
+            self.quiet_power_sum += 10f64.powf(power_db as f64 / 10.0);
+            self.quiet_power_chunks += 1;
+        }
+        if self.silence_counter < self.gap_samples {
+            return None;
+        }
+
+        // End of transmission
+        self.in_transmission = false;
+        let mut samples = std::mem::take(&mut self.tx_buffer);
+
+        // Check minimum duration (excluding padding); the tail of a split transmission is always kept
+        let actual_duration = samples.len().saturating_sub(self.padding_samples);
+        if actual_duration < self.min_duration_samples && !self.tx_continued {
+            let duration_ms = actual_duration as f32 / self.sample_rate as f32 * 1000.0;
+            debug!("{}Discarded short transmission ({:.1}ms)", self.label(), duration_ms);
+            return Some(DetectorEvent::Discarded {
+                duration_ms,
+                peak_power_db: self.tx_peak_db,
+            });
+        }
+
+        // The closing `silence_counter` samples were all below the release threshold
+        if self.trim_tail && self.silence_counter > self.padding_samples {
+            samples.truncate(samples.len() - (self.silence_counter - self.padding_samples));
+        }
+
+        Some(DetectorEvent::Ended(Transmission {
+            start_sample: self.tx_start,
+            samples,
+            peak_power_db: self.tx_peak_db,
+            crest_db: self.tx_crest_db(),
+        }))
+    }
+
+    /// End a transmission still in progress at end of stream or on disconnect, returning it if long enough
+    /// The noise floor and stream position are kept, so detection can resume on a reconnected stream
+    fn flush(&mut self) -> Option<Transmission> {
+        let long_enough = self.tx_continued || self.tx_buffer.len() >= self.min_duration_samples;
+        let in_transmission = std::mem::replace(&mut self.in_transmission, false);
+        self.silence_counter = 0;
+        let samples = std::mem::take(&mut self.tx_buffer);
+        (in_transmission && long_enough && !samples.is_empty()).then(|| Transmission {
+            start_sample: self.tx_start,
+            crest_db: self.tx_crest_db(),
+            samples,
+            peak_power_db: self.tx_peak_db,
+        })
+    }
+
+    /// Continue after `gap` without samples: advance the stream position by the missed time,
+    /// so slice timestamps stay in step with the wall clock, and drop the now discontinuous pre-buffer
+    fn resume_after(&mut self, gap: Duration) {
+        self.position += (gap.as_secs_f64() * self.sample_rate as f64) as usize;
+        self.pre_buffer.clear();
+        match self.noise_floor_db {
+            Some(floor) => info!("{}Resumed, floor={:.1} dB", self.label(), floor),
+            None => info!("{}Resumed, noise floor not yet measured", self.label()),
+        }
+    }
+
+    fn add_tx_power(&mut self, power_db: f32) {
+        if power_db.is_finite() {
+            self.tx_power_sum += 10f64.powf(power_db as f64 / 10.0);
+            self.tx_power_chunks += 1;
+        }
+    }
+
+    /// Peak minus mean chunk power of the current transmission (dB)
+    fn tx_crest_db(&self) -> Option<f32> {
+        (self.tx_power_chunks > 0 && self.tx_peak_db.is_finite()).then(|| {
+            let mean = self.tx_power_sum / self.tx_power_chunks as f64;
+            self.tx_peak_db - 10.0 * mean.log10() as f32
+        })
+    }
+}
+
+/// Slice samples with the `--notch-output` filter, `--correct-cfo` shift and `--fade-ms` edge taper applied,
+/// borrowed unchanged when all are off. `center_hz` is the slice's center relative to the tuned frequency.
+fn output_samples<'a>(config: &SliceConfig, slice: &'a SlicedSegment, sample_rate: u32, center_hz: f64) -> Cow<'a, [IqSample]> {
+    let notch = config.notch_output && !config.notch_hz.is_empty();
+    let fade_len = (config.fade_ms as f64 * sample_rate as f64 / 1000.0) as usize;
+    if !notch && slice.cfo_hz.is_none() && fade_len == 0 {
+        return Cow::Borrowed(&slice.samples);
+    }
+    let mut shaped = slice.samples.clone();
+    if notch {
+        let notches: Vec<f64> = config.notch_hz.iter().map(|hz| hz - center_hz).collect();
+        notch_filter(&mut shaped, sample_rate, &notches);
+    }
+    if let Some(cfo_hz) = slice.cfo_hz {
+        remove_frequency_offset(&mut shaped, sample_rate, cfo_hz);
+    }
+    apply_fade(&mut shaped, fade_len);
+    Cow::Owned(shaped)
+}
+
+/// Linear gain from `--normalize` for one slice, or None when normalization is off
+fn slice_normalize_gain(config: &SliceConfig, samples: &[IqSample]) -> Option<f32> {
+    let mode = config.normalize?;
+    let gain = normalization_gain(samples, mode, config.normalize_target_db);
+    debug!("    Normalize gain: {:+.1} dB", 20.0 * gain.log10());
+    Some(gain)
+}
+
+/// Demodulate a slice (if configured) and write the audio next to it as `<slice>.<mode>.wav`
+fn write_demod_audio(
+    config: &SliceConfig,
+    slice_path: &Path,
+    samples: &[IqSample],
+    sample_rate: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mode) = config.demod else {
+        return Ok(());
+    };
+    let suffix = match mode {
+        Demodulation::Fm => "fm",
+        Demodulation::Am => "am",
+    };
+    let (audio, audio_rate) = demodulate(samples, sample_rate, config.audio_rate, mode);
+    let audio_path = slice_path.with_extension(format!("{}.wav", suffix));
+    write_audio_wav(&audio_path, &audio, audio_rate)?;
+    debug!("    Wrote audio: {}", audio_path.display());
+    Ok(())
+}
+
+/// Resolve where a slice goes; with --planar the name stands for its I and Q pair
+/// Paths in `queued` are taken as well, for slices handed to the writer thread but not yet on disk
+fn resolve_slice_path(
+    config: &SliceConfig,
+    path: &Path,
+    queued: &HashSet<PathBuf>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    resolve_path_with(path, config.overwrite, |path| {
+        if queued.contains(path) {
+            Some(path.to_path_buf())
+        } else if config.planar {
+            planar_paths(path).into_iter().find(|p| p.exists())
+        } else {
+            path.exists().then(|| path.to_path_buf())
+        }
+    })
+}
+
+/// Write a slice as one file, or as separate I and Q files with --planar, returning clipped values
+/// (None for float formats)
+fn write_slice_file(
+    config: &SliceConfig,
+    path: &Path,
+    samples: &[IqSample],
+    sample_rate: u32,
+    gain: f32,
+) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    if config.planar {
+        config.slice_writer.write_planar(path, samples, sample_rate, gain)
+    } else {
+        config.slice_writer.write(path, samples, sample_rate, gain, config.channel_order)
+    }
+}
+
+/// Paths and names of the files written for the slice named `filename` at `path`
+fn slice_files(config: &SliceConfig, path: &Path, filename: &str) -> Vec<(PathBuf, String)> {
+    if !config.planar {
+        return vec![(path.to_path_buf(), filename.to_string())];
+    }
+    planar_paths(path)
+        .into_iter()
+        .zip(planar_paths(Path::new(filename)))
+        .map(|(path, name)| (path, name.to_string_lossy().into_owned()))
+        .collect()
+}
+
+fn low_space_message(output_dir: &Path, min_free_bytes: u64) -> String {
+    format!(
+        "Free space on {} is below {} MB, stopping before writing more slices",
+        output_dir.display(),
+        min_free_bytes / (1024 * 1024)
+    )
+}
+
+/// Slices waiting for the writer thread before the detection loop has to wait for disk
+const STREAM_WRITE_QUEUE_SLICES: usize = 8;
+
+/// A stream slice named on the detection thread, waiting to be written
+struct SliceJob {
+    slice: SlicedSegment,
+    sample_rate: u32,
+    channel_hz: Option<f64>,
+    output_path: PathBuf,
+    /// Name relative to the output directory, including any date folder
+    filename: String,
+    /// Wall-clock time of the slice's first sample
+    started: DateTime<Local>,
+}
+
+/// A slice the writer thread has finished, handed back to the detection thread
+struct WrittenSlice {
+    slice: SlicedSegment,
+    output_path: PathBuf,
+    channel_hz: Option<f64>,
+    clipped: Option<usize>,
+    record: SliceRecord,
+}
+
+/// Writes stream slices on a background thread so the detection loop never waits on disk
+/// Slices are written one at a time, so at most one slice file is open, and at most
+/// `STREAM_WRITE_QUEUE_SLICES` wait in the queue
+struct SliceWriterThread {
+    jobs: Option<SyncSender<SliceJob>>,
+    done: Receiver<Result<WrittenSlice, String>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SliceWriterThread {
+    fn spawn(config: &SliceConfig) -> Self {
+        let (jobs, job_queue) = sync_channel::<SliceJob>(STREAM_WRITE_QUEUE_SLICES);
+        let (done_sender, done) = channel();
+        let config = config.clone();
+        let handle = std::thread::spawn(move || {
+            let mut manifest = Manifest::default();
+            for job in job_queue {
+                let result = write_stream_slice(&config, &mut manifest, job).map_err(|e| e.to_string());
+                if done_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs: Some(jobs),
+            done,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue a slice for writing, waiting only when the queue is full
+    fn submit(&self, job: SliceJob) -> Result<(), Box<dyn std::error::Error>> {
+        let jobs = self.jobs.as_ref().ok_or("slice writer already finished")?;
+        let job = match jobs.try_send(job) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Full(job)) => job,
+            Err(TrySendError::Disconnected(_)) => return Err("slice writer thread stopped".into()),
+        };
+        warn!("Slice writing is falling behind, waiting for disk");
+        jobs.send(job).map_err(|_| "slice writer thread stopped".into())
+    }
+
+    /// Slices finished since the last call, without waiting
+    fn completed(&self) -> Vec<Result<WrittenSlice, String>> {
+        self.done.try_iter().collect()
+    }
+
+    /// Wait for every queued slice to be written and stop the thread
+    fn finish(&mut self) -> Vec<Result<WrittenSlice, String>> {
+        self.jobs = None;
+        let results = self.done.iter().collect();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        results
+    }
+}
+
+impl Drop for SliceWriterThread {
+    /// Let queued slices finish writing even when the stream ends with an error
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Write one stream slice (and its demodulated audio and manifest entry) on the writer thread
+fn write_stream_slice(
+    config: &SliceConfig,
+    manifest: &mut Manifest,
+    job: SliceJob,
+) -> Result<WrittenSlice, Box<dyn std::error::Error>> {
+    let SliceJob {
+        slice,
+        sample_rate,
+        channel_hz,
+        output_path,
+        filename,
+        started,
+    } = job;
+    let samples = &*output_samples(config, &slice, sample_rate, channel_hz.unwrap_or(0.0));
+
+    let normalize_gain = slice_normalize_gain(config, samples);
+    let clipped = write_slice_file(
+        config,
+        &output_path,
+        samples,
+        sample_rate,
+        config.output_gain * normalize_gain.unwrap_or(1.0),
+    )?;
+    write_demod_audio(config, &output_path, samples, sample_rate)?;
+
+    let duration_ms = samples.len() as f32 / sample_rate as f32 * 1000.0;
+    info!("Saved: {} ({:.1}ms)", filename, duration_ms);
+
+    // Rewrite the manifest after every slice so an interrupted session still has a valid index
+    let files = slice_files(config, &output_path, &filename);
+    if let Some(manifest_path) = &config.manifest_path {
+        for (path, name) in &files {
+            manifest.add(ManifestEntry {
+                filename: name.clone(),
+                sha256: sha256_file(path)?,
+                samples: samples.len(),
+                duration_ms,
+                timestamp: started.to_rfc3339(),
+            });
+        }
+        manifest.write(manifest_path)?;
+    }
+
+    let record = SliceRecord {
+        filename,
+        planar_filenames: config.planar.then(|| files.into_iter().map(|(_, name)| name).collect()),
+        start_seconds: None,
+        start_sample: slice.segment.start_sample,
+        end_sample: slice.segment.end_sample,
+        duration_ms,
+        peak_power_db: slice.peak_power_db,
+        crest_db: slice.segment.crest_db,
+        center_hz: slice.center_hz,
+        frequency_hz: config
+            .center_frequency
+            .zip(slice.center_hz)
+            .map(|(tuned, offset)| tuned + channel_hz.unwrap_or(0.0) + offset),
+        bandwidth_hz: slice.bandwidth_hz,
+        symbol_rate_hz: slice.symbol_rate_hz,
+        cfo_hz: slice.cfo_hz,
+        normalize_gain_db: normalize_gain.map(|gain| 20.0 * gain.log10()),
+    };
+    if config.sidecar {
+        SliceSidecar {
+            sample_rate,
+            start_time: started.to_rfc3339(),
+            record: &record,
+        }
+        .write(&output_path)?;
+    }
+    Ok(WrittenSlice {
+        slice,
+        output_path,
+        channel_hz,
+        clipped,
+        record,
+    })
+}
+
+/// Names finished stream transmissions, hands them to the writer thread and tracks per-run results
+struct StreamOutput<'a> {
+    config: &'a SliceConfig,
+    output_dir: &'a Path,
+    date_folders: bool,
+    /// Wall-clock time of the first stream sample, set when the first chunk arrives; slice times are
+    /// offsets from it, as in file mode
+    stream_start: Option<DateTime<Local>>,
+    slice_counter: usize,
+    stats: RunStats,
+    writer: SliceWriterThread,
+    /// Paths of slices queued on the writer thread and not yet reported written
+    queued: HashSet<PathBuf>,
+}
+
+impl<'a> StreamOutput<'a> {
+    fn new(config: &'a SliceConfig, output_dir: &'a Path, date_folders: bool) -> Self {
+        Self {
+            config,
+            output_dir,
+            date_folders,
+            stream_start: None,
+            slice_counter: 0,
+            stats: RunStats::default(),
+            writer: SliceWriterThread::spawn(config),
+            queued: HashSet::new(),
+        }
+    }
+
+    /// Note that a chunk of `len` samples just arrived, stamping the stream start on the first one
+    /// Waiting for a sender and detecting the format can take a while, so stamping at startup would run early
+    fn record_chunk(&mut self, len: usize, sample_rate: u32) {
+        if self.stream_start.is_none() {
+            let duration = chrono::Duration::microseconds((len as f64 / sample_rate as f64 * 1e6) as i64);
+            self.stream_start = Some(Local::now() - duration);
+        }
+    }
+
+    /// Name one transmission as the next slice and queue it for writing
+    /// Channelized slices are named by their channel's center frequency
+    fn save(
+        &mut self,
+        slice: SlicedSegment,
+        sample_rate: u32,
+        channel_hz: Option<f64>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.slice_counter += 1;
+        let stream_start = *self.stream_start.get_or_insert_with(Local::now);
+
+        // Stamp slices with when they started: stream start plus the samples consumed before them
+        let start_seconds = slice.segment.start_sample as f64 / sample_rate as f64;
+        let started = stream_start + chrono::Duration::milliseconds((start_seconds * 1000.0) as i64);
+        let template = match channel_hz {
+            Some(_) => self.config.name_template.with_freq(),
+            None => self.config.name_template.clone(),
+        };
+        let filename = generate_filename(
+            &template,
+            &SliceName {
+                index: self.config.first_index + self.slice_counter - 1,
+                start_sample: slice.segment.start_sample,
+                sample_rate,
+                base_time: stream_start,
+                peak_power_db: Some(slice.peak_power_db),
+                crest_db: slice.segment.crest_db,
+                frequency_hz: channel_hz
+                    .or(slice.located_center_hz(sample_rate))
+                    .map(|offset| offset + self.config.center_frequency.unwrap_or(0.0)),
+            },
+            self.config.slice_writer.extension(),
+        );
+
+        let slice_dir = if self.date_folders {
+            let dir = self.output_dir.join(started.format("%Y-%m-%d").to_string());
+            std::fs::create_dir_all(&dir)?;
+            dir
+        } else {
+            self.output_dir.to_path_buf()
+        };
+        let output_path = resolve_slice_path(self.config, &slice_dir.join(&filename), &self.queued)?;
+        self.queued.insert(output_path.clone());
+
+        // Name relative to the output directory, including any date folder
+        let filename = output_path
+            .strip_prefix(self.output_dir)
+            .unwrap_or(&output_path)
+            .to_string_lossy()
+            .into_owned();
+
+        self.writer.submit(SliceJob {
+            slice,
+            sample_rate,
+            channel_hz,
+            output_path,
+            filename,
+            started,
+        })
+    }
+
+    /// Record slices the writer thread has finished, sending their end events and calling `on_slice`
+    /// With `wait`, blocks until every queued slice is written
+    fn collect_written(
+        &mut self,
+        wait: bool,
+        events: &mut Option<EventSink>,
+        on_slice: &mut impl FnMut(&SlicedSegment),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let results = if wait { self.writer.finish() } else { self.writer.completed() };
+        for result in results {
+            let written = result?;
+            self.queued.remove(&written.output_path);
+            self.stats.record_clipping(written.slice.samples.len(), written.clipped);
+            if let Some(sink) = events.as_mut() {
+                sink.send(&Event::End {
+                    time: Event::now(),
+                    filename: Some(&written.record.filename),
+                    duration_ms: written.record.duration_ms,
+                    peak_db: written.record.peak_power_db,
+                    channel_hz: written.channel_hz,
+                });
+            }
+            on_slice(&written.slice);
+            self.stats.record(written.record);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    /// Quiet noise with a strong tone over each of `bursts` (sample ranges)
+    fn synthetic(len: usize, bursts: &[(usize, usize)]) -> Vec<IqSample> {
+        let mut state = 0x2545_f491_u32;
+        let mut noise = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        };
+        (0..len)
+            .map(|n| {
+                let mut sample = IqSample::new(0.01 * noise(), 0.01 * noise());
+                if bursts.iter().any(|&(start, end)| (start..end).contains(&n)) {
+                    let phase = 2.0 * std::f32::consts::PI * 0.05 * n as f32;
+                    sample.i += 0.5 * phase.cos();
+                    sample.q += 0.5 * phase.sin();
+                }
+                sample
+            })
+            .collect()
+    }
+
+    fn metadata(samples: &[IqSample]) -> IqMetadata {
+        IqMetadata {
+            sample_rate: RATE,
+            total_samples: Some(samples.len()),
+            saturated_samples: 0,
+            center_frequency: None,
+            start_time: None,
+        }
+    }
+
+    /// Two bursts, at 0.5-1.5 s and 2.5-3.2 s of a 4 s recording
+    fn two_bursts() -> Vec<IqSample> {
+        synthetic(4 * RATE as usize, &[(RATE as usize / 2, 3 * RATE as usize / 2), (5 * RATE as usize / 2, 16 * RATE as usize / 5)])
+    }
+
+    #[test]
+    fn process_samples_slices_in_memory_input() {
+        let samples = two_bursts();
+        let dir = tempfile::tempdir().unwrap();
+        let mut ranges = Vec::new();
+        let stats = process_samples(&samples, metadata(&samples), dir.path(), &SliceConfig::default(), |slice| {
+            ranges.push((slice.segment.start_sample, slice.segment.end_sample))
+        })
+        .unwrap();
+
+        assert_eq!(stats.slice_count(), 2);
+        assert_eq!(ranges.len(), 2);
+        // Each slice covers its burst plus up to the 100 ms padding on either side
+        let padding = RATE as usize / 10;
+        for (&(start, end), (burst_start, burst_end)) in ranges.iter().zip([(24_000, 72_000), (120_000, 153_600)]) {
+            assert!(start <= burst_start && start + padding + 2048 >= burst_start, "start {} for burst at {}", start, burst_start);
+            assert!(end >= burst_end && end <= burst_end + padding + 2048, "end {} for burst ending {}", end, burst_end);
+        }
+        let written: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(written.len(), 2);
+        assert!(written.iter().all(|name| name.to_string_lossy().starts_with("slice_000")));
+    }
+
+    #[test]
+    fn slice_to_memory_returns_slices_with_their_samples() {
+        let samples = two_bursts();
+        let (slices, activity) = slice_to_memory(&samples, &metadata(&samples), &SliceConfig::default()).unwrap();
+
+        assert_eq!(slices.len(), 2);
+        for slice in &slices {
+            assert_eq!(slice.samples.len(), slice.segment.duration_samples());
+            // The bursts are a tone at 0.05 of the sample rate
+            let center_hz = slice.center_hz.unwrap();
+            assert!((center_hz - 0.05 * RATE as f64).abs() < 100.0, "center {} Hz", center_hz);
+            assert!(slice.peak_power_db > -20.0);
+        }
+        assert!((activity.signal_seconds - 1.7).abs() < 0.1, "signal {} s", activity.signal_seconds);
+    }
+
+    /// Write samples as a stereo 16-bit WAV
+    fn write_wav(path: &Path, samples: &[IqSample]) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for sample in samples {
+            writer.write_sample((sample.i * 32767.0) as i16).unwrap();
+            writer.write_sample((sample.q * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    /// Every file in `dir` by name, with its contents
+    fn read_outputs(dir: &Path) -> Vec<(std::ffi::OsString, Vec<u8>)> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.file_name(), std::fs::read(entry.path()).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn gzipped_raw_input_slices_like_plain() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bytes: Vec<u8> = two_bursts()
+            .iter()
+            .flat_map(|s| [(s.i * 32767.0) as i16, (s.q * 32767.0) as i16])
+            .flat_map(i16::to_le_bytes)
+            .collect();
+        let plain = dir.path().join("capture.cs16");
+        std::fs::write(&plain, &bytes).unwrap();
+        let gzipped = dir.path().join("capture.cs16.gz");
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&gzipped).unwrap(), flate2::Compression::fast());
+        encoder.write_all(&bytes).unwrap();
+        encoder.finish().unwrap();
+
+        let config = SliceConfig {
+            base_time: BaseTime::Fixed(Local::now()),
+            ..SliceConfig::default()
+        };
+        let options = WavOptions { sample_rate: Some(RATE), ..WavOptions::default() };
+        let slice = |input: &Path, name: &str| {
+            let output_dir = dir.path().join(name);
+            std::fs::create_dir(&output_dir).unwrap();
+            let stats = process_file(input, &output_dir, &config, &options).unwrap();
+            assert_eq!(stats.slice_count(), 2);
+            read_outputs(&output_dir)
+        };
+        assert_eq!(slice(&plain, "plain"), slice(&gzipped, "gzipped"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_wav_from_a_fifo_until_the_writer_closes_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("input.wav");
+        write_wav(&file, &two_bursts());
+        let fifo = dir.path().join("input.fifo");
+        assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+        // Opening a FIFO for writing blocks until the slicer opens it for reading
+        let bytes = std::fs::read(&file).unwrap();
+        let writer_path = fifo.clone();
+        let writer = std::thread::spawn(move || std::fs::write(writer_path, bytes).unwrap());
+        let config = SliceConfig {
+            base_time: BaseTime::Fixed(Local::now()),
+            ..SliceConfig::default()
+        };
+        let from_fifo = dir.path().join("fifo");
+        std::fs::create_dir(&from_fifo).unwrap();
+        let stats = process_file(&fifo, &from_fifo, &config, &WavOptions::default()).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(stats.slice_count(), 2);
+        let from_file = dir.path().join("file");
+        std::fs::create_dir(&from_file).unwrap();
+        process_file(&file, &from_file, &config, &WavOptions::default()).unwrap();
+        assert_eq!(read_outputs(&from_fifo), read_outputs(&from_file));
+    }
+
+    #[test]
+    fn process_file_with_calls_back_for_each_written_slice() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.wav");
+        write_wav(&input, &two_bursts());
+        let output_dir = dir.path().join("slices");
+        std::fs::create_dir(&output_dir).unwrap();
+
+        let mut seen = Vec::new();
+        let stats = process_file_with(&input, &output_dir, &SliceConfig::default(), &WavOptions::default(), |slice| {
+            // Called after the slice is written, so its file is already there
+            let written = std::fs::read_dir(&output_dir).unwrap().count();
+            seen.push((slice.segment.start_sample, slice.samples.len(), written));
+        })
+        .unwrap();
+
+        assert_eq!(stats.slice_count(), 2);
+        assert_eq!(seen.len(), 2);
+        assert!(seen[0].0 < seen[1].0);
+        assert_eq!(seen[0].2, 1);
+        assert_eq!(seen[1].2, 2);
+    }
+
+    #[test]
+    fn process_stream_with_calls_back_as_transmissions_end() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let bytes: Vec<u8> = two_bursts()
+                .iter()
+                .flat_map(|s| [(s.i * 32767.0) as i16, (s.q * 32767.0) as i16])
+                .flat_map(i16::to_le_bytes)
+                .collect();
+            std::io::Write::write_all(&mut socket, &bytes).unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let stream_config = StreamConfig {
+            format: Some(StreamFormat::Int16),
+            ..StreamConfig::default()
+        };
+        let mut seen = Vec::new();
+        process_stream_with(&addr, dir.path(), &SliceConfig::default(), &stream_config, |slice| {
+            seen.push(slice.segment.duration_samples())
+        })
+        .unwrap();
+        server.join().unwrap();
+
+        // Both bursts (1 s and 0.7 s) end before the stream does
+        assert_eq!(seen.len(), 2);
+        assert!(seen[0] > seen[1]);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    /// Quiet profile with a one-frame blip at frame 10 and a 20-frame burst from frame 40
+    fn blip_profile() -> Vec<f32> {
+        let mut profile = vec![-60.0; 100];
+        profile[10] = -10.0;
+        profile[40..60].iter_mut().for_each(|p| *p = -10.0);
+        profile
+    }
+
+    #[test]
+    fn debounce_suppresses_single_frame_blip() {
+        let window = 48;
+        let profile = blip_profile();
+        let total = profile.len() * window / 2 + window;
+        let detect = |debounce| detect_segments(&profile, total, window, -30.0, 0, 0, debounce);
+
+        let undebounced = detect(1);
+        assert_eq!(undebounced.len(), 2);
+        assert_eq!(undebounced[0].start_sample, 10 * window / 2);
+        for debounce in [2, 3] {
+            let segments = detect(debounce);
+            assert_eq!(segments.len(), 1, "debounce {}", debounce);
+            // The burst still starts at its first frame and ends at its last one
+            assert_eq!(segments[0].start_sample, 40 * window / 2);
+            assert_eq!(segments[0].end_sample, 60 * window / 2 + window);
+        }
+    }
+
+    #[test]
+    fn empty_wav_yields_no_slices() {
+        let dir = tempfile::tempdir().unwrap();
+        let header_only = dir.path().join("header_only.wav");
+        write_wav(&header_only, &[]);
+        let zero_bytes = dir.path().join("zero_bytes.wav");
+        std::fs::File::create(&zero_bytes).unwrap();
+        let output_dir = dir.path().join("slices");
+        std::fs::create_dir(&output_dir).unwrap();
+
+        for input in [header_only, zero_bytes] {
+            let stats = process_file(&input, &output_dir, &SliceConfig::default(), &WavOptions::default()).unwrap();
+            assert_eq!(stats.slice_count(), 0, "{}", input.display());
+        }
+        assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn queued_slice_paths_count_as_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slice_001.wav");
+        let queued = HashSet::from([path.clone()]);
+
+        let rename = SliceConfig { overwrite: OverwritePolicy::Rename, ..SliceConfig::default() };
+        assert_eq!(resolve_slice_path(&rename, &path, &queued).unwrap(), dir.path().join("slice_001_1.wav"));
+        assert_eq!(resolve_slice_path(&rename, &path, &HashSet::new()).unwrap(), path);
+        let error = SliceConfig { overwrite: OverwritePolicy::Error, ..SliceConfig::default() };
+        assert!(resolve_slice_path(&error, &path, &queued).is_err());
+    }
+
+    #[test]
+    fn parameter_hash_covers_slicing_parameters_only() {
+        let config = SliceConfig::default();
+        let options = WavOptions::default();
+        let hash = parameter_hash(&config, &options);
+
+        let reporting = SliceConfig { report_path: Some(PathBuf::from("report.json")), ndjson: true, ..config.clone() };
+        assert_eq!(parameter_hash(&reporting, &options), hash);
+        let longer = SliceConfig { min_duration_ms: 600, ..config.clone() };
+        assert_ne!(parameter_hash(&longer, &options), hash);
+        assert_ne!(parameter_hash(&config, &WavOptions { conjugate: true, ..options }), hash);
+        assert_eq!(config.name_template.to_string(), "slice_{index:04}_{freq_khz}_{timestamp}");
+    }
+
+    #[test]
+    fn stream_crest_leaves_out_the_closing_gap() {
+        // A steady tone from 0.5 s to 1.5 s, then quiet until the 200 ms gap closes the transmission
+        let samples = synthetic(2 * RATE as usize, &[(RATE as usize / 2, 3 * RATE as usize / 2)]);
+        let config = SliceConfig::default();
+        let mut detector = StreamDetector::new(&config, RATE, &StreamConfig::default(), None);
+        let mut planner = FftPlanner::new();
+
+        let chunk = RATE as usize / 100;
+        let ended = samples
+            .chunks(chunk)
+            .find_map(|chunk| match detector.process(chunk.to_vec(), &mut planner) {
+                Some(DetectorEvent::Ended(transmission)) => Some(transmission),
+                _ => None,
+            })
+            .expect("transmission should end within the input");
+        // Every kept chunk has the same power, so peak and mean agree
+        let crest = ended.crest_db.unwrap();
+        assert!(crest.abs() < 0.3, "crest {} dB", crest);
+    }
+
+    #[test]
+    fn stream_transmission_starts_at_its_padding_without_repeats() {
+        // A burst starting on a chunk boundary at 0.6 s, after the 0.5 s initial floor measurement
+        let samples = synthetic(2 * RATE as usize, &[(28_800, RATE as usize)]);
+        let mut detector = StreamDetector::new(&SliceConfig::default(), RATE, &StreamConfig::default(), None);
+        let mut planner = FftPlanner::new();
+
+        let ended = samples
+            .chunks(RATE as usize / 100)
+            .find_map(|chunk| match detector.process(chunk.to_vec(), &mut planner) {
+                Some(DetectorEvent::Ended(transmission)) => Some(transmission),
+                _ => None,
+            })
+            .expect("transmission should end within the input");
+        // The 100 ms padding reaches back from the triggering chunk, and the samples follow the input exactly
+        assert_eq!(ended.start_sample, 28_800 - 4_800);
+        let source = &samples[ended.start_sample..ended.start_sample + ended.samples.len()];
+        assert!(ended.samples.iter().zip(source).all(|(a, b)| (a.i, a.q) == (b.i, b.q)));
+    }
+
+    #[test]
+    fn percentile_threshold_comes_from_earlier_chunks_only() {
+        // At the 100th percentile, a chunk counted in its own history could never clear the threshold
+        let samples = synthetic(2 * RATE as usize, &[(RATE as usize, 3 * RATE as usize / 2)]);
+        let stream_config = StreamConfig {
+            threshold_mode: StreamThreshold::Percentile { percentile: 100.0, window_seconds: 0.05 },
+            ..StreamConfig::default()
+        };
+        let mut detector = StreamDetector::new(&SliceConfig::default(), RATE, &stream_config, None);
+        let mut planner = FftPlanner::new();
+
+        let ended: Vec<Transmission> = samples
+            .chunks(RATE as usize / 100)
+            .filter_map(|chunk| match detector.process(chunk.to_vec(), &mut planner) {
+                Some(DetectorEvent::Ended(transmission)) => Some(transmission),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ended.len(), 1);
+        let end = ended[0].start_sample + ended[0].samples.len();
+        assert!(end >= 3 * RATE as usize / 2, "ends at {}", end);
+        // Chunks inside the transmission stay out of the history
+        assert!(detector.recent_powers.iter().all(|&power| power < -40.0), "{:?}", detector.recent_powers);
+    }
+
+    #[test]
+    fn stream_floor_ignores_a_transmission_on_air_at_startup() {
+        // Already transmitting for the first 200 ms, then a second burst at 0.6-1.0 s
+        let samples = synthetic(2 * RATE as usize, &[(0, RATE as usize / 5), (28_800, RATE as usize)]);
+        let mut detector = StreamDetector::new(&SliceConfig::default(), RATE, &StreamConfig::default(), None);
+        let mut planner = FftPlanner::new();
+
+        // The floor is the median of the first 0.5 s, not the loud first chunk, so the second burst is caught
+        let ended: Vec<Transmission> = samples
+            .chunks(RATE as usize / 100)
+            .filter_map(|chunk| match detector.process(chunk.to_vec(), &mut planner) {
+                Some(DetectorEvent::Ended(transmission)) => Some(transmission),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ended.len(), 1);
+        let start = ended[0].start_sample;
+        assert!((23_040..=28_800).contains(&start), "start {}", start);
+    }
+
+    #[test]
+    fn concat_output_follows_the_overwrite_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.wav");
+        write_wav(&input, &two_bursts());
+        let output_dir = dir.path().join("slices");
+        std::fs::create_dir(&output_dir).unwrap();
+        let concat = dir.path().join("all.wav");
+        std::fs::write(concat.with_extension("txt"), "earlier labels").unwrap();
+
+        let config = SliceConfig { concat_path: Some(concat.clone()), ..SliceConfig::default() };
+        assert!(process_file(&input, &output_dir, &config, &WavOptions::default()).is_err());
+        assert!(!concat.exists(), "nothing should be written when the overwrite is refused");
+
+        let rename = SliceConfig { overwrite: OverwritePolicy::Rename, ..config };
+        process_file(&input, &output_dir, &rename, &WavOptions::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(concat.with_extension("txt")).unwrap(), "earlier labels");
+        assert!(dir.path().join("all_1.wav").exists() && dir.path().join("all_1.txt").exists());
+    }
+
+    #[test]
+    fn annotated_copy_labels_markers_like_the_slices() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.wav");
+        write_wav(&input, &two_bursts());
+        let output_dir = dir.path().join("slices");
+        std::fs::create_dir(&output_dir).unwrap();
+        let annotated = dir.path().join("annotated.wav");
+
+        let config = SliceConfig {
+            annotate_path: Some(annotated.clone()),
+            name_template: NameTemplate::standard("cap", 2),
+            first_index: 7,
+            ..SliceConfig::default()
+        };
+        process_file(&input, &output_dir, &config, &WavOptions::default()).unwrap();
+        let bytes = std::fs::read(&annotated).unwrap();
+        let contains = |text: &[u8]| bytes.windows(text.len()).any(|window| window == text);
+        assert!(contains(b"cap_07 (") && contains(b"cap_08 ("));
+
+        // A second run refuses to replace the annotated copy unless told to
+        let rerun_dir = dir.path().join("rerun");
+        std::fs::create_dir(&rerun_dir).unwrap();
+        assert!(process_file(&input, &rerun_dir, &config, &WavOptions::default()).is_err());
+        assert_eq!(std::fs::read(&annotated).unwrap(), bytes);
+    }
+
+    #[test]
+    fn converting_to_the_same_format_keeps_sample_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().join("in");
+        std::fs::create_dir(&input_dir).unwrap();
+        for (bits, values) in [(16u16, vec![32767i32, -32768, 1234, -1]), (8, vec![127, -128, 45, -1])] {
+            let spec = hound::WavSpec { channels: 2, sample_rate: RATE, bits_per_sample: bits, sample_format: hound::SampleFormat::Int };
+            let mut writer = hound::WavWriter::create(input_dir.join(format!("int{}.wav", bits)), spec).unwrap();
+            for &value in &values {
+                writer.write_sample(value).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            let output_dir = dir.path().join(format!("out{}", bits));
+            let slice_writer = if bits == 8 { SliceWriter::WavInt8 } else { SliceWriter::WavInt16 };
+            convert_slices(&input_dir, &output_dir, slice_writer, ChannelOrder::Iq, OverwritePolicy::Error).unwrap();
+            let converted: Vec<i32> = hound::WavReader::open(output_dir.join(format!("int{}.wav", bits)))
+                .unwrap()
+                .samples::<i32>()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(converted, values, "int{}", bits);
+            std::fs::remove_file(input_dir.join(format!("int{}.wav", bits))).unwrap();
+        }
+    }
+
+    /// The sidecars in `dir` as (start_sample, end_sample, sample_rate), in slice order
+    fn read_sidecars(dir: &Path) -> Vec<(u64, u64, u64)> {
+        let mut sidecars: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        sidecars.sort();
+        sidecars
+            .iter()
+            .map(|path| {
+                let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+                let field = |name: &str| json[name].as_u64().unwrap();
+                assert!(json["start_time"].is_string() && json["filename"].is_string());
+                (field("start_sample"), field("end_sample"), field("sample_rate"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sidecars_record_each_slice_source_range() {
+        let samples = two_bursts();
+        let dir = tempfile::tempdir().unwrap();
+        let config = SliceConfig { sidecar: true, ..SliceConfig::default() };
+        let mut ranges = Vec::new();
+        process_samples(&samples, metadata(&samples), dir.path(), &config, |slice| {
+            ranges.push((slice.segment.start_sample as u64, slice.segment.end_sample as u64, RATE as u64))
+        })
+        .unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(read_sidecars(dir.path()), ranges);
+
+        // Stream slices record their range since the stream started
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let bytes: Vec<u8> = samples
+                .iter()
+                .flat_map(|s| [(s.i * 32767.0) as i16, (s.q * 32767.0) as i16])
+                .flat_map(i16::to_le_bytes)
+                .collect();
+            std::io::Write::write_all(&mut socket, &bytes).unwrap();
+        });
+        let stream_dir = dir.path().join("stream");
+        std::fs::create_dir(&stream_dir).unwrap();
+        let stream_config = StreamConfig { format: Some(StreamFormat::Int16), ..StreamConfig::default() };
+        process_stream(&addr, &stream_dir, &config, &stream_config).unwrap();
+        server.join().unwrap();
+
+        let stream_ranges = read_sidecars(&stream_dir);
+        assert_eq!(stream_ranges.len(), 2);
+        for ((start, end, rate), (file_start, file_end, _)) in stream_ranges.into_iter().zip(ranges) {
+            assert_eq!(rate, RATE as u64);
+            // Stream detection triggers on the 10 ms chunk holding the onset, so it starts up to a chunk
+            // after file mode's 1 ms frames do; the end waits out the gap in whole chunks
+            let chunk = RATE as u64 / 100;
+            assert!(start >= file_start && start - file_start <= chunk && end.abs_diff(file_end) <= 2 * RATE as u64 / 5, "{}..{} vs {}..{}", start, end, file_start, file_end);
+        }
+    }
+
+    #[test]
+    fn merged_overlaps_survive_max_duration_drop() {
+        // Two 0.5 s bursts 40 ms apart; their 100 ms padding overlaps, and merged they exceed 0.8 s
+        let samples = synthetic(2 * RATE as usize, &[(24_000, 48_000), (49_920, 73_920)]);
+        let config = SliceConfig {
+            min_duration_ms: 100,
+            gap_ms: 20,
+            merge_gap_ms: 0,
+            max_duration_ms: Some(800),
+            overlap_policy: OverlapPolicy::Merge,
+            ..SliceConfig::default()
+        };
+        let (slices, _) = slice_to_memory(&samples, &metadata(&samples), &config).unwrap();
+        assert_eq!(slices.len(), 1);
+        let segment = &slices[0].segment;
+        assert!(segment.start_sample <= 24_000 && segment.end_sample >= 73_920, "{}..{}", segment.start_sample, segment.end_sample);
+    }
+
+    #[test]
+    fn dc_guard_sees_bursts_past_a_wide_dc_spike() {
+        // A strong offset wandering around DC, well above the bursts' tone at 0.05 of the sample rate
+        let mut samples = two_bursts();
+        for (n, sample) in samples.iter_mut().enumerate() {
+            let phase = 2.0 * std::f32::consts::PI * 0.004 * n as f32;
+            sample.i += 2.0 + phase.cos();
+            sample.q += phase.sin();
+        }
+        let slices_with_guard = |dc_guard_bins| {
+            let config = SliceConfig { dc_guard_bins, ..SliceConfig::default() };
+            slice_to_memory(&samples, &metadata(&samples), &config).unwrap().0
+        };
+
+        // Skipping only the DC bin leaves the spike's skirt to swamp the bursts
+        assert_ne!(slices_with_guard(1).len(), 2);
+        let slices = slices_with_guard(3);
+        assert_eq!(slices.len(), 2);
+        for (slice, (burst_start, burst_end)) in slices.iter().zip([(24_000, 72_000), (120_000, 153_600)]) {
+            let segment = &slice.segment;
+            assert!(segment.start_sample <= burst_start && segment.end_sample >= burst_end, "{}..{}", segment.start_sample, segment.end_sample);
+        }
+
+        // The 1 ms window at 48 kHz is a 48-point FFT, so a guard of 24 bins leaves nothing
+        let config = SliceConfig { dc_guard_bins: 24, ..SliceConfig::default() };
+        let err = slice_to_memory(&samples, &metadata(&samples), &config).unwrap_err();
+        assert!(err.to_string().contains("--dc-guard 24"), "{}", err);
+    }
+}